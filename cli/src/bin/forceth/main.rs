@@ -1,15 +1,17 @@
 use std::panic::PanicInfo;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use common::utils::hex_str_to_bytes;
 use dirs::home_dir;
 use env_logger::Builder;
-use eyre::Result;
+use eyre::{eyre, Result};
 
 use client::ClientBuilder;
-use config::{CliConfig, Config};
+use config::{CliConfig, Config, StorageBackend};
+use forcerelay::verifier::{self, WitnessVerification};
 use log::{debug, warn, LevelFilter};
 
 #[tokio::main]
@@ -27,7 +29,15 @@ async fn main() -> Result<()> {
         debug!("raise the soft open file descriptor resource limit to the hard limit (={limit}).");
     }
 
-    let config = get_config();
+    let cli = Cli::parse();
+    if let Some(Command::Verify(args)) = &cli.command {
+        return verify_offline(args);
+    }
+
+    let config = get_config(&cli);
+    if let Some(Command::ExportSnapshot(args)) = &cli.command {
+        return export_snapshot(&config, args);
+    }
     let (client, shutdown_notifier) = ClientBuilder::new().config(config).build()?;
     let client = Arc::new(Mutex::new(client));
 
@@ -64,16 +74,82 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn get_config() -> Config {
-    let cli = Cli::parse();
+fn get_config(cli: &Cli) -> Config {
     let config_path = home_dir().unwrap().join(".forceth/config.toml");
     let cli_config = cli.as_cli_config();
 
     Config::from_file(&config_path, &cli.network, &cli_config)
 }
 
+fn verify_offline(args: &VerifyArgs) -> Result<()> {
+    let client = verifier::parse_client(&read_hex_file(&args.client)?)?;
+    println!("client: {client}");
+    let verifications = match (&args.tx, &args.proof, &args.payload) {
+        (Some(tx), _, _) => {
+            verifier::verify_transaction_json(&client, &std::fs::read_to_string(tx)?)?
+        }
+        (None, Some(proof), Some(payload)) => {
+            let proof = read_hex_file(proof)?;
+            let payload = read_hex_file(payload)?;
+            vec![WitnessVerification {
+                index: 0,
+                result: verifier::verify_proof(&client, &proof, &payload),
+            }]
+        }
+        _ => {
+            return Err(eyre!(
+                "either --tx or both --proof and --payload are required"
+            ))
+        }
+    };
+    let mut failures = 0;
+    for verification in verifications {
+        match verification.result {
+            Ok(()) => println!("witness #{}: ok", verification.index),
+            Err(err) => {
+                println!("witness #{}: {err}", verification.index);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(eyre!("{failures} witness(es) failed to verify"));
+    }
+    Ok(())
+}
+
+fn export_snapshot(config: &Config, args: &ExportSnapshotArgs) -> Result<()> {
+    if config.storage_backend == StorageBackend::Memory {
+        return Err(eyre!(
+            "no headers to export from the memory storage backend"
+        ));
+    }
+    let client_id = args
+        .client_id
+        .as_deref()
+        .unwrap_or(&config.ckb_ibc_client_id);
+    if !config.ibc_client_ids().iter().any(|id| id == client_id) {
+        return Err(eyre!("unknown ibc client id {client_id}"));
+    }
+    let storage_path = config.ibc_client_storage_path(client_id);
+    let (base_slot, tip_slot) =
+        consensus::export_snapshot(&storage_path, &args.output, args.with_updates)?;
+    println!(
+        "exported headers [{base_slot}, {tip_slot}] of {client_id} to {}",
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn read_hex_file(path: &Path) -> Result<Vec<u8>> {
+    let content = std::fs::read_to_string(path)?;
+    hex_str_to_bytes(content.trim())
+}
+
 #[derive(Parser)]
 struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
     #[clap(short, long, default_value = "mainnet")]
     network: String,
     #[clap(short = 'p', long, env)]
@@ -84,6 +160,24 @@ struct Cli {
     execution_rpc: Option<String>,
     #[clap(short, long, env)]
     consensus_rpc: Option<String>,
+    #[clap(long, env)]
+    ckb_rpc: Option<String>,
+    #[clap(long, env)]
+    ckb_indexer_rpc: Option<String>,
+    #[clap(long, env)]
+    ckb_rpc_timeout_ms: Option<u64>,
+    #[clap(long, env)]
+    ckb_indexer_rpc_timeout_ms: Option<u64>,
+    #[clap(long, env)]
+    lightclient_contract_typeargs: Option<String>,
+    #[clap(long, env)]
+    lightclient_binary_typeargs: Option<String>,
+    #[clap(long, env)]
+    ckb_ibc_client_id: Option<String>,
+    #[clap(long, env)]
+    storage_path: Option<PathBuf>,
+    #[clap(long, env)]
+    snapshot: Option<PathBuf>,
     #[clap(short = 'f', long, env)]
     fallback: Option<String>,
     #[clap(short = 'l', long, env)]
@@ -92,17 +186,71 @@ struct Cli {
     strict_checkpoint_age: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Verify the proofs of a CKB verification transaction offline, without running a node
+    Verify(VerifyArgs),
+    /// Export the stored headers into a snapshot file, the verifier must be stopped
+    ExportSnapshot(ExportSnapshotArgs),
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// The file of hex encoded lightclient cell data, i.e. the serialized client
+    #[clap(long)]
+    client: PathBuf,
+    /// The file of the CKB transaction in JSON
+    #[clap(long, conflicts_with_all = &["proof", "payload"])]
+    tx: Option<PathBuf>,
+    /// The file of hex encoded `TransactionProof`
+    #[clap(long, requires = "payload")]
+    proof: Option<PathBuf>,
+    /// The file of hex encoded `TransactionPayload`
+    #[clap(long, requires = "proof")]
+    payload: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ExportSnapshotArgs {
+    /// The snapshot file to write
+    #[clap(long)]
+    output: PathBuf,
+    /// The ibc client id of the light client to export, the primary one if absent
+    #[clap(long)]
+    client_id: Option<String>,
+    /// Export the finalized updates as well, which are checked against the headers on import
+    #[clap(long)]
+    with_updates: bool,
+}
+
 impl Cli {
     fn as_cli_config(&self) -> CliConfig {
         let checkpoint = self
             .checkpoint
             .as_ref()
             .map(|value| hex_str_to_bytes(value).expect("invalid checkpoint"));
+        let lightclient_contract_typeargs = self
+            .lightclient_contract_typeargs
+            .as_ref()
+            .map(|value| hex_str_to_bytes(value).expect("invalid lightclient contract typeargs"));
+        let lightclient_binary_typeargs = self
+            .lightclient_binary_typeargs
+            .as_ref()
+            .map(|value| hex_str_to_bytes(value).expect("invalid lightclient binary typeargs"));
 
         CliConfig {
             checkpoint,
             execution_rpc: self.execution_rpc.clone(),
             consensus_rpc: self.consensus_rpc.clone(),
+            ckb_rpc: self.ckb_rpc.clone(),
+            ckb_indexer_rpc: self.ckb_indexer_rpc.clone(),
+            ckb_rpc_timeout_ms: self.ckb_rpc_timeout_ms,
+            ckb_indexer_rpc_timeout_ms: self.ckb_indexer_rpc_timeout_ms,
+            lightclient_contract_typeargs,
+            lightclient_binary_typeargs,
+            ckb_ibc_client_id: self.ckb_ibc_client_id.clone(),
+            storage_path: self.storage_path.clone(),
+            snapshot: self.snapshot.clone(),
             rpc_port: self.rpc_port,
             fallback: self.fallback.clone(),
             load_external_fallback: self.load_external_fallback,
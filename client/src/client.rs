@@ -7,7 +7,10 @@ use ethers::types::{Filter, Log, Transaction, TransactionReceipt, H256};
 use eyre::{eyre, Result};
 
 use common::types::BlockTag;
-use config::Config;
+use config::{
+    BackfillConfig, CkbDryRunConfig, CkbLightClientConfig, CkbRpcRetryConfig, CkbSignerConfig,
+    Config, StorageBackend, WatchConfig,
+};
 use consensus::types::Header;
 use execution::types::{CallOpts, ExecutionBlock};
 use log::error;
@@ -23,14 +26,28 @@ use crate::rpc::Rpc;
 pub struct ClientBuilder {
     network: Option<Network>,
     consensus_rpc: Option<String>,
+    extra_consensus_rpcs: Option<Vec<String>>,
+    consensus_rpc_quorum: Option<usize>,
+    backfill: Option<BackfillConfig>,
     execution_rpc: Option<String>,
     ckb_rpc: Option<String>,
+    ckb_indexer_rpc: Option<String>,
+    ckb_rpc_timeout_ms: Option<u64>,
+    ckb_indexer_rpc_timeout_ms: Option<u64>,
+    ckb_rpc_retry: Option<CkbRpcRetryConfig>,
+    ckb_light_client: Option<CkbLightClientConfig>,
     lightclient_contract_typeargs: Option<Vec<u8>>,
     lightclient_binary_typeargs: Option<Vec<u8>>,
     ibc_client_id: Option<String>,
+    extra_ibc_client_ids: Option<Vec<String>>,
+    ckb_signer: Option<CkbSignerConfig>,
+    ckb_dry_run: Option<CkbDryRunConfig>,
+    watch: Option<WatchConfig>,
     checkpoint: Option<Vec<u8>>,
     rpc_port: Option<u16>,
     storage_path: Option<PathBuf>,
+    storage_backend: Option<StorageBackend>,
+    snapshot: Option<PathBuf>,
     config: Option<Config>,
     fallback: Option<String>,
     load_external_fallback: bool,
@@ -52,6 +69,22 @@ impl ClientBuilder {
         self
     }
 
+    pub fn extra_consensus_rpcs(mut self, consensus_rpcs: &[&str]) -> Self {
+        self.extra_consensus_rpcs =
+            Some(consensus_rpcs.iter().map(|rpc| rpc.to_string()).collect());
+        self
+    }
+
+    pub fn consensus_rpc_quorum(mut self, quorum: usize) -> Self {
+        self.consensus_rpc_quorum = Some(quorum);
+        self
+    }
+
+    pub fn backfill(mut self, backfill: BackfillConfig) -> Self {
+        self.backfill = Some(backfill);
+        self
+    }
+
     pub fn execution_rpc(mut self, execution_rpc: &str) -> Self {
         self.execution_rpc = Some(execution_rpc.to_string());
         self
@@ -62,6 +95,31 @@ impl ClientBuilder {
         self
     }
 
+    pub fn ckb_indexer_rpc(mut self, ckb_indexer_rpc: &str) -> Self {
+        self.ckb_indexer_rpc = Some(ckb_indexer_rpc.to_string());
+        self
+    }
+
+    pub fn ckb_rpc_timeout_ms(mut self, timeout: u64) -> Self {
+        self.ckb_rpc_timeout_ms = Some(timeout);
+        self
+    }
+
+    pub fn ckb_indexer_rpc_timeout_ms(mut self, timeout: u64) -> Self {
+        self.ckb_indexer_rpc_timeout_ms = Some(timeout);
+        self
+    }
+
+    pub fn ckb_rpc_retry(mut self, retry: CkbRpcRetryConfig) -> Self {
+        self.ckb_rpc_retry = Some(retry);
+        self
+    }
+
+    pub fn ckb_light_client(mut self, light_client: CkbLightClientConfig) -> Self {
+        self.ckb_light_client = Some(light_client);
+        self
+    }
+
     pub fn lightclient_contract_typeargs(mut self, typeargs: &str) -> Self {
         let typeargs = hex::decode(typeargs.strip_prefix("0x").unwrap_or(typeargs))
             .expect("cannot parse lightclient");
@@ -81,6 +139,26 @@ impl ClientBuilder {
         self
     }
 
+    pub fn extra_ibc_client_ids(mut self, client_ids: &[&str]) -> Self {
+        self.extra_ibc_client_ids = Some(client_ids.iter().map(|id| id.to_string()).collect());
+        self
+    }
+
+    pub fn ckb_signer(mut self, signer: CkbSignerConfig) -> Self {
+        self.ckb_signer = Some(signer);
+        self
+    }
+
+    pub fn ckb_dry_run(mut self, dry_run: CkbDryRunConfig) -> Self {
+        self.ckb_dry_run = Some(dry_run);
+        self
+    }
+
+    pub fn watch(mut self, watch: WatchConfig) -> Self {
+        self.watch = Some(watch);
+        self
+    }
+
     pub fn checkpoint(mut self, checkpoint: &str) -> Self {
         let checkpoint = hex::decode(checkpoint.strip_prefix("0x").unwrap_or(checkpoint))
             .expect("cannot parse checkpoint");
@@ -98,6 +176,16 @@ impl ClientBuilder {
         self
     }
 
+    pub fn storage_backend(mut self, storage_backend: StorageBackend) -> Self {
+        self.storage_backend = Some(storage_backend);
+        self
+    }
+
+    pub fn snapshot(mut self, snapshot: PathBuf) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
     pub fn config(mut self, config: Config) -> Self {
         self.config = Some(config);
         self
@@ -137,6 +225,30 @@ impl ClientBuilder {
                 .clone()
         });
 
+        let extra_consensus_rpcs = if let Some(consensus_rpcs) = self.extra_consensus_rpcs {
+            consensus_rpcs
+        } else if let Some(config) = &self.config {
+            config.consensus_rpcs.clone()
+        } else {
+            vec![]
+        };
+
+        let consensus_rpc_quorum = if self.consensus_rpc_quorum.is_some() {
+            self.consensus_rpc_quorum
+        } else if let Some(config) = &self.config {
+            config.consensus_rpc_quorum
+        } else {
+            None
+        };
+
+        let backfill = if let Some(backfill) = self.backfill {
+            backfill
+        } else if let Some(config) = &self.config {
+            config.backfill.clone()
+        } else {
+            BackfillConfig::default()
+        };
+
         let execution_rpc = self.execution_rpc.unwrap_or_else(|| {
             self.config
                 .as_ref()
@@ -153,6 +265,46 @@ impl ClientBuilder {
                 .clone()
         });
 
+        let ckb_indexer_rpc = if self.ckb_indexer_rpc.is_some() {
+            self.ckb_indexer_rpc
+        } else if let Some(config) = &self.config {
+            config.ckb_indexer_rpc.clone()
+        } else {
+            None
+        };
+
+        let ckb_rpc_timeout_ms = if self.ckb_rpc_timeout_ms.is_some() {
+            self.ckb_rpc_timeout_ms
+        } else if let Some(config) = &self.config {
+            config.ckb_rpc_timeout_ms
+        } else {
+            None
+        };
+
+        let ckb_indexer_rpc_timeout_ms = if self.ckb_indexer_rpc_timeout_ms.is_some() {
+            self.ckb_indexer_rpc_timeout_ms
+        } else if let Some(config) = &self.config {
+            config.ckb_indexer_rpc_timeout_ms
+        } else {
+            None
+        };
+
+        let ckb_rpc_retry = if let Some(retry) = self.ckb_rpc_retry {
+            retry
+        } else if let Some(config) = &self.config {
+            config.ckb_rpc_retry.clone()
+        } else {
+            CkbRpcRetryConfig::default()
+        };
+
+        let ckb_light_client = if self.ckb_light_client.is_some() {
+            self.ckb_light_client
+        } else if let Some(config) = &self.config {
+            config.ckb_light_client.clone()
+        } else {
+            None
+        };
+
         let lightclient_contract_typeargs =
             if let Some(typeargs) = self.lightclient_contract_typeargs {
                 typeargs
@@ -178,6 +330,38 @@ impl ClientBuilder {
             String::new()
         };
 
+        let extra_client_ids = if let Some(client_ids) = self.extra_ibc_client_ids {
+            client_ids
+        } else if let Some(config) = &self.config {
+            config.ckb_ibc_client_ids.clone()
+        } else {
+            vec![]
+        };
+
+        let ckb_signer = if self.ckb_signer.is_some() {
+            self.ckb_signer
+        } else if let Some(config) = &self.config {
+            config.ckb_signer.clone()
+        } else {
+            None
+        };
+
+        let ckb_dry_run = if self.ckb_dry_run.is_some() {
+            self.ckb_dry_run
+        } else if let Some(config) = &self.config {
+            config.ckb_dry_run.clone()
+        } else {
+            None
+        };
+
+        let watch = if self.watch.is_some() {
+            self.watch
+        } else if let Some(config) = &self.config {
+            config.watch.clone()
+        } else {
+            None
+        };
+
         let checkpoint = if let Some(checkpoint) = self.checkpoint {
             checkpoint
         } else if let Some(config) = &self.config {
@@ -202,6 +386,22 @@ impl ClientBuilder {
                 .clone()
         });
 
+        let storage_backend = if let Some(storage_backend) = self.storage_backend {
+            storage_backend
+        } else if let Some(config) = &self.config {
+            config.storage_backend
+        } else {
+            StorageBackend::default()
+        };
+
+        let snapshot = if self.snapshot.is_some() {
+            self.snapshot
+        } else if let Some(config) = &self.config {
+            config.snapshot.clone()
+        } else {
+            None
+        };
+
         let fallback = if self.fallback.is_some() {
             self.fallback
         } else if let Some(config) = &self.config {
@@ -224,14 +424,28 @@ impl ClientBuilder {
 
         let config = Config {
             consensus_rpc,
+            consensus_rpcs: extra_consensus_rpcs,
+            consensus_rpc_quorum,
+            backfill,
             execution_rpc,
             ckb_rpc,
+            ckb_indexer_rpc,
+            ckb_rpc_timeout_ms,
+            ckb_indexer_rpc_timeout_ms,
+            ckb_rpc_retry,
+            ckb_light_client,
             lightclient_contract_typeargs,
             lightclient_binary_typeargs,
             ckb_ibc_client_id: client_id,
+            ckb_ibc_client_ids: extra_client_ids,
+            ckb_signer,
+            ckb_dry_run,
+            watch,
             checkpoint,
             rpc_port,
             storage_path,
+            storage_backend,
+            snapshot,
             chain: base_config.chain,
             forks: base_config.forks,
             max_checkpoint_age: base_config.max_checkpoint_age,
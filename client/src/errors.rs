@@ -1,6 +1,7 @@
 use common::errors::BlockNotFoundError;
 use execution::errors::EvmError;
 use eyre::Report;
+use forcerelay::errors::BeaconBlockMismatch;
 use thiserror::Error;
 
 /// Errors that can occur during Node calls
@@ -30,6 +31,9 @@ pub enum NodeError {
     #[error("forcerelay error: {0}")]
     ForcerelayError(Report),
 
+    #[error(transparent)]
+    BeaconBlockMismatch(#[from] BeaconBlockMismatch),
+
     #[error(transparent)]
     BlockNotFoundError(#[from] BlockNotFoundError),
 
@@ -1,7 +1,12 @@
 use ckb_jsonrpc_types::Transaction as CkbTransaction;
+use ckb_types::core::TransactionView;
 use consensus::rpc::ConsensusRpc;
-use forcerelay::{rpc::RpcClient, CachedBeaconBlockMainnet};
+use forcerelay::{
+    rpc::{CkbRpcBackend, LightClientRpcClient, RetryConfig, RpcClient},
+    CachedBeaconBlockMainnet,
+};
 use futures::TryFutureExt;
+use serde::Serialize;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,24 +18,69 @@ use eyre::{eyre, Result};
 use common::errors::BlockNotFoundError;
 use common::types::BlockTag;
 use config::Config;
-use consensus::rpc::nimbus_rpc::NimbusRpc;
+use consensus::rpc::{multi_rpc::MultiRpc, nimbus_rpc::NimbusRpc};
 use consensus::types::{ExecutionPayload, Header};
-use consensus::ConsensusClient;
+use consensus::{ConsensusClient, IntegrityChecker, StorageIntegrity};
 use execution::evm::Evm;
 use execution::rpc::{http_rpc::HttpRpc, ExecutionRpc};
 use execution::types::{CallOpts, ExecutionBlock};
 use execution::ExecutionClient;
-use forcerelay::forcerelay::ForcerelayClient;
-use log::info;
+use forcerelay::completer::TransactionCompleter;
+use forcerelay::dry_run::DryRunner;
+use forcerelay::errors::BeaconBlockMismatch;
+use forcerelay::forcerelay::{ForcerelayClient, OnChainClient};
+use forcerelay::tracker::{SubmitStatus, SubmittedTransaction, TrackStep};
+use forcerelay::util::check_beacon_block;
+use forcerelay::watcher::{LogWatcher, WatchedTransaction};
+use log::{info, warn};
 
 use crate::errors::NodeError;
 
 const HISTORY_SIZE: usize = 64;
 const CACHED_RECEIPTS_SIZE: usize = 512;
 const CACHED_BLOCK_SIZE: usize = 64;
+const MAX_BATCH_TRANSACTIONS: usize = 32;
+const MAX_WATCH_SLOTS_PER_ADVANCE: u64 = 64;
+
+/// The ckb transaction which verifies the ethereum transaction that emits a log, with the
+/// position of the log inside the verified receipt.
+#[derive(Debug, Clone, Serialize)]
+pub struct CkbTransactionForLog {
+    pub transaction: CkbTransaction,
+    pub eth_transaction_hash: H256,
+    pub log_position: u64,
+}
+
+/// The ckb transaction which passes the dry run, with the consumed cycles.
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunCkbTransaction {
+    pub transaction: CkbTransaction,
+    pub cycles: u64,
+}
+
+/// The integrity of the stored headers of a light client against its on-chain client.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStorageIntegrity {
+    pub client_id: String,
+    #[serde(flatten)]
+    pub integrity: StorageIntegrity,
+}
+
+// the assembled ckb transaction, with the cycles consumed in the dry run if it's enabled
+type AssembledTransaction = (TransactionView, Option<u64>);
+
+type NodeConsensusRpc = MultiRpc<NimbusRpc>;
+
+/// An on-chain light client cell served besides the primary one, which keeps its own native
+/// MMR storage aligned to the cell.
+struct ExtraLightClient {
+    client_id: String,
+    consensus: ConsensusClient<NodeConsensusRpc>,
+    forcerelay: ForcerelayClient<CkbRpcBackend>,
+}
 
 pub struct Node {
-    pub consensus: ConsensusClient<NimbusRpc>,
+    pub consensus: ConsensusClient<NodeConsensusRpc>,
     pub execution: Arc<ExecutionClient<HttpRpc>>,
     pub config: Arc<Config>,
     payloads: BTreeMap<u64, ExecutionPayload>,
@@ -38,27 +88,53 @@ pub struct Node {
     block_number_slots: BTreeMap<u64, u64>,
     cached_block_receipts: BTreeMap<u64, Vec<TransactionReceipt>>,
     cached_beacon_blocks: BTreeMap<u64, CachedBeaconBlockMainnet>,
-    forcerelay: ForcerelayClient<RpcClient>,
+    // the blocks which are not covered by the stored headers of any light client yet, they are
+    // checked again before being moved into `cached_beacon_blocks`
+    unchecked_beacon_blocks: BTreeMap<u64, CachedBeaconBlockMainnet>,
+    forcerelay: ForcerelayClient<CkbRpcBackend>,
+    extra_clients: Vec<ExtraLightClient>,
+    watcher: Option<LogWatcher>,
 }
 
 impl Node {
     pub fn new(config: Arc<Config>) -> Result<Self, NodeError> {
-        let consensus_rpc = &config.consensus_rpc;
         let checkpoint_hash = &config.checkpoint;
         let execution_rpc = &config.execution_rpc;
         let ckb_rpc = &config.ckb_rpc;
-        let contract_typeargs = &config.lightclient_contract_typeargs;
-        let binary_typeargs = &config.lightclient_binary_typeargs;
-        let client_id = &config.ckb_ibc_client_id;
 
-        let consensus = ConsensusClient::new(consensus_rpc, checkpoint_hash, config.clone())
-            .map_err(NodeError::ConsensusClientCreationError)?;
+        let consensus = ConsensusClient::with_rpc(
+            Self::new_consensus_rpc(&config)?,
+            checkpoint_hash,
+            config.clone(),
+            &config.storage_path,
+        )
+        .map_err(NodeError::ConsensusClientCreationError)?;
         let execution = Arc::new(
             ExecutionClient::new(execution_rpc).map_err(NodeError::ExecutionClientCreationError)?,
         );
-
-        let rpc = RpcClient::new(ckb_rpc, ckb_rpc);
-        let forcerelay = ForcerelayClient::new(rpc, contract_typeargs, binary_typeargs, client_id);
+        let forcerelay = Self::new_forcerelay(&config, &config.ckb_ibc_client_id)?;
+
+        let mut extra_clients = vec![];
+        for client_id in config.ibc_client_ids().into_iter().skip(1) {
+            let storage_path = config.ibc_client_storage_path(&client_id);
+            let consensus = ConsensusClient::with_rpc(
+                Self::new_consensus_rpc(&config)?,
+                checkpoint_hash,
+                config.clone(),
+                &storage_path,
+            )
+            .map_err(NodeError::ConsensusClientCreationError)?;
+            let forcerelay = Self::new_forcerelay(&config, &client_id)?;
+            extra_clients.push(ExtraLightClient {
+                client_id,
+                consensus,
+                forcerelay,
+            });
+        }
+        let watcher = config
+            .watch
+            .as_ref()
+            .map(|watch| LogWatcher::new(watch.addresses.clone(), watch.topics.clone()));
 
         Ok(Node {
             consensus,
@@ -69,33 +145,138 @@ impl Node {
             block_number_slots: BTreeMap::new(),
             cached_block_receipts: BTreeMap::new(),
             cached_beacon_blocks: BTreeMap::new(),
+            unchecked_beacon_blocks: BTreeMap::new(),
             forcerelay,
+            extra_clients,
+            watcher,
         })
     }
 
-    pub async fn print_status_log(&self, onchain_log: Option<String>) -> Result<(), NodeError> {
-        let onchain_log = match onchain_log {
-            Some(log) => log,
+    fn new_consensus_rpc(config: &Config) -> Result<NodeConsensusRpc, NodeError> {
+        MultiRpc::from_urls(&config.consensus_rpc_urls())
+            .and_then(|rpc| rpc.with_quorum(config.consensus_rpc_quorum.unwrap_or(1)))
+            .map_err(NodeError::ConsensusClientCreationError)
+    }
+
+    fn new_forcerelay(
+        config: &Config,
+        client_id: &str,
+    ) -> Result<ForcerelayClient<CkbRpcBackend>, NodeError> {
+        let retry = RetryConfig {
+            max_retries: config.ckb_rpc_retry.max_retries,
+            initial_backoff: Duration::from_millis(config.ckb_rpc_retry.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.ckb_rpc_retry.max_backoff_ms),
+        };
+        let ckb_timeout = config.ckb_rpc_timeout_ms.map(Duration::from_millis);
+        let indexer_timeout = config.ckb_indexer_rpc_timeout_ms.map(Duration::from_millis);
+        let completer = config
+            .ckb_signer
+            .as_ref()
+            .map(|signer| {
+                TransactionCompleter::from_key_file(
+                    &signer.address,
+                    &signer.key_path,
+                    signer.fee_rate,
+                )
+            })
+            .transpose()
+            .map_err(NodeError::ForcerelayError)?;
+        let rpc = match &config.ckb_light_client {
+            Some(light_client) => {
+                LightClientRpcClient::new(&config.ckb_rpc, light_client.start_block_number).map(
+                    |mut rpc| {
+                        if let Some(completer) = &completer {
+                            rpc = rpc.with_script_start_block(
+                                completer.lock_script().clone(),
+                                light_client.signer_start_block_number,
+                            );
+                        }
+                        CkbRpcBackend::LightClient(rpc.with_retry(retry).with_timeout(ckb_timeout))
+                    },
+                )
+            }
             None => {
-                let (client, _) = self
-                    .forcerelay
-                    .onchain_client()
-                    .map_err(NodeError::ForcerelayError)
-                    .await?;
-                client.to_string()
+                let indexer_rpc = config.ckb_indexer_rpc.as_ref().unwrap_or(&config.ckb_rpc);
+                RpcClient::new(&config.ckb_rpc, indexer_rpc).map(|rpc| {
+                    CkbRpcBackend::Full(
+                        rpc.with_retry(retry)
+                            .with_timeouts(ckb_timeout, indexer_timeout),
+                    )
+                })
+            }
+        }
+        .map_err(|err| NodeError::ForcerelayError(err.into()))?;
+        let mut forcerelay = ForcerelayClient::new(
+            rpc,
+            &config.lightclient_contract_typeargs,
+            &config.lightclient_binary_typeargs,
+            client_id,
+        );
+        if let Some(completer) = completer {
+            forcerelay = forcerelay.with_completer(completer);
+        }
+        if let Some(dry_run) = &config.ckb_dry_run {
+            let dry_runner = DryRunner::from_file(&dry_run.checker_path, dry_run.max_cycles)
+                .map_err(NodeError::ForcerelayError)?;
+            forcerelay = forcerelay.with_dry_runner(dry_runner);
+        }
+        Ok(forcerelay)
+    }
+
+    // the primary light client comes first
+    fn light_clients(
+        &self,
+    ) -> impl Iterator<
+        Item = (
+            &str,
+            &ConsensusClient<NodeConsensusRpc>,
+            &ForcerelayClient<CkbRpcBackend>,
+        ),
+    > {
+        std::iter::once((
+            self.config.ckb_ibc_client_id.as_str(),
+            &self.consensus,
+            &self.forcerelay,
+        ))
+        .chain(self.extra_clients.iter().map(|extra| {
+            (
+                extra.client_id.as_str(),
+                &extra.consensus,
+                &extra.forcerelay,
+            )
+        }))
+    }
+
+    pub async fn print_status_log(&self, onchain_log: Option<String>) -> Result<(), NodeError> {
+        for (index, (client_id, consensus, forcerelay)) in self.light_clients().enumerate() {
+            let onchain_log = match (index, &onchain_log) {
+                (0, Some(log)) => log.clone(),
+                _ => {
+                    let (client, _) = forcerelay
+                        .onchain_client()
+                        .map_err(NodeError::ForcerelayError)
+                        .await?;
+                    client.to_string()
+                }
+            };
+            let mut log =
+                format!("[STATUS] {client_id} onchain client: {onchain_log}, native client: ");
+            let slot_range = consensus
+                .storage_slot_range()
+                .map_err(NodeError::ConsensusSyncError)?;
+            if let (Some(base_slot), Some(tip_slot)) = slot_range {
+                log += &format!("[{base_slot}, {tip_slot}]");
+            } else {
+                log += "None";
+            }
+            log += &format!(", consensus rpc: {}", consensus.rpc.health_log());
+            if let Some(forced_slot) = consensus.forced_finalized_slot() {
+                log += &format!(", finalized header is force updated at slot {forced_slot}");
+                warn!("{log}");
+            } else {
+                info!("{log}");
             }
-        };
-        let mut log = format!("[STATUS] onchain client: {onchain_log}, native client: ");
-        let slot_range = self
-            .consensus
-            .storage_slot_range()
-            .map_err(NodeError::ConsensusSyncError)?;
-        if let (Some(base_slot), Some(tip_slot)) = slot_range {
-            log += &format!("[{base_slot}, {tip_slot}]");
-        } else {
-            log += "None";
         }
-        info!("{log}");
         Ok(())
     }
 
@@ -106,17 +287,52 @@ impl Node {
             .await
             .map_err(NodeError::ForcerelayError)?;
         self.print_status_log(Some(client.to_string())).await?;
-        self.consensus
+        let mut slots = vec![];
+        slots.push(Self::sync_light_client(&mut self.consensus, &mut self.forcerelay).await?);
+        for extra in &mut self.extra_clients {
+            slots.push(Self::sync_light_client(&mut extra.consensus, &mut extra.forcerelay).await?);
+        }
+        for (minimal_slot, maximal_slot) in slots {
+            self.update_block_number_slots(minimal_slot).await?;
+            self.update_block_number_slots(maximal_slot).await?;
+        }
+        self.update_payloads().await
+    }
+
+    // sync the native storage to the on-chain client, returns the slot range of the client
+    async fn sync_light_client(
+        consensus: &mut ConsensusClient<NodeConsensusRpc>,
+        forcerelay: &mut ForcerelayClient<CkbRpcBackend>,
+    ) -> Result<(u64, u64), NodeError> {
+        let (client, _) = forcerelay
+            .onchain_client()
+            .await
+            .map_err(NodeError::ForcerelayError)?;
+        if let Some(path) = consensus.config.snapshot.clone() {
+            match consensus.import_snapshot(&path, &client) {
+                Ok(true) => info!(
+                    "imported headers [{}, {}] from snapshot {}",
+                    client.minimal_slot,
+                    client.maximal_slot,
+                    path.display()
+                ),
+                Ok(false) => {}
+                Err(err) => warn!("snapshot {} is rejected: {err}", path.display()),
+            }
+        }
+        consensus
             .sync(client.minimal_slot)
             .await
             .map_err(NodeError::ConsensusSyncError)?;
-        self.forcerelay
+        consensus
+            .align_with_onchain_client(&client)
+            .await
+            .map_err(NodeError::ConsensusSyncError)?;
+        forcerelay
             .update_assembler_celldep()
             .await
             .map_err(NodeError::ForcerelayError)?;
-        self.update_block_number_slots(client.minimal_slot).await?;
-        self.update_block_number_slots(client.maximal_slot).await?;
-        self.update_payloads().await
+        Ok((client.minimal_slot, client.maximal_slot))
     }
 
     pub async fn advance(&mut self) -> Result<(), NodeError> {
@@ -125,16 +341,40 @@ impl Node {
             .onchain_client()
             .await
             .map_err(NodeError::ForcerelayError)?;
+        let realigned = self
+            .consensus
+            .align_with_onchain_client(&client)
+            .await
+            .map_err(NodeError::ConsensusAdvanceError)?;
         let new_finality = self
             .consensus
             .advance()
             .await
             .map_err(NodeError::ConsensusAdvanceError)?;
-        if new_finality {
+        if new_finality || realigned {
             self.print_status_log(Some(client.to_string())).await?;
         }
+        Self::prune_cached_proofs(&self.consensus, &self.forcerelay, &client);
+        let mut minimal_slots = vec![client.minimal_slot];
+        let mut maximal_slot = client.maximal_slot;
+        // an extra light client never stalls the primary one, its error is retried in the next
+        // advance
+        for extra in &mut self.extra_clients {
+            match Self::advance_extra_client(extra).await {
+                Ok(client) => {
+                    minimal_slots.push(client.minimal_slot);
+                    maximal_slot = maximal_slot.max(client.maximal_slot);
+                }
+                Err(err) => warn!("advance light client {} error: {err}", extra.client_id),
+            }
+        }
+        let watch_minimal_slot = minimal_slots.iter().copied().min().unwrap_or_default();
+        // the minimal slot may be moved by the relayer
+        for minimal_slot in minimal_slots {
+            self.update_block_number_slots(minimal_slot).await?;
+        }
         if let Some((_, last_maximal_slot)) = self.block_number_slots.last_key_value() {
-            for slot in (*last_maximal_slot + 1)..=client.maximal_slot {
+            for slot in (*last_maximal_slot + 1)..=maximal_slot {
                 if let Some(block_number) = self.update_block_number_slots(slot).await? {
                     self.cache_block_receipts(block_number).await?;
                 }
@@ -145,9 +385,55 @@ impl Node {
             .update_assembler_celldep()
             .await
             .map_err(NodeError::ForcerelayError)?;
+        if let Err(err) = self.track_submitted_ckb_transactions().await {
+            warn!("track submitted ckb transactions error: {err}");
+        }
+        if let Err(err) = self.watch_new_slots(watch_minimal_slot, maximal_slot).await {
+            warn!("watch new slots error: {err}");
+        }
         self.update_payloads().await
     }
 
+    async fn advance_extra_client(
+        extra: &mut ExtraLightClient,
+    ) -> Result<OnChainClient, NodeError> {
+        let (client, _) = extra
+            .forcerelay
+            .onchain_client()
+            .await
+            .map_err(NodeError::ForcerelayError)?;
+        extra
+            .consensus
+            .align_with_onchain_client(&client)
+            .await
+            .map_err(NodeError::ConsensusAdvanceError)?;
+        extra
+            .consensus
+            .advance()
+            .await
+            .map_err(NodeError::ConsensusAdvanceError)?;
+        Self::prune_cached_proofs(&extra.consensus, &extra.forcerelay, &client);
+        extra
+            .forcerelay
+            .update_assembler_celldep()
+            .await
+            .map_err(NodeError::ForcerelayError)?;
+        Ok(client)
+    }
+
+    // the cached proofs are useless once the on-chain client is moved
+    fn prune_cached_proofs(
+        consensus: &ConsensusClient<NodeConsensusRpc>,
+        forcerelay: &ForcerelayClient<CkbRpcBackend>,
+        client: &OnChainClient,
+    ) {
+        match forcerelay.prune_cached_proofs(consensus, client) {
+            Ok(0) => {}
+            Ok(pruned) => info!("pruned {pruned} stale cached proofs"),
+            Err(err) => warn!("prune cached proofs error: {err}"),
+        }
+    }
+
     pub fn duration_until_next_update(&self) -> Duration {
         self.consensus
             .duration_until_next_update()
@@ -194,21 +480,53 @@ impl Node {
         if self.cached_beacon_blocks.contains_key(&slot) {
             return Ok(());
         }
-        let block = self
-            .consensus
-            .rpc
-            .get_block_ssz(slot)
-            .await
-            .map_err(NodeError::ForcerelayError)?;
-        if let Some(block) = block {
-            if self.cached_beacon_blocks.len() >= CACHED_BLOCK_SIZE {
-                self.cached_beacon_blocks.pop_first();
+        let block = match self.unchecked_beacon_blocks.remove(&slot) {
+            Some(block) => block,
+            None => match self
+                .consensus
+                .rpc
+                .get_block_ssz(slot)
+                .await
+                .map_err(NodeError::ForcerelayError)?
+            {
+                Some(block) => block.into(),
+                None => return Ok(()),
+            },
+        };
+        // never trust the consensus rpc, the block must match the finalized header
+        if !self.check_beacon_block(&block)? {
+            info!("beacon block of slot {slot} is out of the stored headers, checked later");
+            if self.unchecked_beacon_blocks.len() >= CACHED_BLOCK_SIZE {
+                self.unchecked_beacon_blocks.pop_first();
             }
-            self.cached_beacon_blocks.insert(slot, block.into());
+            self.unchecked_beacon_blocks.insert(slot, block);
+            return Ok(());
         }
+        if self.cached_beacon_blocks.len() >= CACHED_BLOCK_SIZE {
+            self.cached_beacon_blocks.pop_first();
+        }
+        self.cached_beacon_blocks.insert(slot, block);
         Ok(())
     }
 
+    // check the block against the stored headers of the light client which covers its slot,
+    // returns `false` if no light client covers it yet
+    fn check_beacon_block(&self, block: &CachedBeaconBlockMainnet) -> Result<bool, NodeError> {
+        for (_, consensus, _) in self.light_clients() {
+            match check_beacon_block(consensus.storage(), block) {
+                Ok(true) => return Ok(true),
+                Ok(false) => continue,
+                Err(err) => {
+                    return Err(match err.downcast::<BeaconBlockMismatch>() {
+                        Ok(mismatch) => mismatch.into(),
+                        Err(err) => NodeError::ForcerelayError(err),
+                    })
+                }
+            }
+        }
+        Ok(false)
+    }
+
     async fn get_slot_by_block_number(&mut self, block_number: u64) -> Result<u64, NodeError> {
         if let Some(slot) = self.block_number_slots.get(&block_number) {
             Ok(*slot)
@@ -267,8 +585,11 @@ impl Node {
             .insert(latest_payload.block_number(), latest_payload);
         self.payloads
             .insert(finalized_payload.block_number(), finalized_payload.clone());
-        self.finalized_payloads
-            .insert(finalized_payload.block_number(), finalized_payload);
+        // a force updated header is not finalized, so it's not served as the finalized block
+        if self.consensus.forced_finalized_slot().is_none() {
+            self.finalized_payloads
+                .insert(finalized_payload.block_number(), finalized_payload);
+        }
 
         while self.payloads.len() > HISTORY_SIZE {
             self.payloads.pop_first();
@@ -462,7 +783,378 @@ impl Node {
     pub async fn get_ckb_transaction_by_hash(
         &mut self,
         tx_hash: &H256,
+        client_id: Option<&str>,
+    ) -> Result<Option<CkbTransaction>> {
+        let assembled = self
+            .assemble_partial_ckb_transaction(tx_hash, client_id)
+            .await?;
+        Ok(assembled.map(|(tx, _)| tx.data().into()))
+    }
+
+    // assemble ckb transaction like `get_ckb_transaction_by_hash`, and return it with the cycles
+    // consumed in the dry run
+    pub async fn dry_run_ckb_transaction_by_hash(
+        &mut self,
+        tx_hash: &H256,
+        client_id: Option<&str>,
+    ) -> Result<Option<DryRunCkbTransaction>> {
+        if self.config.ckb_dry_run.is_none() {
+            return Err(eyre!("dry run mode is not enabled"));
+        }
+        let assembled = self
+            .assemble_partial_ckb_transaction(tx_hash, client_id)
+            .await?;
+        Ok(assembled.map(|(tx, cycles)| DryRunCkbTransaction {
+            transaction: tx.data().into(),
+            cycles: cycles.unwrap_or_default(),
+        }))
+    }
+
+    // assemble ckb transaction like `get_ckb_transaction_by_hash`, and then balance and sign it
+    // with the configured ckb signer
+    pub async fn get_complete_ckb_transaction_by_hash(
+        &mut self,
+        tx_hash: &H256,
+        client_id: Option<&str>,
     ) -> Result<Option<CkbTransaction>> {
+        let ckb_transaction = self
+            .assemble_complete_ckb_transaction(tx_hash, client_id)
+            .await?;
+        Ok(ckb_transaction.map(|tx| tx.data().into()))
+    }
+
+    // assemble the complete ckb transaction and send it to ckb, the submitted transaction is
+    // recorded in storage and tracked in `advance` until it's committed or rejected
+    pub async fn submit_ckb_transaction(
+        &mut self,
+        tx_hash: &H256,
+        client_id: Option<&str>,
+    ) -> Result<SubmittedTransaction> {
+        if let Some(record) = SubmittedTransaction::load(self.consensus.storage(), tx_hash)? {
+            if record.status != SubmitStatus::Rejected {
+                return Ok(record);
+            }
+        }
+        let ckb_transaction = self
+            .assemble_complete_ckb_transaction(tx_hash, client_id)
+            .await?
+            .ok_or_else(|| eyre!("cannot find transaction hash {tx_hash:#x}"))?;
+        // the record is saved before sending, so a transaction sent right before a crash is still
+        // tracked, and a failed sending marks it rejected so that it can be submitted again
+        let mut record = SubmittedTransaction::new(*tx_hash, &ckb_transaction);
+        record.client_id = client_id.map(ToOwned::to_owned);
+        record.save(self.consensus.storage())?;
+        if let Err(err) = self.forcerelay.send_tx(&ckb_transaction).await {
+            record.status = SubmitStatus::Rejected;
+            record.reason = Some(err.to_string());
+            record.save(self.consensus.storage())?;
+            return Err(err);
+        }
+        info!(
+            "submitted ckb transaction {:#x} for ethereum transaction {tx_hash:#x}",
+            record.ckb_tx_hash
+        );
+        Ok(record)
+    }
+
+    pub fn get_submitted_ckb_transaction(
+        &self,
+        tx_hash: &H256,
+    ) -> Result<Option<SubmittedTransaction>> {
+        SubmittedTransaction::load(self.consensus.storage(), tx_hash)
+    }
+
+    async fn assemble_complete_ckb_transaction(
+        &mut self,
+        tx_hash: &H256,
+        client_id: Option<&str>,
+    ) -> Result<Option<TransactionView>> {
+        let partial_transaction = match self
+            .assemble_partial_ckb_transaction(tx_hash, client_id)
+            .await?
+        {
+            Some((tx, _)) => tx,
+            None => return Ok(None),
+        };
+        let ckb_transaction = self.forcerelay.complete_tx(partial_transaction).await?;
+        Ok(Some(ckb_transaction))
+    }
+
+    // assemble from the cached proofs first, and then from the ethereum transaction
+    async fn assemble_partial_ckb_transaction(
+        &mut self,
+        tx_hash: &H256,
+        client_id: Option<&str>,
+    ) -> Result<Option<AssembledTransaction>> {
+        if let Some(assembled) = self
+            .assemble_cached_ckb_transaction(&[*tx_hash], client_id)
+            .await?
+        {
+            return Ok(Some(assembled));
+        }
+        let eth_transaction = match self.prepare_eth_transaction(tx_hash).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let assembled = self
+            .assemble_ckb_transaction(&[eth_transaction], client_id)
+            .await?;
+        Ok(Some(assembled))
+    }
+
+    // check the pending ckb transactions, the dropped ones are sent again and the ones which can
+    // never be committed, e.g. the lightclient cell is updated, are rebuilt against the new cells.
+    // an error of one record is logged and it's tracked again in the next pass
+    async fn track_submitted_ckb_transactions(&mut self) -> Result<()> {
+        let pending = SubmittedTransaction::load_pending(self.consensus.storage())?;
+        for mut record in pending {
+            let eth_tx_hash = record.eth_tx_hash;
+            let ckb_tx_hash = record.ckb_tx_hash.clone();
+            match self.forcerelay.track_pending_tx(&mut record).await {
+                Ok(TrackStep::Wait) => continue,
+                Ok(TrackStep::Save) => match record.status {
+                    SubmitStatus::Committed => info!(
+                        "ckb transaction {ckb_tx_hash:#x} for ethereum transaction {eth_tx_hash:#x} is committed"
+                    ),
+                    _ => warn!(
+                        "ckb transaction {ckb_tx_hash:#x} for ethereum transaction {eth_tx_hash:#x} is rejected: {}",
+                        record.reason.as_deref().unwrap_or_default()
+                    ),
+                },
+                Ok(TrackStep::Rebuild) => {
+                    if !self.rebuild_submitted_ckb_transaction(&mut record).await {
+                        continue;
+                    }
+                }
+                Err(err) => {
+                    warn!("track ckb transaction {ckb_tx_hash:#x} error: {err}, retry later");
+                    continue;
+                }
+            }
+            if let Err(err) = record.save(self.consensus.storage()) {
+                warn!("save submitted transaction for {eth_tx_hash:#x} error: {err}");
+            }
+        }
+        Ok(())
+    }
+
+    // rebuild the transaction of the record and send it, returns whether the record is changed.
+    // the record is changed before sending, so a failed sending is retried by the next pass
+    // instead of rebuilding again
+    async fn rebuild_submitted_ckb_transaction(
+        &mut self,
+        record: &mut SubmittedTransaction,
+    ) -> bool {
+        let eth_tx_hash = record.eth_tx_hash;
+        let client_id = record.client_id.clone();
+        let ckb_transaction = match self
+            .assemble_complete_ckb_transaction(&eth_tx_hash, client_id.as_deref())
+            .await
+        {
+            Ok(Some(tx)) => tx,
+            Ok(None) => {
+                record.status = SubmitStatus::Rejected;
+                record.reason = Some("ethereum transaction not found".to_owned());
+                return true;
+            }
+            Err(err) => {
+                // e.g. the storage is not aligned to the new lightclient cell yet
+                warn!("rebuild ckb transaction for {eth_tx_hash:#x} error: {err}, retry later");
+                return false;
+            }
+        };
+        record.rebuild_with(&ckb_transaction);
+        if let Err(err) = record.save(self.consensus.storage()) {
+            warn!("save submitted transaction for {eth_tx_hash:#x} error: {err}");
+        }
+        info!(
+            "rebuilt ckb transaction {:#x} for ethereum transaction {eth_tx_hash:#x}",
+            record.ckb_tx_hash
+        );
+        if let Err(err) = self.forcerelay.send_tx(&ckb_transaction).await {
+            warn!(
+                "send rebuilt ckb transaction {:#x} error: {err}, resend later",
+                record.ckb_tx_hash
+            );
+        }
+        true
+    }
+
+    // scan the receipts of the slots covered by the light clients since the last watched slot,
+    // and push the ckb transactions of the ethereum transactions with matched logs into the queue
+    async fn watch_new_slots(&mut self, minimal_slot: u64, maximal_slot: u64) -> Result<()> {
+        let watcher = match &self.watcher {
+            Some(watcher) => watcher.clone(),
+            None => return Ok(()),
+        };
+        // watching starts from the tip of the light clients for the first time
+        let mut start_slot = WatchedTransaction::last_watched_slot(self.consensus.storage())?
+            .map(|slot| slot + 1)
+            .unwrap_or(maximal_slot);
+        if start_slot < minimal_slot {
+            warn!(
+                "skip watching slots [{start_slot}, {minimal_slot}) which are not covered anymore"
+            );
+            start_slot = minimal_slot;
+        }
+        let end_slot = maximal_slot.min(start_slot + MAX_WATCH_SLOTS_PER_ADVANCE - 1);
+        for slot in start_slot..=end_slot {
+            let mut records = vec![];
+            if let Some(block_number) = self.update_block_number_slots(slot).await? {
+                self.cache_block_receipts(block_number).await?;
+                let matched =
+                    watcher.matched_transactions(&self.cached_block_receipts[&block_number]);
+                for (tx_hash, log_indexes) in matched {
+                    // fetching errors are transient, so the slot is watched again in the next
+                    // advance, while a transaction which fails to assemble is dead-lettered
+                    let eth_transaction = self
+                        .prepare_eth_transaction(&tx_hash)
+                        .await?
+                        .ok_or_else(|| eyre!("cannot find transaction hash {tx_hash:#x}"))?;
+                    let record = match self
+                        .assemble_ckb_transaction(&[eth_transaction], None)
+                        .await
+                    {
+                        Ok((ckb_transaction, _)) => WatchedTransaction::new(
+                            tx_hash,
+                            block_number,
+                            log_indexes,
+                            &ckb_transaction,
+                        ),
+                        Err(err) => {
+                            warn!("skip watched ethereum transaction {tx_hash:#x}: {err}");
+                            WatchedTransaction::failed(
+                                tx_hash,
+                                block_number,
+                                log_indexes,
+                                err.to_string(),
+                            )
+                        }
+                    };
+                    records.push(record);
+                }
+            }
+            WatchedTransaction::push_slot(self.consensus.storage(), slot, &mut records)?;
+            if !records.is_empty() {
+                info!(
+                    "pushed {} watched ckb transactions of beacon slot {slot}",
+                    records.len()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the checkers of the stored headers of the light client named `client_id`, or all
+    /// of the light clients, with their on-chain clients. The checks fetch the headers from the
+    /// consensus rpc, so they're run without borrowing the node.
+    pub async fn storage_integrity_checkers(
+        &self,
+        client_id: Option<&str>,
+    ) -> Result<Vec<(String, IntegrityChecker<NodeConsensusRpc>, OnChainClient)>> {
+        if let Some(client_id) = client_id {
+            if !self.light_clients().any(|(id, _, _)| id == client_id) {
+                return Err(eyre!("unknown ibc client id {client_id}"));
+            }
+        }
+        let mut checkers = vec![];
+        for (id, consensus, forcerelay) in self.light_clients() {
+            if client_id.map(|client_id| client_id != id).unwrap_or(false) {
+                continue;
+            }
+            let (client, _) = forcerelay.onchain_client().await?;
+            checkers.push((id.to_owned(), consensus.integrity_checker(), client));
+        }
+        Ok(checkers)
+    }
+
+    pub fn get_watched_ckb_transactions(&self, limit: usize) -> Result<Vec<WatchedTransaction>> {
+        WatchedTransaction::peek(self.consensus.storage(), limit)
+    }
+
+    pub fn drain_watched_ckb_transactions(&self, limit: usize) -> Result<Vec<WatchedTransaction>> {
+        WatchedTransaction::drain(self.consensus.storage(), limit)
+    }
+
+    // assemble one ckb transaction which verifies all of the ethereum transactions, the order of
+    // witnesses is the same as the order of hashes
+    pub async fn get_ckb_transaction_by_hashes(
+        &mut self,
+        tx_hashes: &[H256],
+        client_id: Option<&str>,
+    ) -> Result<CkbTransaction> {
+        if tx_hashes.is_empty() {
+            return Err(eyre!("empty transaction hashes"));
+        }
+        if tx_hashes.len() > MAX_BATCH_TRANSACTIONS {
+            return Err(eyre!(
+                "too many transactions in one batch: {} > {MAX_BATCH_TRANSACTIONS}",
+                tx_hashes.len()
+            ));
+        }
+        if let Some((ckb_transaction, _)) = self
+            .assemble_cached_ckb_transaction(tx_hashes, client_id)
+            .await?
+        {
+            return Ok(ckb_transaction.data().into());
+        }
+        let mut eth_transactions = Vec::with_capacity(tx_hashes.len());
+        for tx_hash in tx_hashes {
+            match self.prepare_eth_transaction(tx_hash).await? {
+                Some(value) => eth_transactions.push(value),
+                None => return Err(eyre!("cannot find transaction hash {tx_hash:#x}")),
+            }
+        }
+        let (ckb_transaction, _) = self
+            .assemble_ckb_transaction(&eth_transactions, client_id)
+            .await?;
+        Ok(ckb_transaction.data().into())
+    }
+
+    // assemble ckb transaction for the ethereum transaction which emits the log of `log_index`
+    // in the block of `block_hash`, the log is located by counting the logs of the cached
+    // receipts in transaction order, which are the ones verified by the proof, since the log
+    // indexes reported by the execution rpc are not covered by it
+    pub async fn get_ckb_transaction_for_log(
+        &mut self,
+        block_hash: &H256,
+        log_index: u64,
+        client_id: Option<&str>,
+    ) -> Result<Option<CkbTransactionForLog>> {
+        let receipts = self
+            .cached_block_receipts
+            .values()
+            .find(|receipts| {
+                receipts
+                    .first()
+                    .map(|receipt| receipt.block_hash == Some(*block_hash))
+                    .unwrap_or(false)
+            })
+            .ok_or_else(|| eyre!("receipts of block {block_hash:#x} are not cached yet"))?;
+        let (tx_hash, log_position) = match find_log_in_receipts(receipts, log_index) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let eth_transaction = match self.prepare_eth_transaction(&tx_hash).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let (ckb_transaction, _) = self
+            .assemble_ckb_transaction(&[eth_transaction], client_id)
+            .await?;
+        Ok(Some(CkbTransactionForLog {
+            transaction: ckb_transaction.data().into(),
+            eth_transaction_hash: tx_hash,
+            log_position: log_position as u64,
+        }))
+    }
+
+    // fetch the ethereum transaction and cache its receipts and beacon block, returns the
+    // transaction with its beacon slot and block number
+    async fn prepare_eth_transaction(
+        &mut self,
+        tx_hash: &H256,
+    ) -> Result<Option<(Transaction, u64, u64)>> {
         let eth_transaction = match self.execution.rpc.get_transaction(tx_hash).await? {
             Some(tx) => tx,
             None => return Ok(None),
@@ -476,39 +1168,103 @@ impl Node {
             self.cache_beacon_block(slot).await?;
         }
         if slot > 0 && block_number > 0 {
-            let (client, client_celldep) = self
-                .forcerelay
-                .check_onchain_client_alignment(&self.consensus)
-                .await?;
-            if slot < client.minimal_slot || slot > client.maximal_slot {
-                return Err(eyre::eyre!(
-                    "beacon slot {slot} is out of range [{}, {}]",
-                    client.minimal_slot,
-                    client.maximal_slot
+            Ok(Some((eth_transaction, slot, block_number)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // assemble from the proofs cached for the light client named `client_id`, or for any light
+    // client, without fetching the ethereum transactions again
+    async fn assemble_cached_ckb_transaction(
+        &self,
+        tx_hashes: &[H256],
+        client_id: Option<&str>,
+    ) -> Result<Option<AssembledTransaction>> {
+        for (id, consensus, forcerelay) in self.light_clients() {
+            if client_id.map(|client_id| client_id != id).unwrap_or(false) {
+                continue;
+            }
+            let (client, client_celldep) =
+                match forcerelay.check_onchain_client_alignment(consensus).await {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+            let ckb_transaction = forcerelay.assemble_batch_tx_from_cache(
+                &client,
+                &client_celldep,
+                consensus,
+                tx_hashes,
+            )?;
+            if let Some(ckb_transaction) = ckb_transaction {
+                let cycles = forcerelay.dry_run_tx(&ckb_transaction).await?;
+                return Ok(Some((ckb_transaction, cycles)));
+            }
+        }
+        Ok(None)
+    }
+
+    // assemble by the light client named `client_id`, or by the first light client whose slot
+    // range covers all of the ethereum transactions, the transaction is rejected if it fails in
+    // the dry run
+    async fn assemble_ckb_transaction(
+        &self,
+        eth_transactions: &[(Transaction, u64, u64)],
+        client_id: Option<&str>,
+    ) -> Result<AssembledTransaction> {
+        if let Some(client_id) = client_id {
+            if !self.light_clients().any(|(id, _, _)| id == client_id) {
+                return Err(eyre!("unknown ibc client id {client_id}"));
+            }
+        }
+        let mut errors = vec![];
+        for (id, consensus, forcerelay) in self.light_clients() {
+            if client_id.map(|client_id| client_id != id).unwrap_or(false) {
+                continue;
+            }
+            let (client, client_celldep) =
+                match forcerelay.check_onchain_client_alignment(consensus).await {
+                    Ok(value) => value,
+                    Err(err) => {
+                        errors.push(format!("{id}: {err}"));
+                        continue;
+                    }
+                };
+            let out_of_range = eth_transactions
+                .iter()
+                .map(|(_, slot, _)| *slot)
+                .find(|slot| *slot < client.minimal_slot || *slot > client.maximal_slot);
+            if let Some(slot) = out_of_range {
+                errors.push(format!(
+                    "{id}: beacon slot {slot} is out of range [{}, {}]",
+                    client.minimal_slot, client.maximal_slot
                 ));
+                continue;
             }
-            let block = match self.cached_beacon_blocks.get(&slot) {
-                Some(block) => block,
-                None => return Err(eyre!("beacon slot {slot} forked or skipped")),
-            };
-            let receipts = self
-                .cached_block_receipts
-                .get(&block_number)
-                .expect("cache receipts");
-            let ckb_transaction = self
-                .forcerelay
-                .assemble_tx(
-                    client,
-                    &client_celldep,
-                    &self.consensus,
-                    block,
-                    &eth_transaction,
-                    receipts,
-                )
+            let mut items = Vec::with_capacity(eth_transactions.len());
+            for (eth_transaction, slot, block_number) in eth_transactions {
+                let block = match self.cached_beacon_blocks.get(slot) {
+                    Some(block) => block,
+                    None if self.unchecked_beacon_blocks.contains_key(slot) => {
+                        return Err(eyre!(
+                            "beacon slot {slot} is not covered by the stored headers yet"
+                        ))
+                    }
+                    None => return Err(eyre!("beacon slot {slot} forked or skipped")),
+                };
+                let receipts = self
+                    .cached_block_receipts
+                    .get(block_number)
+                    .expect("cache receipts");
+                items.push((block, eth_transaction, receipts.as_slice()));
+            }
+            let ckb_transaction = forcerelay
+                .assemble_batch_tx(client, &client_celldep, consensus, &items)
                 .await?;
-            return Ok(Some(ckb_transaction.data().into()));
+            let cycles = forcerelay.dry_run_tx(&ckb_transaction).await?;
+            return Ok((ckb_transaction, cycles));
         }
-        Ok(None)
+        Err(eyre!("no light client is workable, {}", errors.join("; ")))
     }
 
     pub fn chain_id(&self) -> u64 {
@@ -583,3 +1339,72 @@ impl Node {
         }
     }
 }
+
+// the transaction of the log at `log_index` of the block and the position of the log in its
+// receipt, by counting the logs of the receipts in transaction order
+fn find_log_in_receipts(receipts: &[TransactionReceipt], log_index: u64) -> Option<(H256, usize)> {
+    let mut first_index = 0;
+    for receipt in receipts {
+        let logs_count = receipt.logs.len() as u64;
+        if log_index < first_index + logs_count {
+            let position = (log_index - first_index) as usize;
+            return Some((receipt.transaction_hash, position));
+        }
+        first_index += logs_count;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::prelude::U256;
+    use ethers::types::{Log, TransactionReceipt, H256};
+
+    use super::find_log_in_receipts;
+
+    // a receipt with `count` logs, whose log indexes reported by the rpc are `reported_index`
+    fn receipt(tx_byte: u8, count: usize, reported_index: u64) -> TransactionReceipt {
+        let logs = (0..count)
+            .map(|_| Log {
+                transaction_hash: Some(H256::repeat_byte(tx_byte)),
+                log_index: Some(U256::from(reported_index)),
+                ..Default::default()
+            })
+            .collect();
+        TransactionReceipt {
+            transaction_hash: H256::repeat_byte(tx_byte),
+            logs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_log_in_receipts() {
+        // the log indexes reported by the rpc are all wrong, and never used
+        let receipts = vec![receipt(1, 2, 9), receipt(2, 0, 9), receipt(3, 3, 0)];
+
+        // a matching log
+        assert_eq!(
+            find_log_in_receipts(&receipts, 0),
+            Some((H256::repeat_byte(1), 0))
+        );
+        assert_eq!(
+            find_log_in_receipts(&receipts, 1),
+            Some((H256::repeat_byte(1), 1))
+        );
+        assert_eq!(
+            find_log_in_receipts(&receipts, 3),
+            Some((H256::repeat_byte(3), 1))
+        );
+
+        // the log of the reported index is not the one counted in the receipts
+        assert_ne!(
+            find_log_in_receipts(&receipts, 0).map(|(hash, _)| hash),
+            Some(H256::repeat_byte(3))
+        );
+
+        // a log index out of range
+        assert_eq!(find_log_in_receipts(&receipts, 5), None);
+        assert_eq!(find_log_in_receipts(&[], 0), None);
+    }
+}
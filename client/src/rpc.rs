@@ -14,13 +14,21 @@ use jsonrpsee::{
     proc_macros::rpc,
 };
 
-use crate::{errors::NodeError, node::Node};
+use crate::{
+    errors::NodeError,
+    node::{CkbTransactionForLog, ClientStorageIntegrity, DryRunCkbTransaction, Node},
+};
 
 use common::{
     types::BlockTag,
     utils::{hex_str_to_bytes, u64_to_hex_string},
 };
 use execution::types::{CallOpts, ExecutionBlock};
+use forcerelay::tracker::SubmittedTransaction;
+use forcerelay::watcher::WatchedTransaction;
+
+// the number of watched transactions returned by one request if no limit is specified
+const DEFAULT_WATCHED_LIMIT: usize = 100;
 
 pub struct Rpc {
     node: Arc<RwLock<Node>>,
@@ -136,7 +144,62 @@ trait NetRpc {
 #[rpc(server, namespace = "forcerelay")]
 trait ForcerelayRpc {
     #[method(name = "getForcerelayCkbTransaction")]
-    async fn get_forcerelay_ckb_transaction(&self, hash: &str) -> Result<CkbTransaction, Error>;
+    async fn get_forcerelay_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<CkbTransaction, Error>;
+    #[method(name = "getForcerelayCompleteCkbTransaction")]
+    async fn get_forcerelay_complete_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<CkbTransaction, Error>;
+    #[method(name = "getForcerelayCkbTransactions")]
+    async fn get_forcerelay_ckb_transactions(
+        &self,
+        hashes: Vec<String>,
+        client_id: Option<String>,
+    ) -> Result<CkbTransaction, Error>;
+    #[method(name = "dryRunForcerelayCkbTransaction")]
+    async fn dry_run_forcerelay_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<DryRunCkbTransaction, Error>;
+    #[method(name = "getCkbTransactionForLog")]
+    async fn get_ckb_transaction_for_log(
+        &self,
+        block_hash: &str,
+        log_index: u64,
+        client_id: Option<String>,
+    ) -> Result<CkbTransactionForLog, Error>;
+    #[method(name = "submitCkbTransaction")]
+    async fn submit_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<SubmittedTransaction, Error>;
+    #[method(name = "getCkbTransactionStatus")]
+    async fn get_ckb_transaction_status(
+        &self,
+        hash: &str,
+    ) -> Result<Option<SubmittedTransaction>, Error>;
+    #[method(name = "getWatchedCkbTransactions")]
+    async fn get_watched_ckb_transactions(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<WatchedTransaction>, Error>;
+    #[method(name = "drainWatchedCkbTransactions")]
+    async fn drain_watched_ckb_transactions(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<WatchedTransaction>, Error>;
+    #[method(name = "checkStorageIntegrity")]
+    async fn check_storage_integrity(
+        &self,
+        client_id: Option<String>,
+    ) -> Result<Vec<ClientStorageIntegrity>, Error>;
 }
 
 #[derive(Clone)]
@@ -146,6 +209,18 @@ struct RpcInner {
     ready: bool,
 }
 
+impl RpcInner {
+    fn check_ready(&self) -> Result<(), Error> {
+        if !self.ready {
+            return Err(Error::Custom(
+                "Forcerelay/Eth verifier is in progress of chasing the tip, please wait..."
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl EthRpcServer for RpcInner {
     async fn get_balance(&self, address: &str, block: BlockTag) -> Result<String, Error> {
@@ -325,18 +400,17 @@ impl NetRpcServer for RpcInner {
 
 #[async_trait]
 impl ForcerelayRpcServer for RpcInner {
-    async fn get_forcerelay_ckb_transaction(&self, hash: &str) -> Result<CkbTransaction, Error> {
-        if !self.ready {
-            return Err(Error::Custom(
-                "Forcerelay/Eth verifier is in progress of chasing the tip, please wait..."
-                    .to_string(),
-            ));
-        }
+    async fn get_forcerelay_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<CkbTransaction, Error> {
+        self.check_ready()?;
         let current_time = tokio::time::Instant::now();
         let mut node = self.node.write().await;
         let hash = convert_err(H256::from_str(hash))?;
         let ckb_transaction = node
-            .get_ckb_transaction_by_hash(&hash)
+            .get_ckb_transaction_by_hash(&hash, client_id.as_deref())
             .await
             .map_err(|e| Error::Custom(e.to_string()))?;
         trace!(
@@ -352,6 +426,149 @@ impl ForcerelayRpcServer for RpcInner {
             Err(Error::Custom("cannot find transaction hash".to_string()))
         }
     }
+
+    async fn get_forcerelay_complete_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<CkbTransaction, Error> {
+        self.check_ready()?;
+        let mut node = self.node.write().await;
+        let hash = convert_err(H256::from_str(hash))?;
+        let ckb_transaction = node
+            .get_complete_ckb_transaction_by_hash(&hash, client_id.as_deref())
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+
+        if let Some(tx) = ckb_transaction {
+            Ok(tx)
+        } else {
+            Err(Error::Custom("cannot find transaction hash".to_string()))
+        }
+    }
+
+    async fn get_forcerelay_ckb_transactions(
+        &self,
+        hashes: Vec<String>,
+        client_id: Option<String>,
+    ) -> Result<CkbTransaction, Error> {
+        self.check_ready()?;
+        let current_time = tokio::time::Instant::now();
+        let hashes = hashes
+            .iter()
+            .map(|hash| convert_err(H256::from_str(hash)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut node = self.node.write().await;
+        let ckb_transaction = node
+            .get_ckb_transaction_by_hashes(&hashes, client_id.as_deref())
+            .await
+            .map_err(|e| Error::Custom(e.to_string()))?;
+        trace!(
+            "time elapsed for {} transactions: {} ms",
+            hashes.len(),
+            tokio::time::Instant::now()
+                .duration_since(current_time)
+                .as_millis()
+        );
+        Ok(ckb_transaction)
+    }
+
+    async fn dry_run_forcerelay_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<DryRunCkbTransaction, Error> {
+        self.check_ready()?;
+        let hash = convert_err(H256::from_str(hash))?;
+        let mut node = self.node.write().await;
+        let ckb_transaction = convert_err(
+            node.dry_run_ckb_transaction_by_hash(&hash, client_id.as_deref())
+                .await,
+        )?;
+        ckb_transaction
+            .ok_or_else(|| Error::Custom(format!("cannot find transaction hash {hash:#x}")))
+    }
+
+    async fn get_ckb_transaction_for_log(
+        &self,
+        block_hash: &str,
+        log_index: u64,
+        client_id: Option<String>,
+    ) -> Result<CkbTransactionForLog, Error> {
+        self.check_ready()?;
+        let block_hash = convert_err(H256::from_str(block_hash))?;
+        let mut node = self.node.write().await;
+        let ckb_transaction = convert_err(
+            node.get_ckb_transaction_for_log(&block_hash, log_index, client_id.as_deref())
+                .await,
+        )?;
+
+        if let Some(tx) = ckb_transaction {
+            Ok(tx)
+        } else {
+            Err(Error::Custom("cannot find log in block".to_string()))
+        }
+    }
+
+    async fn submit_ckb_transaction(
+        &self,
+        hash: &str,
+        client_id: Option<String>,
+    ) -> Result<SubmittedTransaction, Error> {
+        self.check_ready()?;
+        let hash = convert_err(H256::from_str(hash))?;
+        let mut node = self.node.write().await;
+        convert_err(
+            node.submit_ckb_transaction(&hash, client_id.as_deref())
+                .await,
+        )
+    }
+
+    async fn get_ckb_transaction_status(
+        &self,
+        hash: &str,
+    ) -> Result<Option<SubmittedTransaction>, Error> {
+        let hash = convert_err(H256::from_str(hash))?;
+        let node = self.node.read().await;
+        convert_err(node.get_submitted_ckb_transaction(&hash))
+    }
+
+    async fn get_watched_ckb_transactions(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<WatchedTransaction>, Error> {
+        let node = self.node.read().await;
+        convert_err(node.get_watched_ckb_transactions(limit.unwrap_or(DEFAULT_WATCHED_LIMIT)))
+    }
+
+    async fn drain_watched_ckb_transactions(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<WatchedTransaction>, Error> {
+        // hold the write lock, so that concurrent consumers never drain the same transactions
+        let node = self.node.write().await;
+        convert_err(node.drain_watched_ckb_transactions(limit.unwrap_or(DEFAULT_WATCHED_LIMIT)))
+    }
+
+    async fn check_storage_integrity(
+        &self,
+        client_id: Option<String>,
+    ) -> Result<Vec<ClientStorageIntegrity>, Error> {
+        // the node is released before the headers are fetched for the checks
+        let checkers = {
+            let node = self.node.read().await;
+            convert_err(node.storage_integrity_checkers(client_id.as_deref()).await)?
+        };
+        let mut results = vec![];
+        for (client_id, checker, client) in checkers {
+            let integrity = convert_err(checker.check(&client).await)?;
+            results.push(ClientStorageIntegrity {
+                client_id,
+                integrity,
+            });
+        }
+        Ok(results)
+    }
 }
 
 async fn start(rpc: RpcInner) -> Result<(HttpServerHandle, SocketAddr)> {
@@ -187,6 +187,74 @@ impl CheckpointFallback {
         most_common.ok_or_else(|| eyre::eyre!("No checkpoint found"))
     }
 
+    /// Fetch the latest checkpoint which is finalized no later than `max_slot` from the healthy
+    /// checkpoint fallback services, returns the slot and the block root of it.
+    ///
+    /// The services only list the checkpoints of the recent epochs, so there is no checkpoint for
+    /// a `max_slot` older than them, which has to be configured explicitly.
+    pub async fn fetch_checkpoint_before(
+        &self,
+        network: &crate::networks::Network,
+        max_slot: u64,
+    ) -> eyre::Result<(u64, H256)> {
+        let services = &self.get_healthy_fallback_services(network);
+        Self::fetch_checkpoint_before_from_services(&services[..], max_slot).await
+    }
+
+    /// Fetch the latest checkpoint which is finalized no later than `max_slot` from a list of
+    /// checkpoint fallback services.
+    pub async fn fetch_checkpoint_before_from_services(
+        services: &[CheckpointFallbackService],
+        max_slot: u64,
+    ) -> eyre::Result<(u64, H256)> {
+        let tasks = services
+            .iter()
+            .map(|service| Self::query_service(&service.endpoint));
+        let slots = futures::future::join_all(tasks)
+            .await
+            .into_iter()
+            .flatten()
+            .flat_map(|raw| raw.data.slots)
+            .collect::<Vec<_>>();
+        Self::select_checkpoint_before(&slots, max_slot)
+            .ok_or_else(|| no_checkpoint_before(&slots, max_slot))
+    }
+
+    /// Associated function to fetch the latest checkpoint which is finalized no later than
+    /// `max_slot` from a specific checkpoint sync fallback service api url.
+    pub async fn fetch_checkpoint_before_from_api(
+        url: &str,
+        max_slot: u64,
+    ) -> eyre::Result<(u64, H256)> {
+        let client = reqwest::Client::new();
+        let constructed_url = Self::construct_url(url);
+        let res = client.get(constructed_url).send().await?;
+        let raw: RawSlotResponse = res.json().await?;
+        Self::select_checkpoint_before(&raw.data.slots, max_slot)
+            .ok_or_else(|| no_checkpoint_before(&raw.data.slots, max_slot))
+    }
+
+    /// Selects the latest checkpoint which is no later than `max_slot` from the slots returned by
+    /// the checkpoint fallback services, the most common block root is chosen if they disagree.
+    pub fn select_checkpoint_before(slots: &[Slot], max_slot: u64) -> Option<(u64, H256)> {
+        let slot = slots
+            .iter()
+            .filter(|x| x.slot <= max_slot && x.block_root.is_some())
+            .map(|x| x.slot)
+            .max()?;
+        let mut m: HashMap<H256, usize> = HashMap::new();
+        for c in slots
+            .iter()
+            .filter(|x| x.slot == slot)
+            .filter_map(|x| x.block_root)
+        {
+            *m.entry(c).or_default() += 1;
+        }
+        m.into_iter()
+            .max_by_key(|(_, v)| *v)
+            .map(|(k, _)| (slot, k))
+    }
+
     /// Associated function to fetch the latest checkpoint from a specific checkpoint sync fallback
     /// service api url.
     pub async fn fetch_checkpoint_from_api(url: &str) -> eyre::Result<H256> {
@@ -263,3 +331,15 @@ impl CheckpointFallback {
         self.services[network].as_ref()
     }
 }
+
+// the services only keep the recent epochs, tell how far the listed checkpoints go back
+fn no_checkpoint_before(slots: &[Slot], max_slot: u64) -> eyre::Report {
+    match slots.iter().map(|x| x.slot).min() {
+        Some(oldest) => eyre::eyre!(
+            "No checkpoint found before slot {max_slot}, the oldest checkpoint listed by the \
+             fallback services is of slot {oldest}, configure a checkpoint finalized no later \
+             than slot {max_slot} instead"
+        ),
+        None => eyre::eyre!("No checkpoint found before slot {max_slot}"),
+    }
+}
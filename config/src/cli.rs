@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use figment::{providers::Serialized, value::Value};
 use serde::{Deserialize, Serialize};
@@ -8,6 +9,15 @@ use serde::{Deserialize, Serialize};
 pub struct CliConfig {
     pub execution_rpc: Option<String>,
     pub consensus_rpc: Option<String>,
+    pub ckb_rpc: Option<String>,
+    pub ckb_indexer_rpc: Option<String>,
+    pub ckb_rpc_timeout_ms: Option<u64>,
+    pub ckb_indexer_rpc_timeout_ms: Option<u64>,
+    pub lightclient_contract_typeargs: Option<Vec<u8>>,
+    pub lightclient_binary_typeargs: Option<Vec<u8>>,
+    pub ckb_ibc_client_id: Option<String>,
+    pub storage_path: Option<PathBuf>,
+    pub snapshot: Option<PathBuf>,
     pub checkpoint: Option<Vec<u8>>,
     pub rpc_port: Option<u16>,
     pub fallback: Option<String>,
@@ -27,6 +37,51 @@ impl CliConfig {
             user_dict.insert("consensus_rpc", Value::from(rpc.clone()));
         }
 
+        if let Some(rpc) = &self.ckb_rpc {
+            user_dict.insert("ckb_rpc", Value::from(rpc.clone()));
+        }
+
+        if let Some(rpc) = &self.ckb_indexer_rpc {
+            user_dict.insert("ckb_indexer_rpc", Value::from(rpc.clone()));
+        }
+
+        if let Some(timeout) = self.ckb_rpc_timeout_ms {
+            user_dict.insert("ckb_rpc_timeout_ms", Value::from(timeout));
+        }
+
+        if let Some(timeout) = self.ckb_indexer_rpc_timeout_ms {
+            user_dict.insert("ckb_indexer_rpc_timeout_ms", Value::from(timeout));
+        }
+
+        if let Some(typeargs) = &self.lightclient_contract_typeargs {
+            user_dict.insert(
+                "lightclient_contract_typeargs",
+                Value::from(hex::encode(typeargs)),
+            );
+        }
+
+        if let Some(typeargs) = &self.lightclient_binary_typeargs {
+            user_dict.insert(
+                "lightclient_binary_typeargs",
+                Value::from(hex::encode(typeargs)),
+            );
+        }
+
+        if let Some(client_id) = &self.ckb_ibc_client_id {
+            user_dict.insert("ckb_ibc_client_id", Value::from(client_id.clone()));
+        }
+
+        if let Some(path) = &self.storage_path {
+            user_dict.insert(
+                "storage_path",
+                Value::from(path.to_string_lossy().into_owned()),
+            );
+        }
+
+        if let Some(path) = &self.snapshot {
+            user_dict.insert("snapshot", Value::from(path.to_string_lossy().into_owned()));
+        }
+
         if let Some(checkpoint) = &self.checkpoint {
             user_dict.insert("checkpoint", Value::from(hex::encode(checkpoint)));
         }
@@ -8,19 +8,43 @@ use std::{path::PathBuf, process::exit};
 use crate::base::BaseConfig;
 use crate::cli::CliConfig;
 use crate::networks;
-use crate::types::{ChainConfig, Forks};
+use crate::types::{
+    BackfillConfig, ChainConfig, CkbDryRunConfig, CkbLightClientConfig, CkbRpcRetryConfig,
+    CkbSignerConfig, Forks, StorageBackend, WatchConfig,
+};
 use crate::utils::{bytes_deserialize, bytes_serialize};
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Config {
     pub consensus_rpc: String,
+    /// The other consensus RPC endpoints for fail-over and cross-checking.
+    #[serde(default)]
+    pub consensus_rpcs: Vec<String>,
+    /// The number of consensus RPC endpoints which must return the same beacon headers and
+    /// blocks, 1 if absent.
+    pub consensus_rpc_quorum: Option<usize>,
+    #[serde(default)]
+    pub backfill: BackfillConfig,
     pub execution_rpc: String,
     pub ckb_rpc: String,
+    /// The CKB indexer RPC, `ckb_rpc` is used if absent.
+    pub ckb_indexer_rpc: Option<String>,
+    /// The timeouts of requests to `ckb_rpc` and `ckb_indexer_rpc`, unlimited if absent.
+    pub ckb_rpc_timeout_ms: Option<u64>,
+    pub ckb_indexer_rpc_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub ckb_rpc_retry: CkbRpcRetryConfig,
+    pub ckb_light_client: Option<CkbLightClientConfig>,
     #[serde(deserialize_with = "bytes_deserialize")]
     pub lightclient_contract_typeargs: Vec<u8>,
     #[serde(deserialize_with = "bytes_deserialize")]
     pub lightclient_binary_typeargs: Vec<u8>,
     pub ckb_ibc_client_id: String,
+    #[serde(default)]
+    pub ckb_ibc_client_ids: Vec<String>,
+    pub ckb_signer: Option<CkbSignerConfig>,
+    pub ckb_dry_run: Option<CkbDryRunConfig>,
+    pub watch: Option<WatchConfig>,
     pub rpc_port: Option<u16>,
     #[serde(
         deserialize_with = "bytes_deserialize",
@@ -28,6 +52,11 @@ pub struct Config {
     )]
     pub checkpoint: Vec<u8>,
     pub storage_path: PathBuf,
+    /// The backend of the native storage, `storage_path` is ignored by the memory backend.
+    #[serde(default)]
+    pub storage_backend: StorageBackend,
+    /// The snapshot file of the stored headers to import on the first start.
+    pub snapshot: Option<PathBuf>,
     pub chain: ChainConfig,
     pub forks: Forks,
     pub max_checkpoint_age: u64,
@@ -90,6 +119,39 @@ impl Config {
         }
     }
 
+    /// Returns all of the consensus RPC endpoints, the primary `consensus_rpc` comes first.
+    pub fn consensus_rpc_urls(&self) -> Vec<String> {
+        let mut urls = vec![self.consensus_rpc.clone()];
+        for url in &self.consensus_rpcs {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+        urls
+    }
+
+    /// Returns all of the served IBC client ids, the primary `ckb_ibc_client_id` comes first.
+    pub fn ibc_client_ids(&self) -> Vec<String> {
+        let mut client_ids = vec![self.ckb_ibc_client_id.clone()];
+        for client_id in &self.ckb_ibc_client_ids {
+            if !client_ids.contains(client_id) {
+                client_ids.push(client_id.clone());
+            }
+        }
+        client_ids
+    }
+
+    /// Returns the MMR storage path of an IBC client, the primary client uses `storage_path`
+    /// directly and the others use its siblings suffixed by their client ids.
+    pub fn ibc_client_storage_path(&self, client_id: &str) -> PathBuf {
+        if client_id == self.ckb_ibc_client_id {
+            return self.storage_path.clone();
+        }
+        let mut path = self.storage_path.clone().into_os_string();
+        path.push(format!("_{client_id}"));
+        path.into()
+    }
+
     pub fn to_base_config(&self) -> BaseConfig {
         BaseConfig {
             rpc_port: self.rpc_port.unwrap_or(8545),
@@ -25,6 +25,15 @@ pub enum Network {
 }
 
 impl Network {
+    /// Returns the network of the chain id, or `None` if it's not a supported one.
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        match chain_id {
+            1 => Some(Self::MAINNET),
+            5 => Some(Self::GOERLI),
+            _ => None,
+        }
+    }
+
     pub fn to_base_config(&self) -> BaseConfig {
         match self {
             Self::MAINNET => mainnet(),
@@ -1,3 +1,6 @@
+use std::path::PathBuf;
+
+use ethers::types::{Address, H256};
 use serde::{Deserialize, Serialize};
 
 use crate::utils::{bytes_deserialize, bytes_serialize};
@@ -30,3 +33,141 @@ pub struct Fork {
     )]
     pub fork_version: Vec<u8>,
 }
+
+/// The signer to balance and sign partial CKB transactions in complete mode.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CkbSignerConfig {
+    /// The CKB address of a secp256k1/blake160 sighash lock script.
+    pub address: String,
+    /// The file whose first line is the hex encoded secret key of the address.
+    pub key_path: PathBuf,
+    /// The fee rate in shannons per kilobyte.
+    #[serde(default = "default_fee_rate")]
+    pub fee_rate: u64,
+}
+
+/// The shannons per kilobyte to pay if no fee rate is configured.
+pub fn default_fee_rate() -> u64 {
+    1000
+}
+
+/// The retry policy of CKB RPC requests which fail before a response is received.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CkbRpcRetryConfig {
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// The delay before the first retry in milliseconds, which is doubled for each retry.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for CkbRpcRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    8000
+}
+
+/// The backfill of the beacon headers between the stored tip and the finalized header.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackfillConfig {
+    /// The number of headers requested at the same time.
+    #[serde(default = "default_backfill_concurrency")]
+    pub concurrency: usize,
+    /// The retries of each header, the delay starts from `initial_backoff_ms` and is doubled for
+    /// each retry up to `max_backoff_ms`.
+    #[serde(default = "default_backfill_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: default_backfill_concurrency(),
+            max_retries: default_backfill_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+fn default_backfill_concurrency() -> usize {
+    16
+}
+
+fn default_backfill_max_retries() -> u32 {
+    5
+}
+
+/// Talks to a `ckb-light-client` node instead of a full node, `ckb_rpc` is the RPC of the light
+/// client then.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CkbLightClientConfig {
+    /// The block number from which the scripts registered by the verifier are synced, e.g. the
+    /// one before the lightclient cells are deployed.
+    #[serde(default)]
+    pub start_block_number: u64,
+    /// The block number from which the lock script of the signer is synced, its cells are
+    /// usually created before the lightclient cells.
+    #[serde(default)]
+    pub signer_start_block_number: u64,
+}
+
+/// The local dry run of assembled CKB transactions in the CKB script VM.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CkbDryRunConfig {
+    /// The business contract which loads the lightclient cell and the verify binary from cell
+    /// deps and verifies the witnesses, its args are the type hashes of both cells.
+    pub checker_path: PathBuf,
+    /// The maximum cycles a transaction may consume.
+    #[serde(default = "default_max_cycles")]
+    pub max_cycles: u64,
+}
+
+fn default_max_cycles() -> u64 {
+    // the max block cycles of CKB mainnet
+    3_500_000_000
+}
+
+/// The Ethereum logs to generate CKB verification transactions for in watch mode.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct WatchConfig {
+    /// The contracts which emit the logs, empty for any contract.
+    #[serde(default)]
+    pub addresses: Vec<Address>,
+    /// The event signatures, i.e. the first topics of the logs, empty for any event.
+    #[serde(default)]
+    pub topics: Vec<H256>,
+}
+
+/// The key-value backend of the native storage.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// The RocksDB database at `storage_path`.
+    #[default]
+    RocksDB,
+    /// The data are kept in memory, and lost on restart.
+    Memory,
+}
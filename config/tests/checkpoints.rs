@@ -68,3 +68,40 @@ async fn test_get_healthy_fallback_endpoints() {
     let urls = cf.get_healthy_fallback_endpoints(&networks::Network::GOERLI);
     assert!(!urls.is_empty());
 }
+
+#[test]
+fn test_select_checkpoint_before() {
+    use config::checkpoints::{CheckpointFallback, Slot};
+
+    let slot = |slot: u64, root: Option<u64>| Slot {
+        slot,
+        block_root: root.map(H256::from_low_u64_be),
+        epoch: slot / 32,
+        ..Default::default()
+    };
+    let slots = [
+        slot(6400, Some(1)),
+        slot(6432, Some(2)),
+        slot(6432, Some(3)),
+        slot(6432, Some(3)),
+        slot(6464, None),
+        slot(6496, Some(4)),
+    ];
+
+    assert_eq!(
+        CheckpointFallback::select_checkpoint_before(&slots, 6399),
+        None
+    );
+    assert_eq!(
+        CheckpointFallback::select_checkpoint_before(&slots, 6400),
+        Some((6400, H256::from_low_u64_be(1)))
+    );
+    assert_eq!(
+        CheckpointFallback::select_checkpoint_before(&slots, 6490),
+        Some((6432, H256::from_low_u64_be(3)))
+    );
+    assert_eq!(
+        CheckpointFallback::select_checkpoint_before(&slots, u64::MAX),
+        Some((6496, H256::from_low_u64_be(4)))
+    );
+}
@@ -1,4 +1,4 @@
-use config::Config;
+use config::{CliConfig, Config};
 use std::path::Path;
 
 #[test]
@@ -8,3 +8,48 @@ fn test_load_full_config() {
     let config = Config::from_file(&path.to_path_buf(), "mainnet", &Default::default());
     assert_eq!(config.ckb_rpc, "https://testnet.ckbapp.dev");
 }
+
+#[test]
+fn test_ibc_client_ids() {
+    let config = Config {
+        ckb_ibc_client_id: "ibc-ckb-1".to_owned(),
+        ckb_ibc_client_ids: vec!["ibc-ckb-2".to_owned(), "ibc-ckb-1".to_owned()],
+        storage_path: "./ckb_mmr_storage".into(),
+        ..Default::default()
+    };
+    assert_eq!(config.ibc_client_ids(), vec!["ibc-ckb-1", "ibc-ckb-2"]);
+    assert_eq!(
+        config.ibc_client_storage_path("ibc-ckb-1"),
+        Path::new("./ckb_mmr_storage")
+    );
+    assert_eq!(
+        config.ibc_client_storage_path("ibc-ckb-2"),
+        Path::new("./ckb_mmr_storage_ibc-ckb-2")
+    );
+}
+
+#[test]
+fn test_cli_overrides_ckb_config() {
+    let path = Path::new("./config.toml");
+    let cli_config = CliConfig {
+        ckb_rpc: Some("http://127.0.0.1:8114".to_owned()),
+        ckb_indexer_rpc: Some("http://127.0.0.1:8116".to_owned()),
+        ckb_indexer_rpc_timeout_ms: Some(30000),
+        lightclient_binary_typeargs: Some(vec![1u8; 32]),
+        ckb_ibc_client_id: Some("ibc-ckb-3".to_owned()),
+        storage_path: Some("./cli_storage".into()),
+        ..Default::default()
+    };
+
+    let config = Config::from_file(&path.to_path_buf(), "mainnet", &cli_config);
+    assert_eq!(config.ckb_rpc, "http://127.0.0.1:8114");
+    assert_eq!(
+        config.ckb_indexer_rpc.as_deref(),
+        Some("http://127.0.0.1:8116")
+    );
+    assert_eq!(config.ckb_rpc_timeout_ms, None);
+    assert_eq!(config.ckb_indexer_rpc_timeout_ms, Some(30000));
+    assert_eq!(config.lightclient_binary_typeargs, vec![1u8; 32]);
+    assert_eq!(config.ckb_ibc_client_id, "ibc-ckb-3");
+    assert_eq!(config.storage_path, Path::new("./cli_storage"));
+}
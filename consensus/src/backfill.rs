@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use config::BackfillConfig;
+use eth2_types::MainnetEthSpec;
+use eyre::{eyre, Result};
+use futures::StreamExt as _;
+use log::{debug, info, warn};
+use storage::{prelude::StorageWriter as _, Storage};
+
+use crate::rpc::ConsensusRpc;
+use crate::types::Header;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Fetches the beacon header of `slot`, a forked or skipped slot is returned as an empty header.
+/// The failed requests are retried with exponential backoff.
+pub async fn fetch_header<R: ConsensusRpc>(
+    rpc: &R,
+    slot: u64,
+    config: &BackfillConfig,
+) -> Result<Header> {
+    let mut retry = 0;
+    let mut backoff = Duration::from_millis(config.initial_backoff_ms);
+    loop {
+        match rpc.get_header(slot).await {
+            Ok(Some(header)) => return Ok(header),
+            Ok(None) => {
+                warn!("forked or skipped beacon header ({slot})");
+                return Ok(Header {
+                    slot,
+                    ..Default::default()
+                });
+            }
+            Err(err) if retry < config.max_retries => {
+                debug!("retry to fetch beacon header ({slot}) in {backoff:?}: {err}");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_millis(config.max_backoff_ms));
+                retry += 1;
+            }
+            Err(err) => {
+                return Err(eyre!(
+                    "failed to fetch beacon header ({slot}) after {retry} retries: {err}"
+                ))
+            }
+        }
+    }
+}
+
+/// Fetches the beacon headers in `[start_slot, end_slot]`, at most `concurrency` of them at the
+/// same time, and puts them into the storage in the order of slots.
+///
+/// The backfilled slot always ends the contiguous backfilled headers, so the next backfill can
+/// continue from it if this one fails halfway.
+pub async fn backfill_headers<R: ConsensusRpc>(
+    rpc: &R,
+    storage: &Storage<MainnetEthSpec>,
+    config: &BackfillConfig,
+    start_slot: u64,
+    end_slot: u64,
+) -> Result<()> {
+    let total = end_slot - start_slot + 1;
+    let started_at = Instant::now();
+    let mut reported_at = started_at;
+    let mut headers = futures::stream::iter(start_slot..=end_slot)
+        .map(|slot| async move { (slot, fetch_header(rpc, slot, config).await) })
+        .buffered(config.concurrency.max(1));
+    let mut done = 0;
+    while let Some((slot, header)) = headers.next().await {
+        storage.put_backfilled_header(slot, &header?)?;
+        done += 1;
+        if reported_at.elapsed() >= REPORT_INTERVAL || done == total {
+            reported_at = Instant::now();
+            let rate = done as f64 / started_at.elapsed().as_secs_f64().max(f64::EPSILON);
+            let eta = (total - done) as f64 / rate;
+            info!(
+                "backfilled {done}/{total} headers of slots [{start_slot}, {end_slot}], \
+                {rate:.1} slots/s, ETA {eta:.0}s"
+            );
+        }
+    }
+    Ok(())
+}
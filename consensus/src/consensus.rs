@@ -1,4 +1,5 @@
 use std::cmp;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::UNIX_EPOCH;
 
@@ -17,24 +18,33 @@ use tree_hash::TreeHash;
 
 use common::types::*;
 use common::utils::*;
-use config::Config;
+use config::{CheckpointFallback, Config, Network, StorageBackend};
 
-use crate::constants::{MAX_REQUEST_LIGHT_CLIENT_UPDATES, MAX_REQUEST_RPC_UPDATES, MAX_RPC_RETRY};
+use crate::backfill;
+use crate::constants::{
+    MAX_REQUEST_LIGHT_CLIENT_UPDATES, MAX_REQUEST_RPC_UPDATES, SYNC_COMMITTEE_SIZE, UPDATE_TIMEOUT,
+};
 use crate::errors::ConsensusError;
+use crate::integrity::{IntegrityChecker, ReferenceCache, StorageIntegrity};
+use crate::snapshot;
 
 use super::rpc::ConsensusRpc;
 use super::types::*;
 use super::utils::*;
 
 // https://github.com/ethereum/consensus-specs/blob/dev/specs/altair/light-client/sync-protocol.md
-// does not implement force updates
 
 pub struct ConsensusClient<R: ConsensusRpc> {
-    pub rpc: R,
+    pub rpc: Arc<R>,
     store: LightClientStore,
     initial_checkpoint: Vec<u8>,
     pub last_checkpoint: Option<Vec<u8>>,
     pub config: Arc<Config>,
+    onchain_maximal_slot: Option<u64>,
+    // the slots of the on-chain client which the storage is re-initialized for because of the
+    // diverged MMR root, it's an error to diverge from the same client again
+    reinitialized_client_slots: Option<(u64, u64)>,
+    integrity_references: ReferenceCache,
 }
 
 struct LightClientStore {
@@ -46,19 +56,104 @@ struct LightClientStore {
     optimistic_header: Header,
     previous_max_active_participants: u64,
     current_max_active_participants: u64,
+    // the candidate to force update with if there's no finality until the update timeout
+    best_valid_update: Option<GenericUpdate>,
+    // the slot of the finalized header if it's force updated, cleared by the next real finality
+    forced_finalized_slot: Option<u64>,
     storage: Storage<MainnetEthSpec>,
 }
 
+// the persisted part of `LightClientStore`, an absent option is encoded as an empty list, and the
+// best valid update is not persisted since it's collected again from the following updates
+#[derive(Default, SimpleSerialize)]
+struct PersistedLightClientStore {
+    finalized_header: Header,
+    current_sync_committee: SyncCommittee,
+    next_sync_committee: List<SyncCommittee, 1>,
+    next_sync_committee_branch: List<List<Bytes32, 512>, 1>,
+    optimistic_header: Header,
+    previous_max_active_participants: u64,
+    current_max_active_participants: u64,
+    forced_finalized_slot: List<u64, 1>,
+}
+
+impl LightClientStore {
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        let persisted = PersistedLightClientStore {
+            finalized_header: self.finalized_header.clone(),
+            current_sync_committee: self.current_sync_committee.clone(),
+            next_sync_committee: List::from_iter(self.next_sync_committee.clone()),
+            next_sync_committee_branch: List::from_iter(
+                self.next_sync_committee_branch.clone().map(List::from_iter),
+            ),
+            optimistic_header: self.optimistic_header.clone(),
+            previous_max_active_participants: self.previous_max_active_participants,
+            current_max_active_participants: self.current_max_active_participants,
+            forced_finalized_slot: List::from_iter(self.forced_finalized_slot),
+        };
+        let mut bytes = Vec::new();
+        persisted.serialize(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn restore_from_slice(&mut self, bytes: &[u8]) -> Result<()> {
+        let persisted = PersistedLightClientStore::deserialize(bytes)?;
+        self.finalized_header = persisted.finalized_header;
+        self.current_sync_committee = persisted.current_sync_committee;
+        self.next_sync_committee = persisted.next_sync_committee.first().cloned();
+        self.next_sync_committee_branch = persisted
+            .next_sync_committee_branch
+            .first()
+            .map(|branch| branch.to_vec());
+        self.optimistic_header = persisted.optimistic_header;
+        self.previous_max_active_participants = persisted.previous_max_active_participants;
+        self.current_max_active_participants = persisted.current_max_active_participants;
+        self.forced_finalized_slot = persisted.forced_finalized_slot.first().copied();
+        Ok(())
+    }
+}
+
 impl<R: ConsensusRpc> ConsensusClient<R> {
     pub fn new(
         rpc: &str,
         checkpoint_block_root: &[u8],
         config: Arc<Config>,
+    ) -> Result<ConsensusClient<R>> {
+        let storage_path = config.storage_path.clone();
+        Self::new_with_storage_path(rpc, checkpoint_block_root, config, &storage_path)
+    }
+
+    /// Creates a client whose MMR storage is at `storage_path` instead of the configured one.
+    pub fn new_with_storage_path(
+        rpc: &str,
+        checkpoint_block_root: &[u8],
+        config: Arc<Config>,
+        storage_path: &Path,
     ) -> Result<ConsensusClient<R>> {
         let rpc = R::new(rpc);
+        Self::with_rpc(rpc, checkpoint_block_root, config, storage_path)
+    }
 
-        let storage_path = &config.storage_path;
-        let storage = Storage::new(storage_path)?;
+    /// Creates a client over an rpc which is constructed already, e.g. a `MultiRpc` with quorum.
+    /// The storage is opened at `storage_path` unless the memory backend is configured.
+    pub fn with_rpc(
+        rpc: R,
+        checkpoint_block_root: &[u8],
+        config: Arc<Config>,
+        storage_path: &Path,
+    ) -> Result<ConsensusClient<R>> {
+        let storage = match config.storage_backend {
+            StorageBackend::RocksDB => Storage::new(storage_path)?,
+            StorageBackend::Memory => Storage::new_in_memory()?,
+        };
+        if storage.repair_torn_writes()? {
+            warn!(
+                "storage is repaired from torn writes, the stored slot range is [{:?}, {:?}]",
+                storage.get_base_beacon_header_slot()?,
+                storage.get_tip_beacon_header_slot()?
+            );
+        }
+        let last_checkpoint = storage.get_last_checkpoint()?;
 
         let store = LightClientStore {
             base_slot: 0,
@@ -69,15 +164,20 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
             optimistic_header: Default::default(),
             previous_max_active_participants: 0,
             current_max_active_participants: 0,
+            best_valid_update: None,
+            forced_finalized_slot: None,
             storage,
         };
 
         Ok(ConsensusClient {
-            rpc,
+            rpc: Arc::new(rpc),
             store,
-            last_checkpoint: None,
+            last_checkpoint,
             config,
             initial_checkpoint: checkpoint_block_root.to_vec(),
+            onchain_maximal_slot: None,
+            reinitialized_client_slots: None,
+            integrity_references: Default::default(),
         })
     }
 
@@ -139,7 +239,23 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
         &self.store.finalized_header
     }
 
+    /// Returns the slot of the finalized header if it's force updated after the update timeout,
+    /// which means it's only confirmed by the sync committee signatures but not finalized yet.
+    pub fn forced_finalized_slot(&self) -> Option<u64> {
+        self.store.forced_finalized_slot
+    }
+
     fn store_finalized_update_batch(&self, updates: &[Update]) -> Result<()> {
+        if let Some(forced_slot) = self.store.forced_finalized_slot {
+            if let Some(update) = updates
+                .iter()
+                .find(|update| update.finalized_header.slot >= forced_slot)
+            {
+                return Err(
+                    ConsensusError::ForceUpdatedHeader(update.finalized_header.slot).into(),
+                );
+            }
+        }
         let storage = self.storage();
         let mut stored_tip_slot = match storage.get_tip_beacon_header_slot()? {
             Some(slot) => slot,
@@ -151,6 +267,8 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
             }
         };
         let base_slot = self.store.base_slot;
+        // the MMR nodes and the tip slot of the headers are committed atomically
+        let batch = storage.batch();
         let mut storage_mmr: Option<ClientRootMMR<_>> = None;
         for update in updates {
             let header = &update.finalized_header;
@@ -166,7 +284,7 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
                         if let Some(mmr) = storage_mmr.as_mut() {
                             mmr.push(digest)?;
                         } else {
-                            let mut mmr = storage.chain_root_mmr(stored_tip_slot)?;
+                            let mut mmr = batch.chain_root_mmr(stored_tip_slot)?;
                             mmr.push(digest)?;
                             storage_mmr = Some(mmr);
                         }
@@ -178,8 +296,11 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
                     }
                 }
                 cmp::Ordering::Equal => {
-                    if !storage.is_initialized()? {
-                        storage.initialize_with(slot, digest)?;
+                    if !storage.is_initialized()? && storage_mmr.is_none() {
+                        batch.put_base_beacon_header_slot(slot)?;
+                        let mut mmr = ClientRootMMR::new(0, batch.clone());
+                        mmr.push(digest)?;
+                        storage_mmr = Some(mmr);
                         stored_tip_slot = slot;
                     } else {
                         return Err(eyre!("storage should be checked"));
@@ -190,76 +311,216 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
                 }
             }
         }
-        storage.put_tip_beacon_header_slot(stored_tip_slot)?;
         if let Some(mmr) = storage_mmr {
             mmr.commit()?;
         }
+        batch.put_tip_beacon_header_slot(stored_tip_slot)?;
+        batch.commit()?;
         Ok(())
     }
 
-    async fn store_updates_from_rpc(&mut self, start_slot: u64, end_slot: u64) -> Result<()> {
-        let tasks: Vec<_> = (start_slot..=end_slot)
-            .map(|slot| self.get_finality_update(slot))
-            .collect();
-        let updates: Result<Vec<Update>> =
-            futures::future::join_all(tasks).await.into_iter().collect();
-        let updates = updates?;
-        self.store_finalized_update_batch(&updates)?;
-        Ok(())
+    // continues the backfill from the backfilled slot if the backfilled headers cover
+    // `start_slot`, otherwise the stale ones are discarded
+    async fn backfill_headers(&self, start_slot: u64, end_slot: u64) -> Result<()> {
+        let storage = self.storage();
+        let from_slot = match storage.get_backfilled_slot()? {
+            Some(backfilled_slot)
+                if backfilled_slot >= start_slot
+                    && storage.get_backfilled_header(start_slot)?.is_some() =>
+            {
+                backfilled_slot + 1
+            }
+            Some(_) => {
+                storage.clear_backfilled_headers()?;
+                start_slot
+            }
+            None => start_slot,
+        };
+        if from_slot > end_slot {
+            return Ok(());
+        }
+        if from_slot > start_slot {
+            info!("resume the backfill of slots [{start_slot}, {end_slot}] from slot {from_slot}");
+        }
+        backfill::backfill_headers(
+            self.rpc.as_ref(),
+            storage,
+            &self.config.backfill,
+            from_slot,
+            end_slot,
+        )
+        .await
     }
 
     pub async fn store_updates_until_finality_update(
         &mut self,
         finality_update: &FinalityUpdate,
     ) -> Result<()> {
-        let end_slot = finality_update.finalized_header.slot;
-        if let Some(stored_tip_slot) = self.storage().get_tip_beacon_header_slot()? {
-            if stored_tip_slot >= end_slot {
+        self.store_updates_until_header(&finality_update.finalized_header)
+            .await
+    }
+
+    /// Stores the headers after the stored tip until `verified_header`, which must be verified by
+    /// the sync committee already.
+    ///
+    /// The headers from the rpc are authenticated by linking their parent roots backwards from
+    /// `verified_header` to the stored tip, none of them is stored if any link is broken. If the
+    /// storage is not initialized, the headers are stored from the base slot.
+    ///
+    /// The headers are read from the backfilled ones slot by slot, instead of being loaded at once.
+    async fn store_updates_until_header(&mut self, verified_header: &Header) -> Result<()> {
+        let end_slot = verified_header.slot;
+        if let Some(forced_slot) = self.store.forced_finalized_slot {
+            if end_slot >= forced_slot {
+                debug!("skip storing headers until slot {end_slot} before real finality");
                 return Ok(());
             }
-            debug!("store finalized update for slots ({stored_tip_slot}, {end_slot}]");
-            let mut slot_pointer = stored_tip_slot + 1;
-            while slot_pointer + MAX_REQUEST_RPC_UPDATES < end_slot {
-                self.store_updates_from_rpc(slot_pointer, slot_pointer + MAX_REQUEST_RPC_UPDATES)
-                    .await?;
-                slot_pointer += MAX_REQUEST_RPC_UPDATES + 1;
+        }
+        let start_slot = if self.storage().is_initialized()? {
+            match self.storage().get_tip_beacon_header_slot()? {
+                Some(stored_tip_slot) if stored_tip_slot >= end_slot => return Ok(()),
+                Some(stored_tip_slot) => stored_tip_slot + 1,
+                None => return Err(eyre!("tip beacon header slot shouldn't be none")),
+            }
+        } else if self.store.base_slot <= end_slot {
+            self.store.base_slot
+        } else {
+            return Ok(());
+        };
+        debug!("store finalized update for slots [{start_slot}, {end_slot}]");
+        if start_slot < end_slot {
+            self.backfill_headers(start_slot, end_slot - 1).await?;
+        }
+
+        // the backfilled headers are authenticated one by one, the unlinked one and the ones
+        // after it are discarded to be fetched again
+        let mut linker = BackwardLinker::new(end_slot);
+        linker.link(&Update::from_finalized_header(verified_header.clone()))?;
+        let mut start_is_empty = false;
+        for slot in (start_slot..end_slot).rev() {
+            let update = self.backfilled_update(slot)?;
+            if let Err(err) = linker.link(&update) {
+                self.storage().truncate_backfilled_headers(slot)?;
+                return Err(err);
             }
-            self.store_updates_from_rpc(slot_pointer, end_slot).await
+            start_is_empty = update.is_finalized_empty();
+        }
+        let parent_root = linker.parent_root()?;
+        if let Err(err) = self
+            .link_to_storage(start_slot, start_is_empty, &parent_root)
+            .await
+        {
+            self.storage().clear_backfilled_headers()?;
+            return Err(err);
+        }
+
+        // all of the headers are authenticated, so they are committed batch by batch
+        let mut batch_start = start_slot;
+        while batch_start <= end_slot {
+            let batch_end = end_slot.min(batch_start + MAX_REQUEST_RPC_UPDATES);
+            let mut batch = Vec::with_capacity((batch_end - batch_start + 1) as usize);
+            for slot in batch_start..=batch_end {
+                if slot == end_slot {
+                    batch.push(Update::from_finalized_header(verified_header.clone()));
+                } else {
+                    batch.push(self.backfilled_update(slot)?);
+                }
+            }
+            self.store_finalized_update_batch(&batch)?;
+            batch_start = batch_end + 1;
+        }
+        self.storage().clear_backfilled_headers()?;
+        Ok(())
+    }
+
+    fn backfilled_update(&self, slot: u64) -> Result<Update> {
+        let header = self
+            .storage()
+            .get_backfilled_header(slot)?
+            .ok_or_else(|| eyre!("backfilled header of slot {slot} is missing"))?;
+        Ok(Update::from_finalized_header(header))
+    }
+
+    async fn link_to_storage(
+        &self,
+        start_slot: u64,
+        start_is_empty: bool,
+        parent_root: &[u8],
+    ) -> Result<()> {
+        if start_slot == self.store.base_slot {
+            if start_is_empty {
+                return Err(eyre!("forked or skipped base slot {start_slot}"));
+            }
+            Ok(())
         } else {
-            Err(eyre!("tip beacon header slot shouldn't be none"))
+            self.check_linked_to_stored_tip(start_slot - 1, parent_root)
+                .await
         }
     }
 
-    pub async fn get_finality_update(&self, finality_update_slot: u64) -> Result<Update> {
-        let mut retry = 0;
-        let finalized_header = loop {
-            let finalized_header = self.rpc.get_header(finality_update_slot).await;
-            if finalized_header.is_ok() || retry < MAX_RPC_RETRY {
-                break finalized_header;
+    // the parent of the earliest non-empty new header must be the latest non-empty stored one,
+    // the stored headers are fetched again and authenticated by the stored digests
+    async fn check_linked_to_stored_tip(
+        &self,
+        stored_tip_slot: u64,
+        parent_root: &[u8],
+    ) -> Result<()> {
+        let mut slot = stored_tip_slot;
+        loop {
+            let update = self.get_finality_update(slot).await?;
+            let digest = {
+                let header: core::Header = (&update.finalized_header).into();
+                header.calc_cache().digest()
+            };
+            if self.storage().is_header_digest_matched(slot, &digest)? != Some(true) {
+                return Err(eyre!(
+                    "header of slot {slot} from rpc mismatches the stored header digest"
+                ));
             }
-            retry += 1;
-        };
-        let finalized_header = match finalized_header {
-            Ok(Some(header)) => header,
-            Ok(None) => {
-                warn!("forked or skipped beacon header ({finality_update_slot})");
-                Header {
-                    slot: finality_update_slot,
-                    ..Default::default()
+            if !update.is_finalized_empty() {
+                let root = header_root(&update.finalized_header)?;
+                if root != parent_root {
+                    return Err(ConsensusError::UnlinkedHeader(
+                        slot,
+                        format!("0x{}", hex::encode(&root)),
+                        format!("0x{}", hex::encode(parent_root)),
+                    )
+                    .into());
                 }
+                return Ok(());
             }
-            Err(error) => return Err(eyre!("{error}")),
-        };
+            if slot <= self.store.base_slot {
+                return Err(eyre!(
+                    "no stored header at or before slot {stored_tip_slot} is non-empty"
+                ));
+            }
+            slot -= 1;
+        }
+    }
+
+    pub async fn get_finality_update(&self, finality_update_slot: u64) -> Result<Update> {
+        let finalized_header = backfill::fetch_header(
+            self.rpc.as_ref(),
+            finality_update_slot,
+            &self.config.backfill,
+        )
+        .await?;
         let update = Update::from_finalized_header(finalized_header);
         Ok(update)
     }
 
+    /// Syncs the light client to the latest finalized header, it resumes from the persisted store
+    /// if there is one, otherwise it bootstraps from the last persisted checkpoint or the
+    /// configured one.
     pub async fn sync(&mut self, base_slot: u64) -> Result<()> {
-        info!(
-            "consensus client sync with checkpoint: 0x{}",
-            hex::encode(&self.initial_checkpoint)
-        );
-        self.bootstrap(base_slot).await?;
+        if self.restore_store(base_slot)? {
+            info!(
+                "consensus client resumes from the persisted store at slot {}",
+                self.store.finalized_header.slot
+            );
+        } else {
+            self.bootstrap(base_slot).await?;
+        }
 
         let current_period = calc_sync_period(self.store.finalized_header.slot);
         let updates = self
@@ -269,7 +530,7 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
 
         for update in updates {
             self.verify_update(&update)?;
-            self.apply_update(&update);
+            self.apply_update(&update)?;
             if update.finalized_header.slot > base_slot {
                 self.store_updates_until_finality_update(&update.into())
                     .await?;
@@ -278,13 +539,13 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
 
         let finality_update = self.rpc.get_finality_update().await?;
         self.verify_finality_update(&finality_update)?;
-        self.apply_finality_update(&finality_update);
+        self.apply_finality_update(&finality_update)?;
         self.store_updates_until_finality_update(&finality_update)
             .await?;
 
         let optimistic_update = self.rpc.get_optimistic_update().await?;
         self.verify_optimistic_update(&optimistic_update)?;
-        self.apply_optimistic_update(&optimistic_update);
+        self.apply_optimistic_update(&optimistic_update)?;
 
         info!(
             "consensus client has already synced with slots [{base_slot}, {}]",
@@ -297,11 +558,13 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
         let previous_finality_slot = self.get_finalized_header().slot;
         let finality_update = self.rpc.get_finality_update().await?;
         self.verify_finality_update(&finality_update)?;
-        self.apply_finality_update(&finality_update);
+        self.apply_finality_update(&finality_update)?;
 
         let optimistic_update = self.rpc.get_optimistic_update().await?;
         self.verify_optimistic_update(&optimistic_update)?;
-        self.apply_optimistic_update(&optimistic_update);
+        self.apply_optimistic_update(&optimistic_update)?;
+
+        self.process_force_update()?;
 
         if self.store.next_sync_committee.is_none() {
             debug!("checking for sync committee update");
@@ -314,7 +577,7 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
 
                 if res.is_ok() {
                     info!("updating sync committee");
-                    self.apply_update(update);
+                    self.apply_update(update)?;
                 }
             }
         }
@@ -324,25 +587,289 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
         Ok(previous_finality_slot < self.get_finalized_header().slot)
     }
 
-    pub async fn bootstrap(&mut self, base_slot: u64) -> Result<()> {
+    /// Compares the storage with the on-chain light client and repairs the storage if they are
+    /// not aligned anymore, returns whether the storage is changed.
+    ///
+    /// - if the on-chain minimal slot is moved, the storage is re-initialized from the new one
+    /// - if the on-chain maximal slot goes backwards, the storage is rolled back to it
+    /// - if the MMR root is different from the on-chain one, the storage is rolled back to the
+    ///   slot before the first diverging one, or re-initialized if it can't be found, unless the
+    ///   headers from the consensus rpc can't reproduce the on-chain root either. It's an error if
+    ///   the root is still different after the storage is re-initialized for the same client
+    ///
+    /// The integrity of the storage is checked here once per call, after the slots are aligned.
+    pub async fn align_with_onchain_client(&mut self, client: &core::Client) -> Result<bool> {
+        let last_maximal_slot = self.onchain_maximal_slot.replace(client.maximal_slot);
+        let (base_slot, tip_slot) = match self.storage_slot_range()? {
+            (Some(base_slot), Some(tip_slot)) => (base_slot, tip_slot),
+            _ => return Ok(false),
+        };
+        if base_slot != client.minimal_slot {
+            warn!(
+                "on-chain minimal slot is moved from {base_slot} to {}, re-initialize storage",
+                client.minimal_slot
+            );
+            self.reinitialize_storage(client.minimal_slot).await?;
+            return Ok(true);
+        }
+        let mut changed = false;
+        if let Some(last_maximal_slot) = last_maximal_slot {
+            if client.maximal_slot < last_maximal_slot && client.maximal_slot < tip_slot {
+                warn!(
+                    "on-chain maximal slot goes backwards from {last_maximal_slot} to {}, \
+                    roll back storage from tip slot {tip_slot}",
+                    client.maximal_slot
+                );
+                self.storage().rollback_to(Some(client.maximal_slot))?;
+                changed = true;
+            }
+        }
+        if tip_slot >= client.maximal_slot {
+            let integrity = self.check_storage_integrity(client).await?;
+            match integrity.first_diverging_slot {
+                _ if integrity.matched => {}
+                Some(slot) if slot > base_slot => {
+                    warn!(
+                        "stored header of slot {slot} diverges from on-chain client {client}, \
+                        roll back storage from tip slot {tip_slot}"
+                    );
+                    self.storage().rollback_to(Some(slot - 1))?;
+                    changed = true;
+                }
+                None if integrity.reproducible == Some(false) => {
+                    // the stored headers may be right, so back off until the headers are fetched
+                    // from the consensus rpc again
+                    warn!(
+                        "MMR root of storage is different from on-chain client {client}, which \
+                        can't be reproduced by the consensus rpc either, check again later"
+                    );
+                }
+                _ => {
+                    let client_slots = (client.minimal_slot, client.maximal_slot);
+                    if self.reinitialized_client_slots == Some(client_slots) {
+                        return Err(eyre!(
+                            "MMR root of storage is still different from on-chain client \
+                            {client} after re-initialization"
+                        ));
+                    }
+                    warn!(
+                        "MMR root of storage is different from on-chain client {client}, \
+                        re-initialize storage"
+                    );
+                    self.reinitialize_storage(client.minimal_slot).await?;
+                    self.reinitialized_client_slots = Some(client_slots);
+                    return Ok(true);
+                }
+            }
+        }
+        if changed {
+            let finalized_header = self.store.finalized_header.clone();
+            self.store_updates_until_header(&finalized_header).await?;
+        }
+        Ok(changed)
+    }
+
+    /// Checks the stored headers against the MMR root of the on-chain client, and finds the first
+    /// diverging slot if they're different. The stored headers must cover the client.
+    pub async fn check_storage_integrity(&self, client: &core::Client) -> Result<StorageIntegrity> {
+        self.integrity_checker().check(client).await
+    }
+
+    /// Returns the checker of the stored headers, which shares the headers fetched for the checks
+    /// with this client.
+    pub fn integrity_checker(&self) -> IntegrityChecker<R> {
+        IntegrityChecker::new(
+            self.rpc.clone(),
+            self.storage().clone(),
+            self.config.backfill.clone(),
+            self.integrity_references.clone(),
+        )
+    }
+
+    /// Imports the headers of the on-chain client from the snapshot file if the storage is empty,
+    /// returns false if the storage is initialized already.
+    pub fn import_snapshot(&self, path: &Path, client: &core::Client) -> Result<bool> {
+        if self.storage().is_initialized()? {
+            return Ok(false);
+        }
+        let snapshot = snapshot::read_snapshot(path)?;
+        self.storage().import_snapshot(&snapshot, client)?;
+        Ok(true)
+    }
+
+    // clear the storage and store headers from `base_slot` to the finalized slot again
+    async fn reinitialize_storage(&mut self, base_slot: u64) -> Result<()> {
+        self.storage().rollback_to(None)?;
+        self.store.base_slot = base_slot;
+        self.initialize_storage(base_slot)?;
+        let finalized_header = self.store.finalized_header.clone();
+        self.store_updates_until_header(&finalized_header).await
+    }
+
+    // only a verified base header is stored here, otherwise the base header is authenticated and
+    // stored along with the following headers
+    fn initialize_storage(&mut self, base_slot: u64) -> Result<()> {
+        if self.store.finalized_header.slot != base_slot {
+            return Ok(());
+        }
+        debug!("initialize mmr stroage for base slot {base_slot}");
+        let update = Update::from_finalized_header(self.store.finalized_header.clone());
+        self.store_finalized_update_batch(&[update])
+    }
+
+    // the storage is cleared if the on-chain minimal slot is moved forwards from its base slot,
+    // while a minimal slot before the base slot means another on-chain client is configured
+    fn check_stored_base_slot(&self, base_slot: u64) -> Result<()> {
         if let Some(stored_base_slot) = self.storage().get_base_beacon_header_slot()? {
+            if stored_base_slot > base_slot {
+                return Err(
+                    ConsensusError::StoredBaseSlotMismatch(stored_base_slot, base_slot).into(),
+                );
+            }
             if stored_base_slot != base_slot {
-                panic!(
-                    "The minimal slot in the light client cell on CKB chain \
-                    is not the same as the cached minimal slot in the storage.\n\
-                    Please check if the configured light client cell is correct! \n\
-                    If you have changed the light client cell, you should delete the local storage."
+                warn!(
+                    "on-chain minimal slot {base_slot} is different from the stored base slot \
+                    {stored_base_slot}, re-initialize storage"
                 );
+                self.storage().rollback_to(None)?;
+            }
+        }
+        Ok(())
+    }
+
+    // restores the light client store persisted by the last run, returns false if there is none
+    // or it is too old to be trusted
+    fn restore_store(&mut self, base_slot: u64) -> Result<bool> {
+        let bytes = match self.storage().get_light_client_store()? {
+            Some(bytes) => bytes,
+            None => return Ok(false),
+        };
+        let mut store = LightClientStore {
+            base_slot,
+            finalized_header: Default::default(),
+            current_sync_committee: Default::default(),
+            next_sync_committee: None,
+            next_sync_committee_branch: None,
+            optimistic_header: Default::default(),
+            previous_max_active_participants: 0,
+            current_max_active_participants: 0,
+            best_valid_update: None,
+            forced_finalized_slot: None,
+            storage: self.store.storage.clone(),
+        };
+        if let Err(err) = store.restore_from_slice(&bytes) {
+            warn!("discard the persisted light client store: {err}");
+            return Ok(false);
+        }
+        if !self.is_valid_checkpoint(store.finalized_header.slot) {
+            if self.config.strict_checkpoint_age {
+                warn!("the persisted light client store is too old, bootstrap again");
+                return Ok(false);
+            } else {
+                warn!("the persisted light client store is too old");
+            }
+        }
+        self.check_stored_base_slot(base_slot)?;
+        self.store = store;
+        Ok(true)
+    }
+
+    fn persist_store(&self) -> Result<()> {
+        let bytes = self.store.to_bytes()?;
+        self.storage().put_light_client_store(&bytes)?;
+        if let Some(checkpoint) = &self.last_checkpoint {
+            self.storage().put_last_checkpoint(checkpoint)?;
+        }
+        Ok(())
+    }
+
+    /// Bootstraps the light client from the last persisted checkpoint, or the configured one if
+    /// there is no persisted checkpoint or the bootstrap of it is not available anymore.
+    ///
+    /// If the configured checkpoint is too old or newer than `base_slot`, a checkpoint finalized
+    /// no later than `base_slot` is fetched from the checkpoint fallback services instead. The
+    /// services only list the recent epochs, so an old `base_slot` fails with the oldest listed
+    /// slot in the error, and a checkpoint of it has to be configured explicitly.
+    pub async fn bootstrap(&mut self, base_slot: u64) -> Result<()> {
+        self.check_stored_base_slot(base_slot)?;
+
+        if let Some(checkpoint) = self.last_checkpoint.clone() {
+            match self
+                .bootstrap_with_checkpoint(base_slot, &checkpoint, false)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(err) => warn!(
+                    "failed to bootstrap from the persisted checkpoint 0x{}: {err}",
+                    hex::encode(&checkpoint)
+                ),
+            }
+        }
+        let checkpoint = self.initial_checkpoint.clone();
+        let err = match self
+            .bootstrap_with_checkpoint(base_slot, &checkpoint, true)
+            .await
+        {
+            Err(err) if is_rejected_checkpoint(&err) && self.has_checkpoint_fallback() => err,
+            result => return result,
+        };
+        warn!("configured checkpoint is rejected: {err}, fetch one from the fallback services");
+        let checkpoint = self.fetch_fallback_checkpoint(base_slot).await?;
+        self.bootstrap_with_checkpoint(base_slot, &checkpoint, true)
+            .await
+    }
+
+    fn has_checkpoint_fallback(&self) -> bool {
+        self.config.fallback.is_some() || self.config.load_external_fallback
+    }
+
+    // fetches the latest checkpoint finalized no later than `base_slot` from the explicit
+    // fallback service, or the healthy external ones if it fails or is absent
+    async fn fetch_fallback_checkpoint(&self, base_slot: u64) -> Result<Vec<u8>> {
+        if let Some(fallback) = &self.config.fallback {
+            match CheckpointFallback::fetch_checkpoint_before_from_api(fallback, base_slot).await {
+                Ok((slot, checkpoint)) => {
+                    info!("fetched checkpoint {checkpoint:?} of slot {slot} from {fallback}");
+                    return Ok(checkpoint.as_bytes().to_vec());
+                }
+                Err(err) if self.config.load_external_fallback => {
+                    warn!("failed to fetch checkpoint from {fallback}: {err}");
+                }
+                Err(err) => return Err(err),
             }
         }
+        let chain_id = self.config.chain.chain_id;
+        let network = Network::from_chain_id(chain_id).ok_or_else(|| {
+            eyre!("no external checkpoint fallback services for chain {chain_id}")
+        })?;
+        let (slot, checkpoint) = CheckpointFallback::new()
+            .build()
+            .await?
+            .fetch_checkpoint_before(&network, base_slot)
+            .await?;
+        info!("fetched checkpoint {checkpoint:?} of slot {slot} from external fallback services");
+        Ok(checkpoint.as_bytes().to_vec())
+    }
 
+    // the persisted checkpoint is not required to be finalized no later than `base_slot`, since
+    // the stored headers are linked backwards from the verified finalized header anyway
+    async fn bootstrap_with_checkpoint(
+        &mut self,
+        base_slot: u64,
+        checkpoint: &[u8],
+        check_base_slot: bool,
+    ) -> Result<()> {
+        info!(
+            "consensus client bootstrap with checkpoint: 0x{}",
+            hex::encode(checkpoint)
+        );
         let mut bootstrap = self
             .rpc
-            .get_bootstrap(&self.initial_checkpoint)
+            .get_bootstrap(checkpoint)
             .await
             .map_err(|_| eyre!("could not fetch bootstrap"))?;
 
-        if bootstrap.header.slot > base_slot {
+        if check_base_slot && bootstrap.header.slot > base_slot {
             return Err(ConsensusError::CheckpointTooNew.into());
         }
 
@@ -363,7 +890,7 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
         );
 
         let header_hash = bootstrap.header.hash_tree_root()?.to_string();
-        let expected_hash = format!("0x{}", hex::encode(&self.initial_checkpoint));
+        let expected_hash = format!("0x{}", hex::encode(checkpoint));
         let header_valid = header_hash == expected_hash;
 
         if !header_valid {
@@ -381,17 +908,10 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
 
         // force to initialize mmr storage with on-chain base slot
         if !self.storage().is_initialized()? {
-            debug!("initialize mmr stroage in bootstrap for base slot {base_slot}");
-            let header = self
-                .rpc
-                .get_header(base_slot)
-                .await?
-                .expect("forked or skipped base slot");
-            let update = Update::from_finalized_header(header);
-            self.store_finalized_update_batch(&[update])?;
+            self.initialize_storage(base_slot)?;
         }
 
-        Ok(())
+        self.persist_store()
     }
 
     // implements checks from validate_light_client_update and process_light_client_update in the
@@ -499,11 +1019,21 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
         self.verify_generic_update(&mut update)
     }
 
-    // implements state changes from apply_light_client_update and process_light_client_update in
-    // the specification
-    fn apply_generic_update(&mut self, update: GenericUpdate) {
+    // implements state changes from process_light_client_update in the specification, returns
+    // whether the finalized header or the sync committees are changed
+    fn apply_generic_update(&mut self, update: GenericUpdate) -> bool {
         let committee_bits = get_bits(&update.sync_aggregate.sync_committee_bits);
 
+        let is_best_valid_update = self
+            .store
+            .best_valid_update
+            .as_ref()
+            .map(|best_valid_update| is_better_update(&update, best_valid_update))
+            .unwrap_or(true);
+        if is_best_valid_update {
+            self.store.best_valid_update = Some(update.clone());
+        }
+
         self.store.current_max_active_participants =
             u64::max(self.store.current_max_active_participants, committee_bits);
 
@@ -530,7 +1060,7 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
             && update_finalized_period == update_attested_period;
 
         let should_apply_update = {
-            let has_majority = committee_bits * 3 >= 512 * 2;
+            let has_majority = has_supermajority(committee_bits);
             let update_is_newer = update_finalized_slot > self.store.finalized_header.slot;
             let good_update = update_is_newer || update_has_finalized_next_committee;
 
@@ -538,50 +1068,119 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
         };
 
         if should_apply_update {
-            let store_period = calc_sync_period(self.store.finalized_header.slot);
+            self.store.best_valid_update = None;
+            self.apply_light_client_update(update, false)
+        } else {
+            false
+        }
+    }
 
-            if self.store.next_sync_committee.is_none() {
-                self.store.next_sync_committee = update.next_sync_committee;
-                self.store.next_sync_committee_branch = update.next_sync_committee_branch;
-            } else if update_finalized_period == store_period + 1 {
-                self.store.current_sync_committee = self.store.next_sync_committee.clone().unwrap();
-                self.store.next_sync_committee = update.next_sync_committee;
-                self.store.next_sync_committee_branch = update.next_sync_committee_branch;
-                self.store.previous_max_active_participants =
-                    self.store.current_max_active_participants;
-                self.store.current_max_active_participants = 0;
-            }
+    // implements apply_light_client_update in the specification, a forced update moves the
+    // finalized header without real finality. returns whether the finalized header or the sync
+    // committees are changed
+    fn apply_light_client_update(&mut self, update: GenericUpdate, forced: bool) -> bool {
+        let update_finalized_slot = update
+            .finalized_header
+            .as_ref()
+            .map(|h| h.slot)
+            .unwrap_or(0);
+        let update_finalized_period = calc_sync_period(update_finalized_slot);
+        let store_period = calc_sync_period(self.store.finalized_header.slot);
+        let mut changed = false;
+
+        if self.store.next_sync_committee.is_none() {
+            changed = update.next_sync_committee.is_some();
+            self.store.next_sync_committee = update.next_sync_committee;
+            self.store.next_sync_committee_branch = update.next_sync_committee_branch;
+        } else if update_finalized_period == store_period + 1 {
+            changed = true;
+            self.store.current_sync_committee = self.store.next_sync_committee.clone().unwrap();
+            self.store.next_sync_committee = update.next_sync_committee;
+            self.store.next_sync_committee_branch = update.next_sync_committee_branch;
+            self.store.previous_max_active_participants =
+                self.store.current_max_active_participants;
+            self.store.current_max_active_participants = 0;
+        }
 
-            if update_finalized_slot > self.store.finalized_header.slot {
-                self.store.finalized_header = update.finalized_header.unwrap();
+        if update_finalized_slot > self.store.finalized_header.slot {
+            changed = true;
+            self.store.finalized_header = update.finalized_header.unwrap();
 
+            if forced {
+                self.store.forced_finalized_slot = Some(self.store.finalized_header.slot);
+            } else {
+                self.store.forced_finalized_slot = None;
                 if self.store.finalized_header.slot % 32 == 0 {
                     let checkpoint_res = self.store.finalized_header.hash_tree_root();
                     if let Ok(checkpoint) = checkpoint_res {
                         self.last_checkpoint = Some(checkpoint.as_bytes().to_vec());
                     }
                 }
+            }
 
-                if self.store.finalized_header.slot > self.store.optimistic_header.slot {
-                    self.store.optimistic_header = self.store.finalized_header.clone();
-                }
+            if self.store.finalized_header.slot > self.store.optimistic_header.slot {
+                self.store.optimistic_header = self.store.finalized_header.clone();
             }
         }
+        changed
+    }
+
+    // implements process_light_client_store_force_update in the specification, the best valid
+    // update is applied if there's no finality for the update timeout, so the client can move on
+    // to the later sync committee periods
+    fn process_force_update(&mut self) -> Result<()> {
+        let finalized_slot = self.store.finalized_header.slot;
+        if self.expected_current_slot() <= finalized_slot + UPDATE_TIMEOUT {
+            return Ok(());
+        }
+        let mut update = match self.store.best_valid_update.take() {
+            Some(update) => update,
+            None => return Ok(()),
+        };
+        let update_finalized_slot = update
+            .finalized_header
+            .as_ref()
+            .map(|h| h.slot)
+            .unwrap_or(0);
+        // the attested header is treated as the finalized one in extended periods of
+        // non-finality
+        if update_finalized_slot <= finalized_slot {
+            update.finalized_header = Some(update.attested_header.clone());
+        }
+        warn!(
+            "no finality since slot {finalized_slot} for the update timeout, force to update \
+            the finalized header to slot {} without finality",
+            update
+                .finalized_header
+                .as_ref()
+                .map(|h| h.slot)
+                .unwrap_or(0)
+        );
+        if self.apply_light_client_update(update, true) {
+            self.persist_store()?;
+        }
+        Ok(())
+    }
+
+    // the store is persisted only if the finalized header or the sync committees are changed,
+    // the optimistic header and the best valid update are recovered from the rpc after restarts
+    fn apply_and_persist_update(&mut self, update: GenericUpdate) -> Result<()> {
+        if self.apply_generic_update(update) {
+            self.persist_store()?;
+        }
+        Ok(())
     }
 
-    fn apply_update(&mut self, update: &Update) {
-        let update = GenericUpdate::from(update);
-        self.apply_generic_update(update);
+    fn apply_update(&mut self, update: &Update) -> Result<()> {
+        self.apply_and_persist_update(GenericUpdate::from(update))
     }
 
-    fn apply_finality_update(&mut self, update: &FinalityUpdate) {
-        let update = GenericUpdate::from(update);
-        self.apply_generic_update(update);
+    fn apply_finality_update(&mut self, update: &FinalityUpdate) -> Result<()> {
+        self.apply_and_persist_update(GenericUpdate::from(update))
     }
 
-    fn apply_optimistic_update(&mut self, update: &OptimisticUpdate) {
-        let update = GenericUpdate::from(update);
-        self.apply_generic_update(update);
+    fn apply_optimistic_update(&mut self, update: &OptimisticUpdate) -> Result<()> {
+        self.apply_and_persist_update(GenericUpdate::from(update))
     }
 
     fn has_finality_update(&self, update: &GenericUpdate) -> bool {
@@ -675,9 +1274,130 @@ impl<R: ConsensusRpc> ConsensusClient<R> {
     }
 }
 
+// implements is_better_update in the specification
+fn is_better_update(new_update: &GenericUpdate, old_update: &GenericUpdate) -> bool {
+    let new_active_participants = get_bits(&new_update.sync_aggregate.sync_committee_bits);
+    let old_active_participants = get_bits(&old_update.sync_aggregate.sync_committee_bits);
+    let new_has_supermajority = has_supermajority(new_active_participants);
+    let old_has_supermajority = has_supermajority(old_active_participants);
+    if new_has_supermajority != old_has_supermajority {
+        return new_has_supermajority;
+    }
+    if !new_has_supermajority && new_active_participants != old_active_participants {
+        return new_active_participants > old_active_participants;
+    }
+
+    // compare presence of relevant sync committee
+    let has_relevant_sync_committee = |update: &GenericUpdate| {
+        update.next_sync_committee.is_some()
+            && calc_sync_period(update.attested_header.slot)
+                == calc_sync_period(update.signature_slot)
+    };
+    let new_has_relevant_sync_committee = has_relevant_sync_committee(new_update);
+    if new_has_relevant_sync_committee != has_relevant_sync_committee(old_update) {
+        return new_has_relevant_sync_committee;
+    }
+
+    // compare indication of any finality
+    let new_has_finality = new_update.finality_branch.is_some();
+    if new_has_finality != old_update.finality_branch.is_some() {
+        return new_has_finality;
+    }
+
+    // compare sync committee finality
+    if new_has_finality {
+        let has_sync_committee_finality = |update: &GenericUpdate| {
+            let finalized_slot = update.finalized_header.as_ref().map(|h| h.slot);
+            finalized_slot.map(calc_sync_period)
+                == Some(calc_sync_period(update.attested_header.slot))
+        };
+        let new_has_sync_committee_finality = has_sync_committee_finality(new_update);
+        if new_has_sync_committee_finality != has_sync_committee_finality(old_update) {
+            return new_has_sync_committee_finality;
+        }
+    }
+
+    // tiebreaker 1: sync committee participation beyond supermajority
+    if new_active_participants != old_active_participants {
+        return new_active_participants > old_active_participants;
+    }
+
+    // tiebreaker 2: prefer older data (fewer changes to best)
+    if new_update.attested_header.slot != old_update.attested_header.slot {
+        return new_update.attested_header.slot < old_update.attested_header.slot;
+    }
+    new_update.signature_slot < old_update.signature_slot
+}
+
+fn is_rejected_checkpoint(err: &eyre::Report) -> bool {
+    matches!(
+        err.downcast_ref::<ConsensusError>(),
+        Some(ConsensusError::CheckpointTooOld | ConsensusError::CheckpointTooNew)
+    )
+}
+
+fn header_root(header: &Header) -> Result<Vec<u8>> {
+    let root = header.clone().hash_tree_root()?;
+    Ok(root.as_bytes().to_vec())
+}
+
+/// Authenticates the headers of consecutive slots by linking their parent roots backwards from the
+/// last one, which must be verified already. Empty headers stand for skipped slots, which are
+/// never referred by parent roots.
+struct BackwardLinker {
+    next_slot: Option<u64>,
+    expected_root: Option<Vec<u8>>,
+}
+
+impl BackwardLinker {
+    fn new(last_slot: u64) -> Self {
+        Self {
+            next_slot: Some(last_slot),
+            expected_root: None,
+        }
+    }
+
+    fn link(&mut self, update: &Update) -> Result<()> {
+        let slot = self
+            .next_slot
+            .ok_or_else(|| eyre!("no header to link before slot 0"))?;
+        let header = &update.finalized_header;
+        if header.slot != slot {
+            return Err(ConsensusError::InvalidHeaderSlot(slot, header.slot).into());
+        }
+        self.next_slot = slot.checked_sub(1);
+        if update.is_finalized_empty() {
+            return Ok(());
+        }
+        let root = header_root(header)?;
+        if let Some(expected_root) = &self.expected_root {
+            if &root != expected_root {
+                return Err(ConsensusError::UnlinkedHeader(
+                    slot,
+                    format!("0x{}", hex::encode(&root)),
+                    format!("0x{}", hex::encode(expected_root)),
+                )
+                .into());
+            }
+        }
+        self.expected_root = Some(header.parent_root.to_vec());
+        Ok(())
+    }
+
+    // the parent root of the earliest non-empty linked header, which is left to be linked
+    fn parent_root(self) -> Result<Vec<u8>> {
+        self.expected_root.ok_or_else(|| eyre!("no header to link"))
+    }
+}
+
+// at least two thirds of the sync committee participate
+fn has_supermajority(active_participants: u64) -> bool {
+    active_participants * 3 >= SYNC_COMMITTEE_SIZE as u64 * 2
+}
+
 fn get_participating_keys(
     committee: &SyncCommittee,
-    bitfield: &Bitvector<512>,
+    bitfield: &Bitvector<SYNC_COMMITTEE_SIZE>,
 ) -> Result<Vec<PublicKey>> {
     let mut pks: Vec<PublicKey> = Vec::new();
     bitfield.iter().enumerate().for_each(|(i, bit)| {
@@ -691,7 +1411,7 @@ fn get_participating_keys(
     Ok(pks)
 }
 
-fn get_bits(bitfield: &Bitvector<512>) -> u64 {
+fn get_bits(bitfield: &Bitvector<SYNC_COMMITTEE_SIZE>) -> u64 {
     let mut count = 0;
     bitfield.iter().for_each(|bit| {
         if bit == true {
@@ -743,26 +1463,41 @@ mod tests {
     use std::{path::PathBuf, sync::Arc};
     use tempfile::TempDir;
 
-    use crate::constants::MAX_REQUEST_LIGHT_CLIENT_UPDATES;
+    use crate::constants::{MAX_REQUEST_LIGHT_CLIENT_UPDATES, SYNC_COMMITTEE_SIZE};
+    use eth_light_client_in_ckb_verification::{
+        mmr,
+        types::{core, packed},
+    };
     use ssz_rs::Vector;
+    use storage::prelude::{StorageAsMMRStore as _, StorageReader as _, StorageWriter as _};
 
     use crate::{
-        consensus::calc_sync_period,
+        consensus::{calc_sync_period, is_better_update, BackwardLinker},
         errors::ConsensusError,
         rpc::{mock_rpc::MockRpc, ConsensusRpc},
-        types::Header,
+        types::{GenericUpdate, Header, Update},
         ConsensusClient,
     };
-    use config::{networks, Config};
+    use config::{networks, Config, StorageBackend};
 
-    async fn get_client(strict_checkpoint_age: bool, path: PathBuf) -> ConsensusClient<MockRpc> {
+    // the storage is kept in memory if `path` is absent
+    async fn get_client(
+        strict_checkpoint_age: bool,
+        path: Option<PathBuf>,
+    ) -> ConsensusClient<MockRpc> {
         let base_config = networks::goerli();
+        let storage_backend = if path.is_some() {
+            StorageBackend::RocksDB
+        } else {
+            StorageBackend::Memory
+        };
         let config = Config {
             consensus_rpc: String::new(),
             execution_rpc: String::new(),
             chain: base_config.chain,
             forks: base_config.forks,
-            storage_path: path,
+            storage_path: path.unwrap_or_default(),
+            storage_backend,
             strict_checkpoint_age,
             ..Default::default()
         };
@@ -776,10 +1511,92 @@ mod tests {
         client
     }
 
+    // serves the checkpointz slots api with `slots` as (slot, block root) pairs
+    async fn start_checkpointz(slots: Vec<(u64, [u8; 32])>) -> String {
+        use config::checkpoints::{RawSlotResponse, RawSlotResponseData, Slot};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let slots = slots
+            .into_iter()
+            .map(|(slot, root)| Slot {
+                slot,
+                block_root: Some(root.into()),
+                epoch: slot / 32,
+                ..Default::default()
+            })
+            .collect();
+        let body = serde_json::to_string(&RawSlotResponse {
+            data: RawSlotResponseData { slots },
+        })
+        .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![];
+                let mut chunk = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut chunk).await {
+                        Ok(n) if n > 0 => request.extend_from_slice(&chunk[..n]),
+                        _ => break,
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        url
+    }
+
+    fn fallback_client(fallback: String) -> ConsensusClient<MockRpc> {
+        let base_config = networks::goerli();
+        let config = Config {
+            consensus_rpc: String::new(),
+            execution_rpc: String::new(),
+            chain: base_config.chain,
+            forks: base_config.forks,
+            storage_backend: StorageBackend::Memory,
+            fallback: Some(fallback),
+            ..Default::default()
+        };
+        ConsensusClient::new("testdata/", &[1u8; 32], Arc::new(config)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_with_fallback_checkpoint() {
+        // the bootstrap in testdata is of slot 3781056, which is too new for the base slot
+        let base_slot = 3781000;
+        let url = start_checkpointz(vec![(3780960, [2u8; 32]), (3781056, [3u8; 32])]).await;
+        let mut client = fallback_client(url);
+        let err = client.bootstrap(base_slot).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ConsensusError>(),
+            Some(ConsensusError::CheckpointTooNew)
+        ));
+        // the configured checkpoint is rejected, then the one before the base slot is tried
+        assert_eq!(
+            client.rpc.bootstrap_roots(),
+            vec![vec![1u8; 32], vec![2u8; 32]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_with_recent_fallback_checkpoints() {
+        let base_slot = 3781000;
+        let url = start_checkpointz(vec![(3781056, [3u8; 32])]).await;
+        let mut client = fallback_client(url);
+        let err = client.bootstrap(base_slot).await.unwrap_err();
+        assert!(err.to_string().contains("oldest checkpoint listed"));
+        assert_eq!(client.rpc.bootstrap_roots(), vec![vec![1u8; 32]]);
+    }
+
     #[tokio::test]
     async fn test_verify_update() {
-        let storage = TempDir::new().unwrap();
-        let client = get_client(false, storage.into_path()).await;
+        let client = get_client(false, None).await;
         let period = calc_sync_period(client.store.finalized_header.slot);
         let updates = client
             .rpc
@@ -793,8 +1610,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_update_invalid_committee() {
-        let storage = TempDir::new().unwrap();
-        let client = get_client(false, storage.into_path()).await;
+        let client = get_client(false, None).await;
         let period = calc_sync_period(client.store.finalized_header.slot);
         let updates = client
             .rpc
@@ -814,8 +1630,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_update_invalid_finality() {
-        let storage = TempDir::new().unwrap();
-        let client = get_client(false, storage.into_path()).await;
+        let client = get_client(false, None).await;
         let period = calc_sync_period(client.store.finalized_header.slot);
         let updates = client
             .rpc
@@ -835,8 +1650,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_update_invalid_sig() {
-        let storage = TempDir::new().unwrap();
-        let client = get_client(false, storage.into_path()).await;
+        let client = get_client(false, None).await;
         let period = calc_sync_period(client.store.finalized_header.slot);
         let updates = client
             .rpc
@@ -856,8 +1670,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_finality() {
-        let storage = TempDir::new().unwrap();
-        let mut client = get_client(false, storage.into_path()).await;
+        let mut client = get_client(false, None).await;
         client.sync(3781056).await.unwrap();
 
         let update = client.rpc.get_finality_update().await.unwrap();
@@ -867,8 +1680,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_finality_invalid_finality() {
-        let storage = TempDir::new().unwrap();
-        let mut client = get_client(false, storage.into_path()).await;
+        let mut client = get_client(false, None).await;
         client.sync(3781056).await.unwrap();
 
         let mut update = client.rpc.get_finality_update().await.unwrap();
@@ -883,8 +1695,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_finality_invalid_sig() {
-        let storage = TempDir::new().unwrap();
-        let mut client = get_client(false, storage.into_path()).await;
+        let mut client = get_client(false, None).await;
         client.sync(3781056).await.unwrap();
 
         let mut update = client.rpc.get_finality_update().await.unwrap();
@@ -899,8 +1710,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_optimistic() {
-        let storage = TempDir::new().unwrap();
-        let mut client = get_client(false, storage.into_path()).await;
+        let mut client = get_client(false, None).await;
         client.sync(3781056).await.unwrap();
 
         let update = client.rpc.get_optimistic_update().await.unwrap();
@@ -909,8 +1719,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_verify_optimistic_invalid_sig() {
-        let storage = TempDir::new().unwrap();
-        let mut client = get_client(false, storage.into_path()).await;
+        let mut client = get_client(false, None).await;
         client.sync(3781056).await.unwrap();
 
         let mut update = client.rpc.get_optimistic_update().await.unwrap();
@@ -926,7 +1735,422 @@ mod tests {
     #[tokio::test]
     #[should_panic]
     async fn test_verify_checkpoint_age_invalid() {
+        get_client(true, None).await;
+    }
+
+    #[tokio::test]
+    async fn test_force_update() {
+        let mut client = get_client(false, None).await;
+        client.sync(3781056).await.unwrap();
+        let (_, tip_slot) = client.storage_slot_range().unwrap();
+
+        let update = client.rpc.get_optimistic_update().await.unwrap();
+        let attested_header = update.attested_header.clone();
+        assert!(attested_header.slot > client.store.finalized_header.slot);
+        client.store.best_valid_update = Some(GenericUpdate::from(&update));
+        client.process_force_update().unwrap();
+        assert_eq!(client.store.finalized_header.slot, attested_header.slot);
+        assert_eq!(client.forced_finalized_slot(), Some(attested_header.slot));
+        assert!(client.store.best_valid_update.is_none());
+
+        let err = client
+            .store_finalized_update_batch(&[Update::from_finalized_header(attested_header.clone())])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            ConsensusError::ForceUpdatedHeader(attested_header.slot).to_string()
+        );
+        client
+            .store_updates_until_header(&attested_header)
+            .await
+            .unwrap();
+        assert_eq!(client.storage_slot_range().unwrap().1, tip_slot);
+    }
+
+    #[tokio::test]
+    async fn test_is_better_update_by_supermajority() {
+        let client = get_client(false, None).await;
+        let update = client.rpc.get_optimistic_update().await.unwrap();
+        let with_participants = |participants: usize| {
+            let mut update = GenericUpdate::from(&update);
+            let bits = &mut update.sync_aggregate.sync_committee_bits;
+            for index in 0..SYNC_COMMITTEE_SIZE {
+                bits.set(index, index < participants);
+            }
+            update
+        };
+
+        // two thirds of the sync committee, 342 of 512, is the supermajority
+        let supermajority = with_participants(342);
+        let minority = with_participants(341);
+        assert!(is_better_update(&supermajority, &minority));
+        assert!(!is_better_update(&minority, &supermajority));
+        assert!(is_better_update(&minority, &with_participants(340)));
+    }
+
+    #[tokio::test]
+    async fn test_persist_store_on_finality_only() {
+        let mut client = get_client(false, None).await;
+        client.sync(3781056).await.unwrap();
+        client
+            .storage()
+            .put_light_client_store(b"unchanged")
+            .unwrap();
+
+        // an optimistic update moves neither the finalized header nor the committees
+        let update = client.rpc.get_optimistic_update().await.unwrap();
+        client.apply_optimistic_update(&update).unwrap();
+        assert_eq!(
+            client.storage().get_light_client_store().unwrap(),
+            Some(b"unchanged".to_vec())
+        );
+
+        client.store.best_valid_update = Some(GenericUpdate::from(&update));
+        client.process_force_update().unwrap();
+        let persisted = client.storage().get_light_client_store().unwrap().unwrap();
+        assert_eq!(persisted, client.store.to_bytes().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_backfill_resumes_from_backfilled_headers() {
+        let mut client = get_client(false, None).await;
+        let base_slot = 3781056;
+
+        // a backfill fails halfway, and one of the backfilled headers is tampered
+        for slot in base_slot + 1..=base_slot + 10 {
+            let mut header = client
+                .get_finality_update(slot)
+                .await
+                .unwrap()
+                .finalized_header;
+            if slot == base_slot + 5 {
+                header.proposer_index += 1;
+            }
+            client
+                .storage()
+                .put_backfilled_header(slot, &header)
+                .unwrap();
+        }
+
+        // the backfilled headers are used instead of the ones from rpc, so the tampered one
+        // breaks the link, and it is discarded with the ones after it
+        let err = client.sync(base_slot).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("is not linked to the verified header"));
+        assert_eq!(
+            client.storage().get_backfilled_slot().unwrap(),
+            Some(base_slot + 4)
+        );
+        assert!(client
+            .storage()
+            .get_backfilled_header(base_slot + 5)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            client.storage_slot_range().unwrap(),
+            (Some(base_slot), Some(base_slot))
+        );
+
+        client.sync(base_slot).await.unwrap();
+        assert_eq!(client.storage().get_backfilled_slot().unwrap(), None);
+        assert_eq!(
+            client.storage_slot_range().unwrap().1,
+            Some(client.store.finalized_header.slot)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sync_resumes_from_persisted_store() {
+        let storage = TempDir::new().unwrap();
+        let path = storage.into_path();
+        let (finalized_header, next_sync_committee) = {
+            let mut client = get_client(false, Some(path.clone())).await;
+            client.sync(3781056).await.unwrap();
+            (
+                client.store.finalized_header.clone(),
+                client.store.next_sync_committee.clone(),
+            )
+        };
+
+        // the bootstrap of an unknown checkpoint fails, so the client must be resumed
+        let base_config = networks::goerli();
+        let config = Config {
+            chain: base_config.chain,
+            forks: base_config.forks,
+            storage_path: path,
+            ..Default::default()
+        };
+        let mut client: ConsensusClient<MockRpc> =
+            ConsensusClient::new("testdata/", &[0u8; 32], Arc::new(config)).unwrap();
+        client.sync(3781056).await.unwrap();
+        assert_eq!(client.store.finalized_header.slot, finalized_header.slot);
+        assert_eq!(
+            client.store.finalized_header.state_root,
+            finalized_header.state_root
+        );
+        assert_eq!(
+            client.store.next_sync_committee.is_some(),
+            next_sync_committee.is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repair_torn_writes() {
         let storage = TempDir::new().unwrap();
-        get_client(true, storage.into_path()).await;
+        let path = storage.into_path();
+        let slot_range = {
+            let mut client = get_client(false, Some(path.clone())).await;
+            client.sync(3781056).await.unwrap();
+            // the tip slot was written before the MMR nodes of its headers
+            let slot_range = client.storage_slot_range().unwrap();
+            client
+                .storage()
+                .put_tip_beacon_header_slot(slot_range.1.unwrap() + 3)
+                .unwrap();
+            slot_range
+        };
+
+        let client = get_client(false, Some(path)).await;
+        assert_eq!(client.storage_slot_range().unwrap(), slot_range);
+    }
+
+    #[tokio::test]
+    async fn test_find_first_diverging_slot() {
+        let base_slot = 3781056;
+        let mut client = get_client(false, None).await;
+        client.sync(base_slot).await.unwrap();
+        let mut reference = get_client(false, None).await;
+        reference.sync(base_slot).await.unwrap();
+        let tip_slot = client.storage_slot_range().unwrap().1.unwrap();
+        assert_eq!(
+            client
+                .storage()
+                .find_first_diverging_slot(tip_slot, reference.storage())
+                .unwrap(),
+            None
+        );
+
+        let diverging_slot = base_slot + (tip_slot - base_slot) / 3;
+        let position = mmr::lib::leaf_index_to_pos(diverging_slot - base_slot);
+        let digest = client
+            .storage()
+            .get_beacon_header_digest(position + 1)
+            .unwrap()
+            .unwrap();
+        client
+            .storage()
+            .put_beacon_header_digest(position, &digest)
+            .unwrap();
+        assert_eq!(
+            client
+                .storage()
+                .find_first_diverging_slot(tip_slot, reference.storage())
+                .unwrap(),
+            Some(diverging_slot)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_snapshot() {
+        let storage = TempDir::new().unwrap();
+        let path = storage.into_path();
+        let slot_range = {
+            let mut client = get_client(false, Some(path.clone())).await;
+            client.sync(3781056).await.unwrap();
+            client.storage_slot_range().unwrap()
+        };
+
+        let output = TempDir::new().unwrap().into_path().join("snapshot");
+        let slots = crate::export_snapshot(&path, &output, false).unwrap();
+        assert_eq!((Some(slots.0), Some(slots.1)), slot_range);
+
+        let snapshot = crate::snapshot::read_snapshot(&output).unwrap();
+        assert_eq!((snapshot.base_slot, snapshot.tip_slot), slots);
+        assert_eq!(snapshot.updates_count(), 0);
+    }
+
+    fn link_updates_backwards(updates: &[Update]) -> eyre::Result<Vec<u8>> {
+        let mut linker = BackwardLinker::new(updates.last().unwrap().finalized_header.slot);
+        for update in updates.iter().rev() {
+            linker.link(update)?;
+        }
+        linker.parent_root()
+    }
+
+    #[test]
+    fn test_link_updates_backwards() {
+        let headers: Vec<Header> =
+            serde_json::from_slice(&std::fs::read("testdata/headers.json").unwrap()).unwrap();
+        let updates = headers[..256]
+            .iter()
+            .cloned()
+            .map(Update::from_finalized_header)
+            .collect::<Vec<_>>();
+        let parent_root = link_updates_backwards(&updates).unwrap();
+        assert_eq!(
+            parent_root,
+            updates[0].finalized_header.parent_root.to_vec()
+        );
+
+        // a forged header breaks the link to it
+        let mut forged = updates.clone();
+        forged[100].finalized_header.state_root = Vector::default();
+        let err = link_updates_backwards(&forged).unwrap_err();
+        let slot = forged[100].finalized_header.slot;
+        assert!(err
+            .to_string()
+            .starts_with(&format!("header of slot {slot} is not linked")));
+
+        // a hidden header breaks the link to the one before it
+        let mut hidden = updates.clone();
+        hidden[100] = Update::from_finalized_header(Header {
+            slot,
+            ..Default::default()
+        });
+        let err = link_updates_backwards(&hidden).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with(&format!("header of slot {} is not linked", slot - 1)));
+    }
+
+    fn onchain_client(
+        minimal_slot: u64,
+        maximal_slot: u64,
+        headers_mmr_root: packed::HeaderDigest,
+    ) -> core::Client {
+        use eth_light_client_in_ckb_verification::types::prelude::*;
+        packed::Client::new_builder()
+            .minimal_slot(minimal_slot.pack())
+            .maximal_slot(maximal_slot.pack())
+            .headers_mmr_root(headers_mmr_root)
+            .build()
+            .unpack()
+    }
+
+    fn stored_root(client: &ConsensusClient<MockRpc>, slot: u64) -> packed::HeaderDigest {
+        client
+            .storage()
+            .chain_root_mmr(slot)
+            .unwrap()
+            .get_root()
+            .unwrap()
+    }
+
+    // replaces the stored header digest of `slot` with the one of the next position
+    fn tamper_stored_header(client: &ConsensusClient<MockRpc>, base_slot: u64, slot: u64) {
+        let position = mmr::lib::leaf_index_to_pos(slot - base_slot);
+        let digest = client
+            .storage()
+            .get_beacon_header_digest(position + 1)
+            .unwrap()
+            .unwrap();
+        client
+            .storage()
+            .put_beacon_header_digest(position, &digest)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_align_with_moved_minimal_slot() {
+        let base_slot = 3781056;
+        let mut client = get_client(false, None).await;
+        client.sync(base_slot).await.unwrap();
+        let (_, tip_slot) = client.storage_slot_range().unwrap();
+
+        let minimal_slot = base_slot + 100;
+        let root = stored_root(&client, minimal_slot);
+        let onchain = onchain_client(minimal_slot, minimal_slot + 10, root);
+        assert!(client.align_with_onchain_client(&onchain).await.unwrap());
+        assert_eq!(
+            client.storage_slot_range().unwrap(),
+            (Some(minimal_slot), tip_slot)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_align_with_backward_maximal_slot() {
+        let base_slot = 3781056;
+        let mut client = get_client(false, None).await;
+        client.sync(base_slot).await.unwrap();
+        let tip_slot = client.storage_slot_range().unwrap().1.unwrap();
+
+        let maximal_slot = tip_slot - 10;
+        let onchain = onchain_client(base_slot, tip_slot, stored_root(&client, tip_slot));
+        assert!(!client.align_with_onchain_client(&onchain).await.unwrap());
+        let onchain = onchain_client(base_slot, maximal_slot, stored_root(&client, maximal_slot));
+        // the storage is rolled back to the maximal slot, then stored to the finalized slot again
+        assert!(client.align_with_onchain_client(&onchain).await.unwrap());
+        assert_eq!(
+            client.storage_slot_range().unwrap(),
+            (Some(base_slot), Some(tip_slot))
+        );
+        assert!(client
+            .storage()
+            .is_onchain_client_matched(&onchain)
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_align_with_mismatched_root() {
+        let base_slot = 3781056;
+        let mut client = get_client(false, None).await;
+        client.sync(base_slot).await.unwrap();
+        let tip_slot = client.storage_slot_range().unwrap().1.unwrap();
+        let onchain = onchain_client(base_slot, tip_slot, stored_root(&client, tip_slot));
+
+        // rolled back to the slot before the diverging one
+        tamper_stored_header(&client, base_slot, base_slot + 100);
+        assert!(client.align_with_onchain_client(&onchain).await.unwrap());
+        assert!(client
+            .storage()
+            .is_onchain_client_matched(&onchain)
+            .unwrap());
+
+        // re-initialized if the base slot diverges, but only once for the same client
+        tamper_stored_header(&client, base_slot, base_slot);
+        assert!(client.align_with_onchain_client(&onchain).await.unwrap());
+        assert!(client
+            .storage()
+            .is_onchain_client_matched(&onchain)
+            .unwrap());
+        tamper_stored_header(&client, base_slot, base_slot);
+        assert!(client.align_with_onchain_client(&onchain).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_align_with_unreproducible_root() {
+        let base_slot = 3781056;
+        let mut client = get_client(false, None).await;
+        client.sync(base_slot).await.unwrap();
+        let tip_slot = client.storage_slot_range().unwrap().1.unwrap();
+
+        // the headers from the consensus rpc don't match the on-chain root either, so the storage
+        // is kept as is
+        let onchain = onchain_client(base_slot, tip_slot, stored_root(&client, tip_slot - 1));
+        let integrity = client.check_storage_integrity(&onchain).await.unwrap();
+        assert!(!integrity.matched);
+        assert_eq!(integrity.reproducible, Some(false));
+        assert!(!client.align_with_onchain_client(&onchain).await.unwrap());
+        assert_eq!(
+            client.storage_slot_range().unwrap(),
+            (Some(base_slot), Some(tip_slot))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_with_backward_minimal_slot() {
+        let base_slot = 3781056;
+        let path = TempDir::new().unwrap().into_path();
+        {
+            let mut client = get_client(false, Some(path.clone())).await;
+            client.sync(base_slot).await.unwrap();
+        }
+        let mut client = get_client(false, Some(path)).await;
+        let err = client.sync(base_slot - 1).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ConsensusError>(),
+            Some(ConsensusError::StoredBaseSlotMismatch(_, _))
+        ));
     }
 }
@@ -3,4 +3,9 @@
 // https://github.com/ethereum/consensus-specs/blob/dev/specs/altair/light-client/p2p-interface.md#configuration
 pub const MAX_REQUEST_LIGHT_CLIENT_UPDATES: u8 = 128;
 pub const MAX_REQUEST_RPC_UPDATES: u64 = 32;
-pub const MAX_RPC_RETRY: u8 = 5;
+
+// https://github.com/ethereum/consensus-specs/blob/dev/specs/altair/light-client/sync-protocol.md#constants
+pub const UPDATE_TIMEOUT: u64 = 32 * 256; // slots per sync committee period
+
+// https://github.com/ethereum/consensus-specs/blob/dev/specs/altair/beacon-chain.md#sync-committee
+pub const SYNC_COMMITTEE_SIZE: usize = 512;
@@ -20,12 +20,29 @@ pub enum ConsensusError {
     InvalidSignature,
     #[error("invalid header hash found: {0}, expected: {1}")]
     InvalidHeaderHash(String, String),
+    #[error("header of slot {0} is not linked to the verified header, root: {1}, expected: {2}")]
+    UnlinkedHeader(u64, String, String),
+    #[error("header of slot {1} is returned for slot {0}")]
+    InvalidHeaderSlot(u64, u64),
+    #[error("header of slot {0} is force updated without finality")]
+    ForceUpdatedHeader(u64),
+    #[error("consensus rpc endpoints mismatch on {0}: {1}")]
+    RpcMismatch(String, String),
+    #[error("no consensus rpc endpoint")]
+    NoRpcEndpoint,
+    #[error("consensus rpc quorum {0} is out of [1, {1}], the number of endpoints")]
+    InvalidRpcQuorum(usize, usize),
     #[error("payload not found for slot: {0}")]
     PayloadNotFound(u64),
     #[error("checkpoint is too old")]
     CheckpointTooOld,
     #[error("checkpoint is too new")]
     CheckpointTooNew,
+    #[error(
+        "on-chain minimal slot {1} is before the stored base slot {0}, check if the configured \
+        light client cell is correct, or delete the storage if it's changed"
+    )]
+    StoredBaseSlotMismatch(u64, u64),
     #[error("storage error: {0}")]
     StorageError(#[from] storage::error::Error),
 }
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use config::BackfillConfig;
+use eth2_types::MainnetEthSpec;
+use eth_light_client_in_ckb_verification::{
+    mmr::{
+        lib::{Error as MMRError, MMRStore, Result as MMRResult},
+        ClientRootMMR,
+    },
+    types::{core, packed, prelude::*},
+};
+use eyre::Result;
+use futures::StreamExt as _;
+use log::warn;
+use serde::Serialize;
+use storage::{prelude::StorageAsMMRStore as _, Storage};
+
+use crate::backfill::fetch_header;
+use crate::rpc::ConsensusRpc;
+
+// the headers of an on-chain client whose MMR root can't be reproduced by the consensus rpc are
+// not fetched again until the interval is passed
+const UNREPRODUCIBLE_RETRY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// The result of the integrity check of the stored headers against an on-chain client.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageIntegrity {
+    pub minimal_slot: u64,
+    pub maximal_slot: u64,
+    /// Whether the MMR root of the stored headers is the same as the on-chain one.
+    pub matched: bool,
+    /// The first slot whose stored header is different from the one of the consensus rpc, which
+    /// is absent if they are matched, or the headers from the consensus rpc don't match the
+    /// on-chain client either.
+    pub first_diverging_slot: Option<u64>,
+    /// Whether the headers from the consensus rpc reproduce the MMR root of the on-chain client,
+    /// which is absent if they're not fetched since the stored headers are matched.
+    pub reproducible: Option<bool>,
+}
+
+/// An MMR of header digests in memory, which is shared by its clones.
+#[derive(Clone, Default)]
+struct MemoryMMRStore(Arc<RwLock<HashMap<u64, packed::HeaderDigest>>>);
+
+impl MMRStore<packed::HeaderDigest> for MemoryMMRStore {
+    fn get_elem(&self, pos: u64) -> MMRResult<Option<packed::HeaderDigest>> {
+        let elems = self
+            .0
+            .read()
+            .map_err(|err| MMRError::StoreError(err.to_string()))?;
+        Ok(elems.get(&pos).cloned())
+    }
+
+    fn append(&mut self, pos: u64, elems: Vec<packed::HeaderDigest>) -> MMRResult<()> {
+        let mut stored = self
+            .0
+            .write()
+            .map_err(|err| MMRError::StoreError(err.to_string()))?;
+        for (offset, elem) in elems.into_iter().enumerate() {
+            stored.insert(pos + offset as u64, elem);
+        }
+        Ok(())
+    }
+}
+
+impl MemoryMMRStore {
+    /// Builds the MMR of the headers in `[start_slot, end_slot]` fetched from the consensus rpc,
+    /// and returns it with its root.
+    async fn build<R: ConsensusRpc>(
+        rpc: &R,
+        config: &BackfillConfig,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> Result<(Self, packed::HeaderDigest)> {
+        let store = Self::default();
+        let mut mmr = ClientRootMMR::new(0, store.clone());
+        let mut headers = futures::stream::iter(start_slot..=end_slot)
+            .map(|slot| fetch_header(rpc, slot, config))
+            .buffered(config.concurrency.max(1));
+        while let Some(header) = headers.next().await {
+            let header: core::Header = (&header?).into();
+            mmr.push(header.calc_cache().digest())?;
+        }
+        let root = mmr.get_root()?;
+        mmr.commit()?;
+        Ok((store, root))
+    }
+}
+
+// the slots and the MMR root of an on-chain client
+type ReferenceKey = (u64, u64, Vec<u8>);
+
+#[derive(Clone)]
+struct Reference {
+    key: ReferenceKey,
+    store: MemoryMMRStore,
+    reproducible: bool,
+    built_at: Instant,
+}
+
+/// The reference MMR built for the last checked on-chain client, so the headers of the same
+/// client are fetched from the consensus rpc only once. It's shared by its clones.
+#[derive(Clone, Default)]
+pub struct ReferenceCache(Arc<Mutex<Option<Reference>>>);
+
+impl ReferenceCache {
+    fn get(&self, key: &ReferenceKey) -> Option<Reference> {
+        let reference = self.0.lock().ok()?.clone()?;
+        let fresh =
+            reference.reproducible || reference.built_at.elapsed() < UNREPRODUCIBLE_RETRY_INTERVAL;
+        (reference.key == *key && fresh).then_some(reference)
+    }
+
+    fn put(&self, reference: Reference) {
+        if let Ok(mut cached) = self.0.lock() {
+            *cached = Some(reference);
+        }
+    }
+}
+
+/// Checks the stored headers of a light client against its on-chain client. It owns all of its
+/// parts, so the headers can be fetched without borrowing the consensus client.
+pub struct IntegrityChecker<R> {
+    rpc: Arc<R>,
+    storage: Storage<MainnetEthSpec>,
+    config: BackfillConfig,
+    cache: ReferenceCache,
+}
+
+impl<R: ConsensusRpc> IntegrityChecker<R> {
+    pub(crate) fn new(
+        rpc: Arc<R>,
+        storage: Storage<MainnetEthSpec>,
+        config: BackfillConfig,
+        cache: ReferenceCache,
+    ) -> Self {
+        Self {
+            rpc,
+            storage,
+            config,
+            cache,
+        }
+    }
+
+    /// Checks the stored headers against the MMR root of the on-chain client. If they're
+    /// different, the headers of the client are fetched from the consensus rpc to find the first
+    /// diverging slot, and they're reused by the following checks of the same client.
+    pub async fn check(&self, client: &core::Client) -> Result<StorageIntegrity> {
+        let mut integrity = StorageIntegrity {
+            minimal_slot: client.minimal_slot,
+            maximal_slot: client.maximal_slot,
+            matched: self.storage.is_onchain_client_matched(client)?,
+            first_diverging_slot: None,
+            reproducible: None,
+        };
+        if integrity.matched {
+            return Ok(integrity);
+        }
+        let expected: packed::HeaderDigest = client.headers_mmr_root.pack();
+        let key = (
+            client.minimal_slot,
+            client.maximal_slot,
+            expected.as_slice().to_vec(),
+        );
+        let reference = match self.cache.get(&key) {
+            Some(reference) => reference,
+            None => {
+                let (store, root) = MemoryMMRStore::build(
+                    self.rpc.as_ref(),
+                    &self.config,
+                    client.minimal_slot,
+                    client.maximal_slot,
+                )
+                .await?;
+                let reference = Reference {
+                    key,
+                    store,
+                    reproducible: root.as_slice() == expected.as_slice(),
+                    built_at: Instant::now(),
+                };
+                self.cache.put(reference.clone());
+                reference
+            }
+        };
+        integrity.reproducible = Some(reference.reproducible);
+        if !reference.reproducible {
+            warn!(
+                "MMR root of headers from consensus rpc is different from on-chain client {client}"
+            );
+            return Ok(integrity);
+        }
+        integrity.first_diverging_slot = self
+            .storage
+            .find_first_diverging_slot(client.maximal_slot, &reference.store)?;
+        Ok(integrity)
+    }
+}
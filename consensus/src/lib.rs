@@ -2,8 +2,15 @@ pub mod errors;
 pub mod rpc;
 pub extern crate types;
 
+mod backfill;
 mod consensus;
 pub use crate::consensus::*;
 
+mod integrity;
+pub use crate::integrity::{IntegrityChecker, StorageIntegrity};
+
+mod snapshot;
+pub use crate::snapshot::export_snapshot;
+
 mod constants;
 mod utils;
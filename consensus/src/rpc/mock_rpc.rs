@@ -1,4 +1,4 @@
-use std::{fs::read_to_string, path::PathBuf};
+use std::{fs::read_to_string, path::PathBuf, sync::Mutex};
 
 use async_trait::async_trait;
 use eyre::Result;
@@ -9,6 +9,14 @@ use crate::types::{BeaconBlock, Bootstrap, FinalityUpdate, Header, OptimisticUpd
 pub struct MockRpc {
     testdata: PathBuf,
     headers: Vec<Header>,
+    bootstrap_roots: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockRpc {
+    /// The block roots of the requested bootstraps, in the order of the requests.
+    pub fn bootstrap_roots(&self) -> Vec<Vec<u8>> {
+        self.bootstrap_roots.lock().unwrap().clone()
+    }
 }
 
 #[async_trait]
@@ -20,10 +28,18 @@ impl ConsensusRpc for MockRpc {
             serde_json::from_str(&value).expect("headers jsonify")
         };
         assert!(!headers.is_empty());
-        MockRpc { testdata, headers }
+        MockRpc {
+            testdata,
+            headers,
+            bootstrap_roots: Default::default(),
+        }
     }
 
-    async fn get_bootstrap(&self, _block_root: &'_ [u8]) -> Result<Bootstrap> {
+    async fn get_bootstrap(&self, block_root: &'_ [u8]) -> Result<Bootstrap> {
+        self.bootstrap_roots
+            .lock()
+            .unwrap()
+            .push(block_root.to_vec());
         let bootstrap = read_to_string(self.testdata.join("bootstrap.json"))?;
         Ok(serde_json::from_str(&bootstrap)?)
     }
@@ -1,4 +1,5 @@
 pub mod mock_rpc;
+pub mod multi_rpc;
 pub mod nimbus_rpc;
 
 use async_trait::async_trait;
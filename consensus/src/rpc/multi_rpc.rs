@@ -0,0 +1,417 @@
+use std::cmp::Reverse;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_trait::async_trait;
+use eyre::{eyre, Result};
+use log::warn;
+use ssz_rs::prelude::*;
+use tree_hash::TreeHash;
+
+use super::ConsensusRpc;
+use crate::errors::ConsensusError;
+use crate::types::{BeaconBlock, Bootstrap, FinalityUpdate, Header, OptimisticUpdate, Update};
+
+type RpcFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// A consensus RPC over several endpoints.
+///
+/// Each request is sent to the healthiest endpoint first and fails over to the others on errors.
+/// The beacon headers and blocks, which are not verified by the sync committee, are requested
+/// from `quorum` endpoints and returned only if all of them are the same.
+pub struct MultiRpc<R> {
+    endpoints: Vec<Endpoint<R>>,
+    quorum: usize,
+}
+
+struct Endpoint<R> {
+    url: String,
+    rpc: R,
+    health: EndpointHealth,
+}
+
+/// The counters of the responses of an endpoint.
+#[derive(Default)]
+pub struct EndpointHealth {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    mismatches: AtomicU64,
+}
+
+impl EndpointHealth {
+    pub fn successes(&self) -> u64 {
+        self.successes.load(Ordering::Relaxed)
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches.load(Ordering::Relaxed)
+    }
+
+    /// The endpoints of higher scores are requested first, a mismatched response is much worse
+    /// than a failed request.
+    pub fn score(&self) -> i64 {
+        self.successes() as i64 - self.failures() as i64 - 8 * self.mismatches() as i64
+    }
+}
+
+impl fmt::Display for EndpointHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "score {} (ok {}, failed {}, mismatched {})",
+            self.score(),
+            self.successes(),
+            self.failures(),
+            self.mismatches()
+        )
+    }
+}
+
+impl<R: ConsensusRpc + Send + Sync> MultiRpc<R> {
+    pub fn from_urls(urls: &[String]) -> Result<Self> {
+        let rpcs = urls.iter().map(|url| (url.clone(), R::new(url))).collect();
+        Self::from_rpcs(rpcs)
+    }
+
+    pub fn from_rpcs(rpcs: Vec<(String, R)>) -> Result<Self> {
+        if rpcs.is_empty() {
+            return Err(ConsensusError::NoRpcEndpoint.into());
+        }
+        Ok(Self::from_nonempty_rpcs(rpcs))
+    }
+
+    fn from_nonempty_rpcs(rpcs: Vec<(String, R)>) -> Self {
+        let endpoints = rpcs
+            .into_iter()
+            .map(|(url, rpc)| Endpoint {
+                url,
+                rpc,
+                health: Default::default(),
+            })
+            .collect();
+        Self {
+            endpoints,
+            quorum: 1,
+        }
+    }
+
+    /// Sets the number of endpoints which must return the same headers and blocks, it can't be
+    /// more than the number of endpoints.
+    pub fn with_quorum(mut self, quorum: usize) -> Result<Self> {
+        if quorum == 0 || quorum > self.endpoints.len() {
+            return Err(ConsensusError::InvalidRpcQuorum(quorum, self.endpoints.len()).into());
+        }
+        self.quorum = quorum;
+        Ok(self)
+    }
+
+    pub fn quorum(&self) -> usize {
+        self.quorum
+    }
+
+    /// Returns the health of each endpoint, in the order of the endpoints.
+    pub fn health(&self) -> impl Iterator<Item = (&str, &EndpointHealth)> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| (endpoint.url.as_str(), &endpoint.health))
+    }
+
+    pub fn health_log(&self) -> String {
+        self.health()
+            .map(|(url, health)| format!("{url}: {health}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // the healthier endpoints come first, and the configured order is kept for the same scores
+    fn sorted_endpoints(&self) -> Vec<&Endpoint<R>> {
+        let mut endpoints = self.endpoints.iter().collect::<Vec<_>>();
+        endpoints.sort_by_key(|endpoint| Reverse(endpoint.health.score()));
+        endpoints
+    }
+
+    async fn call_with_failover<'a, T, F>(&'a self, method: &str, call: F) -> Result<T>
+    where
+        F: Fn(&'a R) -> RpcFuture<'a, T> + Send,
+        T: Send,
+    {
+        let mut errors = vec![];
+        for endpoint in self.sorted_endpoints() {
+            match call(&endpoint.rpc).await {
+                Ok(value) => {
+                    endpoint.health.successes.fetch_add(1, Ordering::Relaxed);
+                    return Ok(value);
+                }
+                Err(err) => {
+                    endpoint.health.failures.fetch_add(1, Ordering::Relaxed);
+                    warn!("consensus rpc {} failed on {method}: {err}", endpoint.url);
+                    errors.push(format!("{}: {err}", endpoint.url));
+                }
+            }
+        }
+        Err(eyre!(
+            "all consensus rpc endpoints failed on {method}, {}",
+            errors.join("; ")
+        ))
+    }
+
+    // requests the healthiest `quorum` endpoints together, and the others one by one in place of
+    // the failed ones
+    async fn call_with_quorum<'a, T, F>(
+        &'a self,
+        method: &str,
+        call: F,
+        digest: fn(&T) -> Result<Vec<u8>>,
+    ) -> Result<T>
+    where
+        F: Fn(&'a R) -> RpcFuture<'a, T> + Send,
+        T: Send,
+    {
+        let endpoints = self.sorted_endpoints();
+        let (first, rest) = endpoints.split_at(self.quorum);
+        let mut results =
+            futures::future::join_all(first.iter().map(|endpoint| call(&endpoint.rpc)))
+                .await
+                .into_iter()
+                .zip(first.iter().copied())
+                .collect::<Vec<_>>();
+        let mut rest = rest.iter().copied();
+        let mut responses = vec![];
+        let mut errors = vec![];
+        while let Some((result, endpoint)) = results.pop() {
+            match result {
+                Ok(value) => {
+                    endpoint.health.successes.fetch_add(1, Ordering::Relaxed);
+                    responses.push((endpoint, digest(&value)?, value));
+                }
+                Err(err) => {
+                    endpoint.health.failures.fetch_add(1, Ordering::Relaxed);
+                    warn!("consensus rpc {} failed on {method}: {err}", endpoint.url);
+                    errors.push(format!("{}: {err}", endpoint.url));
+                    if let Some(endpoint) = rest.next() {
+                        results.push((call(&endpoint.rpc).await, endpoint));
+                    }
+                }
+            }
+        }
+        if responses.len() < self.quorum {
+            return Err(eyre!(
+                "only {} of {} consensus rpc endpoints respond to {method}, {}",
+                responses.len(),
+                self.quorum,
+                errors.join("; ")
+            ));
+        }
+        if responses
+            .iter()
+            .all(|(_, digest, _)| digest == &responses[0].1)
+        {
+            return Ok(responses.pop().expect("quorum response").2);
+        }
+
+        // the endpoints out of the largest group of the same responses are mismatched, or all
+        // of them if there is no such single group
+        let count = |digest: &Vec<u8>| responses.iter().filter(|(_, d, _)| d == digest).count();
+        let max_count = responses
+            .iter()
+            .map(|(_, d, _)| count(d))
+            .max()
+            .unwrap_or(0);
+        let majorities = responses
+            .iter()
+            .filter(|(_, d, _)| count(d) == max_count)
+            .map(|(_, d, _)| d)
+            .collect::<Vec<_>>();
+        let majority = majorities
+            .iter()
+            .all(|d| d == &majorities[0])
+            .then(|| majorities[0].clone());
+        let mut mismatched = vec![];
+        for (endpoint, digest, _) in &responses {
+            if Some(digest) != majority.as_ref() {
+                endpoint.health.mismatches.fetch_add(1, Ordering::Relaxed);
+                mismatched.push(endpoint.url.clone());
+            }
+        }
+        Err(ConsensusError::RpcMismatch(method.to_owned(), mismatched.join(", ")).into())
+    }
+}
+
+#[async_trait]
+impl<R: ConsensusRpc + Send + Sync> ConsensusRpc for MultiRpc<R> {
+    /// The endpoints are separated by commas, a path without any endpoint is kept as the only
+    /// one, so that its requests fail instead of the construction.
+    fn new(path: &str) -> Self {
+        let mut urls = path
+            .split(',')
+            .map(|url| url.trim().to_owned())
+            .filter(|url| !url.is_empty())
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            urls.push(path.to_owned());
+        }
+        let rpcs = urls.iter().map(|url| (url.clone(), R::new(url))).collect();
+        Self::from_nonempty_rpcs(rpcs)
+    }
+
+    async fn get_bootstrap(&self, block_root: &'_ [u8]) -> Result<Bootstrap> {
+        self.call_with_failover("bootstrap", |rpc| rpc.get_bootstrap(block_root))
+            .await
+    }
+
+    async fn get_updates(&self, period: u64, count: u8) -> Result<Vec<Update>> {
+        self.call_with_failover("updates", |rpc| rpc.get_updates(period, count))
+            .await
+    }
+
+    async fn get_finality_update(&self) -> Result<FinalityUpdate> {
+        self.call_with_failover("finality_update", |rpc| rpc.get_finality_update())
+            .await
+    }
+
+    async fn get_optimistic_update(&self) -> Result<OptimisticUpdate> {
+        self.call_with_failover("optimistic_update", |rpc| rpc.get_optimistic_update())
+            .await
+    }
+
+    async fn get_block(&self, slot: u64) -> Result<Option<BeaconBlock>> {
+        self.call_with_quorum("block", |rpc| rpc.get_block(slot), block_digest)
+            .await
+    }
+
+    async fn get_block_ssz(&self, slot: u64) -> Result<Option<BeaconBlock>> {
+        self.call_with_quorum("block_ssz", |rpc| rpc.get_block_ssz(slot), block_digest)
+            .await
+    }
+
+    async fn get_header(&self, slot: u64) -> Result<Option<Header>> {
+        self.call_with_quorum("header", |rpc| rpc.get_header(slot), header_digest)
+            .await
+    }
+}
+
+fn header_digest(header: &Option<Header>) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    if let Some(header) = header {
+        header.serialize(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+fn block_digest(block: &Option<BeaconBlock>) -> Result<Vec<u8>> {
+    Ok(block
+        .as_ref()
+        .map(|block| block.tree_hash_root().as_bytes().to_vec())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::MultiRpc;
+    use crate::errors::ConsensusError;
+    use crate::rpc::{mock_rpc::MockRpc, ConsensusRpc};
+    use crate::types::Header;
+
+    // a mock endpoint which only serves headers, and the state roots of them are tampered
+    fn lying_rpc(dir: &TempDir) -> MockRpc {
+        let headers = fs::read_to_string("testdata/headers.json").unwrap();
+        let mut headers: Vec<Header> = serde_json::from_str(&headers).unwrap();
+        for header in &mut headers {
+            header.state_root = Default::default();
+        }
+        let headers = serde_json::to_string(&headers).unwrap();
+        fs::write(dir.path().join("headers.json"), headers).unwrap();
+        MockRpc::new(dir.path().to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_multi_rpc_failover() {
+        let dir = TempDir::new().unwrap();
+        let rpc = MultiRpc::from_rpcs(vec![
+            ("lying".to_owned(), lying_rpc(&dir)),
+            ("honest".to_owned(), MockRpc::new("testdata/")),
+        ])
+        .unwrap();
+
+        rpc.get_finality_update().await.unwrap();
+        let health = rpc.health().collect::<Vec<_>>();
+        assert_eq!(health[0].1.failures(), 1);
+        assert_eq!(health[1].1.successes(), 1);
+
+        // the healthier endpoint is requested first
+        rpc.get_optimistic_update().await.unwrap();
+        let health = rpc.health().collect::<Vec<_>>();
+        assert_eq!(health[0].1.failures(), 1);
+        assert_eq!(health[1].1.successes(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multi_rpc_quorum() {
+        let dir = TempDir::new().unwrap();
+        let honest: MockRpc = MockRpc::new("testdata/");
+        let slot = {
+            let headers = fs::read_to_string("testdata/headers.json").unwrap();
+            let headers: Vec<Header> = serde_json::from_str(&headers).unwrap();
+            headers[0].slot
+        };
+        let expected = honest.get_header(slot).await.unwrap().unwrap();
+
+        let rpc = MultiRpc::from_rpcs(vec![
+            ("honest-1".to_owned(), honest),
+            ("lying".to_owned(), lying_rpc(&dir)),
+            ("honest-2".to_owned(), MockRpc::new("testdata/")),
+        ])
+        .unwrap();
+        let header = rpc.get_header(slot).await.unwrap().unwrap();
+        assert_eq!(header.state_root, expected.state_root);
+
+        let rpc = rpc.with_quorum(3).unwrap();
+        let err = rpc.get_header(slot).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            ConsensusError::RpcMismatch("header".to_owned(), "lying".to_owned()).to_string()
+        );
+        let mismatches = rpc
+            .health()
+            .map(|(_, health)| health.mismatches())
+            .collect::<Vec<_>>();
+        assert_eq!(mismatches, vec![0, 1, 0]);
+
+        // the mismatched endpoint is out of the quorum of two healthier ones
+        let rpc = rpc.with_quorum(2).unwrap();
+        let header = rpc.get_header(slot).await.unwrap().unwrap();
+        assert_eq!(header.state_root, expected.state_root);
+    }
+
+    #[test]
+    fn test_multi_rpc_invalid_config() {
+        let err = MultiRpc::<MockRpc>::from_rpcs(vec![]).err().unwrap();
+        assert_eq!(err.to_string(), ConsensusError::NoRpcEndpoint.to_string());
+
+        let rpcs = || {
+            MultiRpc::from_rpcs(vec![
+                ("first".to_owned(), MockRpc::new("testdata/")),
+                ("second".to_owned(), MockRpc::new("testdata/")),
+            ])
+            .unwrap()
+        };
+        assert_eq!(rpcs().with_quorum(2).unwrap().quorum(), 2);
+        for quorum in [0, 3] {
+            let err = rpcs().with_quorum(quorum).err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                ConsensusError::InvalidRpcQuorum(quorum, 2).to_string()
+            );
+        }
+    }
+}
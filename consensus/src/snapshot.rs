@@ -0,0 +1,27 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write as _};
+use std::path::Path;
+
+use eth2_types::MainnetEthSpec;
+use eyre::Result;
+use storage::{snapshot::Snapshot, Storage};
+
+/// Exports the headers stored at `storage_path` into the snapshot file `output`, returns the
+/// slots of the exported headers.
+pub fn export_snapshot(
+    storage_path: &Path,
+    output: &Path,
+    with_updates: bool,
+) -> Result<(u64, u64)> {
+    let storage = Storage::<MainnetEthSpec>::new(storage_path)?;
+    let mut writer = BufWriter::new(File::create(output)?);
+    let slots = storage.export_snapshot(&mut writer, with_updates)?;
+    writer.flush()?;
+    Ok(slots)
+}
+
+pub(crate) fn read_snapshot(path: &Path) -> Result<Snapshot> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let snapshot = Snapshot::read_from(&mut reader)?;
+    Ok(snapshot)
+}
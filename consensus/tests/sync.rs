@@ -1,10 +1,9 @@
-use std::{path::PathBuf, sync::Arc};
-use tempfile::TempDir;
+use std::sync::Arc;
 
-use config::{networks, Config};
+use config::{networks, Config, StorageBackend};
 use consensus::{rpc::mock_rpc::MockRpc, ConsensusClient};
 
-async fn setup(path: PathBuf) -> ConsensusClient<MockRpc> {
+async fn setup() -> ConsensusClient<MockRpc> {
     let base_config = networks::goerli();
     let config = Config {
         consensus_rpc: String::new(),
@@ -12,7 +11,7 @@ async fn setup(path: PathBuf) -> ConsensusClient<MockRpc> {
         chain: base_config.chain,
         forks: base_config.forks,
         max_checkpoint_age: 123123123,
-        storage_path: path,
+        storage_backend: StorageBackend::Memory,
         ..Default::default()
     };
 
@@ -25,8 +24,7 @@ async fn setup(path: PathBuf) -> ConsensusClient<MockRpc> {
 
 #[tokio::test]
 async fn test_sync() {
-    let storage = TempDir::new().unwrap();
-    let mut client = setup(storage.into_path()).await;
+    let mut client = setup().await;
     client.sync(3781056).await.expect("sync");
 
     let head = client.get_header();
@@ -39,8 +37,7 @@ async fn test_sync() {
 #[tokio::test]
 #[should_panic = "payload: invalid header hash found: 0x1f80…1b7f, expected: 0x75b0d40fd8fb98e5535ee63c242bf2fbeb36a00ca59729cb5ae9f4b7d89522dc"]
 async fn test_get_payload() {
-    let storage = TempDir::new().unwrap();
-    let mut client = setup(storage.into_path()).await;
+    let mut client = setup().await;
     client.sync(3781056).await.expect("sync");
 
     let payload = client
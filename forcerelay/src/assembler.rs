@@ -1,25 +1,44 @@
+use std::collections::{BTreeSet, HashMap};
+
 use ckb_sdk::constants::TYPE_ID_CODE_HASH;
-use ckb_types::core::{ScriptHashType, TransactionView};
-use ckb_types::packed::{CellDep, Script};
+use ckb_types::core::{Cycle, ScriptHashType, TransactionView};
+use ckb_types::packed::{BytesVec, BytesVecReader, CellDep, Script};
 use ckb_types::prelude::{Builder, Entity, Pack, Reader};
 use consensus::rpc::ConsensusRpc;
 use consensus::ConsensusClient;
 use eth2_types::MainnetEthSpec;
-use eth_light_client_in_ckb_prover::CachedBeaconBlock;
+use eth_light_client_in_ckb_prover::{CachedBeaconBlock, Receipts};
 use eth_light_client_in_ckb_verification::mmr;
-use eth_light_client_in_ckb_verification::types::{core, packed, prelude::Unpack as LcUnpack};
-use ethers::types::{Transaction, TransactionReceipt};
+use eth_light_client_in_ckb_verification::types::{
+    core, packed,
+    prelude::{Pack as LcPack, Unpack as LcUnpack},
+};
+use ethers::types::{Transaction, TransactionReceipt, H256};
 use eyre::Result;
-use storage::prelude::StorageAsMMRStore as _;
+use log::warn;
+use storage::prelude::{StorageAsMMRStore as _, StorageReader as _, StorageWriter as _};
+use storage::Storage;
 
+use crate::dry_run::DryRunner;
 use crate::rpc::CkbRpc;
 use crate::util::*;
+use crate::verifier::verify_packed_proof;
+
+/// The beacon block, the Ethereum transaction and all receipts of its execution block.
+pub type BatchItem<'a> = (
+    &'a CachedBeaconBlock<MainnetEthSpec>,
+    &'a Transaction,
+    &'a [TransactionReceipt],
+);
+
+type PackedProof = (packed::TransactionProof, packed::TransactionPayload);
 
 pub struct ForcerelayAssembler<R: CkbRpc> {
     rpc: R,
     binary_celldep: CellDep,
     pub binary_typeid_script: Script,
     pub lightclient_typescript: Script,
+    dry_runner: Option<DryRunner>,
 }
 
 impl<R: CkbRpc> ForcerelayAssembler<R> {
@@ -50,21 +69,62 @@ impl<R: CkbRpc> ForcerelayAssembler<R> {
             binary_celldep: CellDep::default(),
             binary_typeid_script,
             lightclient_typescript,
+            dry_runner: None,
         }
     }
 
+    pub(crate) fn set_dry_runner(&mut self, dry_runner: DryRunner) {
+        self.dry_runner = Some(dry_runner);
+    }
+
+    /// Runs the verification transaction in the CKB script VM against the live cell deps, and
+    /// returns the consumed cycles, or `None` if the dry run mode is not enabled.
+    pub async fn dry_run_tx(&self, tx: &TransactionView) -> Result<Option<Cycle>> {
+        let dry_runner = match &self.dry_runner {
+            Some(dry_runner) => dry_runner,
+            None => return Ok(None),
+        };
+        let mut checker_args = self
+            .lightclient_typescript
+            .calc_script_hash()
+            .as_slice()
+            .to_vec();
+        checker_args.extend_from_slice(self.binary_typeid_script.calc_script_hash().as_slice());
+        let cycles = dry_runner
+            .dry_run(&self.rpc, tx, checker_args.into())
+            .await?;
+        Ok(Some(cycles))
+    }
+
+    pub(crate) fn rpc(&self) -> &R {
+        &self.rpc
+    }
+
+    /// Fetches the lightclient cell, the one with the greatest maximal slot is chosen if there
+    /// are several lightclient cells.
     pub async fn fetch_onchain_packed_client(&self) -> Result<Option<(core::Client, CellDep)>> {
-        match search_cell(&self.rpc, &self.lightclient_typescript).await? {
-            Some(cell) => {
-                if packed::ClientReader::verify(&cell.output_data, false).is_err() {
-                    return Err(eyre::eyre!("unsupported lightlient data"));
-                }
-                let packed_client = packed::Client::new_unchecked(cell.output_data);
-                let celldep = CellDep::new_builder().out_point(cell.out_point).build();
-                Ok(Some((packed_client.unpack(), celldep)))
+        let cells = search_cells(&self.rpc, &self.lightclient_typescript).await?;
+        if cells.is_empty() {
+            return Ok(None);
+        }
+        let mut clients = vec![];
+        for cell in cells {
+            if packed::ClientReader::verify(&cell.output_data, false).is_err() {
+                warn!(
+                    "skip lightclient cell {} of unsupported data",
+                    cell.out_point
+                );
+                continue;
             }
-            None => Ok(None),
+            let packed_client = packed::Client::new_unchecked(cell.output_data);
+            let celldep = CellDep::new_builder().out_point(cell.out_point).build();
+            clients.push((packed_client.unpack(), celldep));
         }
+        clients
+            .into_iter()
+            .max_by_key(|(client, _): &(core::Client, CellDep)| client.maximal_slot)
+            .map(Some)
+            .ok_or_else(|| eyre::eyre!("unsupported lightlient data"))
     }
 
     pub async fn update_binary_celldep(&mut self) -> Result<()> {
@@ -80,7 +140,7 @@ impl<R: CkbRpc> ForcerelayAssembler<R> {
 
     #[allow(clippy::too_many_arguments)]
     pub async fn assemble_tx(
-        &mut self,
+        &self,
         client: core::Client,
         client_celldep: &CellDep,
         consensus: &ConsensusClient<impl ConsensusRpc>,
@@ -88,39 +148,200 @@ impl<R: CkbRpc> ForcerelayAssembler<R> {
         tx: &Transaction,
         receipts: &[TransactionReceipt],
     ) -> Result<TransactionView> {
-        let receipts = receipts.to_owned().into();
-
-        let header_mmr_proof = {
-            let mmr = consensus.storage().chain_root_mmr(client.maximal_slot)?;
-            let mmr_position = block.slot() - client.minimal_slot;
-            let mmr_index = mmr::lib::leaf_index_to_pos(mmr_position.into());
-            mmr.gen_proof(vec![mmr_index])?
-                .proof_items()
-                .iter()
-                .map(LcUnpack::unpack)
-                .collect::<Vec<_>>()
-        };
+        self.assemble_batch_tx(client, client_celldep, consensus, &[(block, tx, receipts)])
+            .await
+    }
 
-        let transaction_index = match find_receipt_index(tx.hash, &receipts) {
-            Some(index) => index,
-            None => return Err(eyre::eyre!("cannot find receipt from receipts")),
-        };
-        let packed_proof = generate_packed_transaction_proof(
-            block,
-            &receipts,
-            transaction_index,
-            &header_mmr_proof,
-        )?;
-        client
-            .verify_packed_transaction_proof(packed_proof.as_reader())
-            .map_err(|e| eyre::eyre!("verify transaction proof error {}", e as i8))?;
-        let packed_payload = generate_packed_payload(block, tx, &receipts, transaction_index)?;
-        packed_proof
-            .unpack()
-            .verify_packed_payload(packed_payload.as_reader())
-            .map_err(|e| eyre::eyre!("verify payload proof error {}", e as i8))?;
+    /// Assembles one partial verification transaction for several Ethereum transactions,
+    /// which may come from different blocks.
+    ///
+    /// Each Ethereum transaction is carried by its own witness, in the same order as `items`,
+    /// and the header MMR proof is generated only once for each distinct beacon block.
+    pub async fn assemble_batch_tx(
+        &self,
+        client: core::Client,
+        client_celldep: &CellDep,
+        consensus: &ConsensusClient<impl ConsensusRpc>,
+        items: &[BatchItem<'_>],
+    ) -> Result<TransactionView> {
+        if items.is_empty() {
+            return Err(eyre::eyre!("no ethereum transaction to assemble"));
+        }
+        let storage = consensus.storage();
+        let mut cached_proofs = Vec::with_capacity(items.len());
+        for (_, tx, _) in items {
+            cached_proofs.push(load_cached_proof(storage, &client, &tx.hash)?);
+        }
+        let slots = items
+            .iter()
+            .zip(&cached_proofs)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|((block, _, _), _)| block.slot().as_u64())
+            .collect::<BTreeSet<_>>();
+        let header_mmr_proofs = generate_header_mmr_proofs(storage, &client, &slots)?;
+        let mut proofs = Vec::with_capacity(items.len());
 
+        for ((block, tx, receipts), cached) in items.iter().zip(cached_proofs) {
+            if let Some(proof) = cached {
+                proofs.push(proof);
+                continue;
+            }
+            let receipts: Receipts = receipts.to_vec().into();
+            let header_mmr_proof = &header_mmr_proofs[&block.slot().as_u64()];
+
+            let transaction_index = match find_receipt_index(tx.hash, &receipts) {
+                Some(index) => index,
+                None => {
+                    return Err(eyre::eyre!(
+                        "cannot find receipt of {:#x} from receipts",
+                        tx.hash
+                    ))
+                }
+            };
+            let packed_proof = generate_packed_transaction_proof(
+                block,
+                &receipts,
+                transaction_index,
+                header_mmr_proof,
+            )?;
+            let packed_payload = generate_packed_payload(block, tx, &receipts, transaction_index)?;
+            verify_packed_proof(
+                &client,
+                packed_proof.as_reader(),
+                packed_payload.as_reader(),
+            )?;
+            let proof = (packed_proof, packed_payload);
+            save_cached_proof(storage, &client, &tx.hash, &proof)?;
+            proofs.push(proof);
+        }
+
+        let celldeps = vec![self.binary_celldep.clone(), client_celldep.clone()];
+        assemble_batch_verification_transaction(&proofs, &celldeps)
+    }
+
+    /// Assembles the verification transaction only from the proofs cached for the range and the
+    /// MMR root of `client`, returns `None` if any of the proofs is not cached.
+    pub fn assemble_batch_tx_from_cache(
+        &self,
+        client: &core::Client,
+        client_celldep: &CellDep,
+        consensus: &ConsensusClient<impl ConsensusRpc>,
+        tx_hashes: &[H256],
+    ) -> Result<Option<TransactionView>> {
+        let mut proofs = Vec::with_capacity(tx_hashes.len());
+        for tx_hash in tx_hashes {
+            match load_cached_proof(consensus.storage(), client, tx_hash)? {
+                Some(proof) => proofs.push(proof),
+                None => return Ok(None),
+            }
+        }
         let celldeps = vec![self.binary_celldep.clone(), client_celldep.clone()];
-        assemble_partial_verification_transaction(&packed_proof, &packed_payload, &celldeps)
+        assemble_batch_verification_transaction(&proofs, &celldeps).map(Some)
+    }
+}
+
+/// Generates the header MMR proofs of `slots` from the chain root MMR of `client`.
+///
+/// The on-chain verifier checks each witness on its own and only accepts a single-leaf proof of
+/// the header carried by that witness, so every slot gets a proof of its own and no multi-leaf
+/// proof is generated. Each proof is checked against the on-chain MMR root before it's returned.
+pub fn generate_header_mmr_proofs(
+    storage: &Storage<MainnetEthSpec>,
+    client: &core::Client,
+    slots: &BTreeSet<u64>,
+) -> Result<HashMap<u64, Vec<core::HeaderDigest>>> {
+    if slots.is_empty() {
+        return Ok(HashMap::new());
     }
+    let mmr = storage.chain_root_mmr(client.maximal_slot)?;
+    let root: packed::HeaderDigest = LcPack::pack(&client.headers_mmr_root);
+    let mut proofs = HashMap::with_capacity(slots.len());
+    for slot in slots {
+        if *slot < client.minimal_slot || *slot > client.maximal_slot {
+            return Err(eyre::eyre!(
+                "beacon slot {slot} is out of the on-chain client {client}"
+            ));
+        }
+        let position = mmr::lib::leaf_index_to_pos(slot - client.minimal_slot);
+        let digest = storage
+            .get_beacon_header_digest(position)?
+            .ok_or_else(|| eyre::eyre!("no stored header of beacon slot {slot}"))?;
+        let proof = mmr.gen_proof(vec![position])?;
+        if !proof.verify(root.clone(), vec![(position, digest)])? {
+            return Err(eyre::eyre!(
+                "stored header of beacon slot {slot} mismatches the on-chain client {client}"
+            ));
+        }
+        let items = proof.proof_items().iter().map(LcUnpack::unpack).collect();
+        proofs.insert(*slot, items);
+    }
+    Ok(proofs)
+}
+
+/// Deletes the cached proofs which are generated for other ranges of the on-chain client.
+pub fn prune_cached_proofs(
+    consensus: &ConsensusClient<impl ConsensusRpc>,
+    client: &core::Client,
+) -> Result<usize> {
+    let pruned = consensus
+        .storage()
+        .delete_stale_transaction_proofs(client.minimal_slot, client.maximal_slot)?;
+    Ok(pruned)
+}
+
+// The cached proof is a vector of the MMR root of the client, the proof and the payload, and it
+// is stale if the client is re-initialized with the same range but a different MMR root.
+fn load_cached_proof(
+    storage: &Storage<MainnetEthSpec>,
+    client: &core::Client,
+    tx_hash: &H256,
+) -> Result<Option<PackedProof>> {
+    let raw = match storage.get_transaction_proof(
+        client.minimal_slot,
+        client.maximal_slot,
+        tx_hash.as_bytes(),
+    )? {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+    let items = match BytesVecReader::from_slice(&raw) {
+        Ok(items) if items.len() == 3 => items,
+        _ => return Ok(None),
+    };
+    let root: packed::HeaderDigest = LcPack::pack(&client.headers_mmr_root);
+    if items.get_unchecked(0).raw_data() != root.as_slice() {
+        return Ok(None);
+    }
+    let proof = items.get_unchecked(1).raw_data();
+    let payload = items.get_unchecked(2).raw_data();
+    if packed::TransactionProofReader::verify(proof, false).is_err()
+        || packed::TransactionPayloadReader::verify(payload, false).is_err()
+    {
+        return Ok(None);
+    }
+    Ok(Some((
+        packed::TransactionProof::new_unchecked(proof.to_vec().into()),
+        packed::TransactionPayload::new_unchecked(payload.to_vec().into()),
+    )))
+}
+
+fn save_cached_proof(
+    storage: &Storage<MainnetEthSpec>,
+    client: &core::Client,
+    tx_hash: &H256,
+    (proof, payload): &PackedProof,
+) -> Result<()> {
+    let root: packed::HeaderDigest = LcPack::pack(&client.headers_mmr_root);
+    let value = BytesVec::new_builder()
+        .push(Pack::pack(root.as_slice()))
+        .push(Pack::pack(proof.as_slice()))
+        .push(Pack::pack(payload.as_slice()))
+        .build();
+    storage.put_transaction_proof(
+        client.minimal_slot,
+        client.maximal_slot,
+        tx_hash.as_bytes(),
+        value.as_slice(),
+    )?;
+    Ok(())
 }
@@ -0,0 +1,473 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+use ckb_sdk::rpc::ckb_indexer::SearchKey;
+use ckb_sdk::traits::{
+    CellQueryOptions, DefaultCellDepResolver, LiveCell, PrimaryScriptType, SecpCkbRawKeySigner,
+    ValueRangeOption,
+};
+use ckb_sdk::unlock::{ScriptSigner, SecpSighashScriptSigner};
+use ckb_sdk::{Address, AddressPayload, ScriptGroup, ScriptGroupType};
+use ckb_types::core::{BlockView, Capacity, TransactionView};
+use ckb_types::packed::{
+    self, BytesOpt, CellDep, CellInput, CellOutput, OutPoint, Script, WitnessArgs,
+};
+use ckb_types::prelude::*;
+use eyre::{eyre, Result};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+use crate::rpc::CkbRpc;
+
+const SIGNATURE_SIZE: usize = 65;
+const SEARCH_CELLS_LIMIT: u32 = 64;
+
+/// Turns a partial verification transaction into a balanced and signed one, which can be
+/// broadcasted to CKB directly.
+///
+/// The capacity is collected from the live cells of the configured secp256k1/blake160 sighash
+/// lock script, and the change goes back to the same lock script.
+///
+/// The signature is put in a dedicated witness of the first collected input, the witnesses which
+/// are not bound to an input of the partial transaction, such as the verification witnesses, are
+/// moved behind the collected inputs as they are.
+pub struct TransactionCompleter {
+    lock_script: Script,
+    secret_key: SecretKey,
+    fee_rate: u64,
+    sighash_celldep: Option<CellDep>,
+}
+
+impl TransactionCompleter {
+    pub fn new(lock_script: Script, secret_key: SecretKey, fee_rate: u64) -> Result<Self> {
+        let public_key = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key);
+        let sighash_script = Script::from(&AddressPayload::from_pubkey(&public_key));
+        if sighash_script != lock_script {
+            return Err(eyre!(
+                "lock script {lock_script} is not the sighash lock of the secret key"
+            ));
+        }
+        Ok(Self {
+            lock_script,
+            secret_key,
+            fee_rate,
+            sighash_celldep: None,
+        })
+    }
+
+    /// Creates a completer from a CKB address and a key file whose first line is the hex encoded
+    /// secp256k1 secret key, which is the same format as `ckb-cli` exports.
+    pub fn from_key_file<P: AsRef<Path>>(
+        address: &str,
+        key_path: P,
+        fee_rate: u64,
+    ) -> Result<Self> {
+        let address = Address::from_str(address).map_err(|e| eyre!("invalid address: {e}"))?;
+        let content = std::fs::read_to_string(key_path.as_ref())?;
+        let key = content
+            .lines()
+            .next()
+            .ok_or_else(|| eyre!("empty key file {}", key_path.as_ref().display()))?
+            .trim();
+        let secret_key = SecretKey::from_str(key.strip_prefix("0x").unwrap_or(key))
+            .map_err(|e| eyre!("invalid secret key: {e}"))?;
+        Self::new(Script::from(&address), secret_key, fee_rate)
+    }
+
+    pub fn lock_script(&self) -> &Script {
+        &self.lock_script
+    }
+
+    /// Searches the secp256k1 dep group from the genesis block, only once.
+    pub async fn update_sighash_celldep<R: CkbRpc>(&mut self, rpc: &R) -> Result<()> {
+        if self.sighash_celldep.is_some() {
+            return Ok(());
+        }
+        let genesis: BlockView = rpc.get_block_by_number(0.into()).await?.into();
+        let resolver = DefaultCellDepResolver::from_genesis(&genesis)
+            .map_err(|e| eyre!("cannot resolve genesis celldeps: {e}"))?;
+        let (celldep, _) = resolver
+            .sighash_dep()
+            .ok_or_else(|| eyre!("sighash celldep not found in genesis block"))?;
+        self.sighash_celldep = Some(celldep.clone());
+        Ok(())
+    }
+
+    /// Balances the transaction with the live cells of the lock script and signs it.
+    ///
+    /// The inputs and outputs which are already in the transaction are kept as they are.
+    pub async fn complete<R: CkbRpc>(
+        &self,
+        rpc: &R,
+        tx: TransactionView,
+    ) -> Result<TransactionView> {
+        let sighash_celldep = self
+            .sighash_celldep
+            .clone()
+            .ok_or_else(|| eyre!("sighash celldep is not prepared"))?;
+        let mut inputs_capacity = 0u64;
+        for input in tx.inputs() {
+            inputs_capacity += resolve_input_capacity(rpc, &input.previous_output()).await?;
+        }
+        let outputs_capacity = tx
+            .outputs_capacity()
+            .map_err(|e| eyre!("outputs capacity overflow: {e}"))?
+            .as_u64();
+        let change_output = CellOutput::new_builder()
+            .lock(self.lock_script.clone())
+            .build_exact_capacity(Capacity::zero())
+            .map_err(|e| eyre!("change output: {e}"))?;
+        let min_change_capacity: u64 = change_output.capacity().unpack();
+
+        let mut used: HashSet<OutPoint> = tx
+            .inputs()
+            .into_iter()
+            .map(|i| i.previous_output())
+            .collect();
+        let witnesses = tx.witnesses().into_iter().collect::<Vec<_>>();
+        let mut input_indices = vec![];
+        let mut builder = tx.as_advanced_builder();
+        if !tx
+            .cell_deps()
+            .into_iter()
+            .any(|celldep| celldep == sighash_celldep)
+        {
+            builder = builder.cell_dep(sighash_celldep);
+        }
+        builder = builder
+            .output(change_output.clone())
+            .output_data(Default::default());
+
+        let mut cursor = None;
+        let mut fee = 0;
+        'collect: loop {
+            let page = rpc
+                .fetch_live_cells(self.search_key(), SEARCH_CELLS_LIMIT, cursor)
+                .await?;
+            if page.objects.is_empty() {
+                break;
+            }
+            for cell in page.objects {
+                let cell: LiveCell = cell.into();
+                if used.contains(&cell.out_point)
+                    || cell.output.type_().to_opt().is_some()
+                    || !cell.output_data.is_empty()
+                {
+                    continue;
+                }
+                used.insert(cell.out_point.clone());
+                input_indices.push(tx.inputs().len() + input_indices.len());
+                inputs_capacity += Unpack::<u64>::unpack(&cell.output.capacity());
+                builder = builder.input(CellInput::new(cell.out_point, 0));
+
+                let placeholder = self.with_signature_placeholder(
+                    builder.clone().build(),
+                    &witnesses,
+                    input_indices.len(),
+                );
+                fee = self.calculate_fee(&placeholder);
+                if inputs_capacity >= outputs_capacity + min_change_capacity + fee {
+                    break 'collect;
+                }
+            }
+            cursor = Some(page.last_cursor);
+        }
+        if input_indices.is_empty()
+            || inputs_capacity < outputs_capacity + min_change_capacity + fee
+        {
+            return Err(eyre!(
+                "insufficient capacity of lock script {}: {inputs_capacity} < {}",
+                self.lock_script,
+                outputs_capacity + min_change_capacity + fee
+            ));
+        }
+
+        let change_capacity = inputs_capacity - outputs_capacity - fee;
+        let tx = builder.build();
+        let change_index = tx.outputs().len() - 1;
+        let mut outputs = tx.outputs().into_iter().collect::<Vec<_>>();
+        outputs[change_index] = change_output
+            .as_builder()
+            .capacity(change_capacity.pack())
+            .build();
+        let tx = tx.as_advanced_builder().set_outputs(outputs).build();
+        let tx = self.with_signature_placeholder(tx, &witnesses, input_indices.len());
+
+        let signer = SecpSighashScriptSigner::new(Box::new(
+            SecpCkbRawKeySigner::new_with_secret_keys(vec![self.secret_key]),
+        ));
+        let script_group = ScriptGroup {
+            script: self.lock_script.clone(),
+            group_type: ScriptGroupType::Lock,
+            input_indices,
+            output_indices: vec![],
+        };
+        signer
+            .sign_tx(&tx, &script_group)
+            .map_err(|e| eyre!("sign transaction error: {e}"))
+    }
+
+    fn search_key(&self) -> SearchKey {
+        let mut query = CellQueryOptions::new(self.lock_script.clone(), PrimaryScriptType::Lock);
+        query.secondary_script_len_range = Some(ValueRangeOption::new_exact(0));
+        query.data_len_range = Some(ValueRangeOption::new_exact(0));
+        query.into()
+    }
+
+    // lays out the witnesses as: the ones of the partial inputs, an empty signature of the first
+    // collected input, empty ones of the other collected inputs, and the unbound ones
+    fn with_signature_placeholder(
+        &self,
+        tx: TransactionView,
+        witnesses: &[packed::Bytes],
+        collected_inputs: usize,
+    ) -> TransactionView {
+        let partial_inputs = tx.inputs().len() - collected_inputs;
+        let (bound, unbound) = witnesses.split_at(partial_inputs.min(witnesses.len()));
+        let mut laid_out = bound.to_vec();
+        laid_out.resize(partial_inputs, Default::default());
+        let lock = BytesOpt::new_builder()
+            .set(Some(vec![0u8; SIGNATURE_SIZE].pack()))
+            .build();
+        let signature = WitnessArgs::new_builder().lock(lock).build();
+        laid_out.push(signature.as_bytes().pack());
+        laid_out.resize(partial_inputs + collected_inputs, Default::default());
+        laid_out.extend_from_slice(unbound);
+        tx.as_advanced_builder().set_witnesses(laid_out).build()
+    }
+
+    fn calculate_fee(&self, tx: &TransactionView) -> u64 {
+        let size = tx.data().serialized_size_in_block() as u64;
+        (size * self.fee_rate + 999) / 1000
+    }
+}
+
+async fn resolve_input_capacity<R: CkbRpc>(rpc: &R, out_point: &OutPoint) -> Result<u64> {
+    let cell = rpc.get_live_cell(&out_point.clone().into(), false).await?;
+    match cell.cell {
+        Some(info) => Ok(info.output.capacity.value()),
+        None => Err(eyre!("input {out_point} is not a live cell")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ckb_jsonrpc_types::{
+        BlockNumber, BlockView, CellWithStatus, HeaderView, JsonBytes, OutPoint as JsonOutPoint,
+        OutputsValidator, Transaction, TransactionWithStatusResponse,
+    };
+    use ckb_sdk::rpc::ckb_indexer::{Cell, Pagination, SearchKey};
+    use ckb_sdk::AddressPayload;
+    use ckb_types::core::{Capacity, TransactionBuilder, TransactionView};
+    use ckb_types::packed::{Bytes, BytesOpt, CellDep, CellOutput, OutPoint, Script, WitnessArgs};
+    use ckb_types::{prelude::*, H256};
+    use config::default_fee_rate;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+    use std::str::FromStr;
+
+    use super::{TransactionCompleter, SIGNATURE_SIZE};
+    use crate::rpc::{CkbRpc, Rpc};
+
+    const SECRET_KEY: &str = "63d86723e08f0f813a36ce6aa123bb2289d90680ae1e99d4de8cdb334553f24d";
+
+    // a ckb rpc whose indexer holds the capacity cells of the signer only
+    struct StubRpc {
+        cells: Vec<Cell>,
+    }
+
+    impl CkbRpc for StubRpc {
+        fn get_block_by_number(&self, _number: BlockNumber) -> Rpc<BlockView> {
+            unimplemented!()
+        }
+
+        fn get_block(&self, _hash: &H256) -> Rpc<BlockView> {
+            unimplemented!()
+        }
+
+        fn get_tip_header(&self) -> Rpc<HeaderView> {
+            unimplemented!()
+        }
+
+        fn get_transaction(&self, _hash: &H256) -> Rpc<Option<TransactionWithStatusResponse>> {
+            unimplemented!()
+        }
+
+        fn get_live_cell(
+            &self,
+            _out_point: &JsonOutPoint,
+            _with_data: bool,
+        ) -> Rpc<CellWithStatus> {
+            unimplemented!()
+        }
+
+        fn get_txs_by_hashes(
+            &self,
+            _hashes: Vec<H256>,
+        ) -> Rpc<Vec<Option<TransactionWithStatusResponse>>> {
+            unimplemented!()
+        }
+
+        fn fetch_live_cells(
+            &self,
+            _search_key: SearchKey,
+            _limit: u32,
+            cursor: Option<JsonBytes>,
+        ) -> Rpc<Pagination<Cell>> {
+            let objects = if cursor.is_none() {
+                self.cells.clone()
+            } else {
+                vec![]
+            };
+            let page = Pagination {
+                objects,
+                last_cursor: JsonBytes::from_vec(vec![1]),
+            };
+            Box::pin(async { Ok(page) })
+        }
+
+        fn send_transaction(
+            &self,
+            _tx: &Transaction,
+            _outputs_validator: Option<OutputsValidator>,
+        ) -> Rpc<H256> {
+            unimplemented!()
+        }
+    }
+
+    fn signer() -> (Script, SecretKey) {
+        let secret_key = SecretKey::from_str(SECRET_KEY).unwrap();
+        let public_key = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key);
+        (
+            Script::from(&AddressPayload::from_pubkey(&public_key)),
+            secret_key,
+        )
+    }
+
+    fn completer(fee_rate: u64) -> TransactionCompleter {
+        let (lock_script, secret_key) = signer();
+        let mut completer = TransactionCompleter::new(lock_script, secret_key, fee_rate).unwrap();
+        let sighash_celldep = CellDep::new_builder()
+            .out_point(OutPoint::new([9u8; 32].pack(), 0))
+            .build();
+        completer.sighash_celldep = Some(sighash_celldep);
+        completer
+    }
+
+    fn capacity_cells(capacities: &[u64]) -> StubRpc {
+        let (lock_script, _) = signer();
+        let cells = capacities
+            .iter()
+            .enumerate()
+            .map(|(index, ckb)| Cell {
+                output: CellOutput::new_builder()
+                    .lock(lock_script.clone())
+                    .capacity(Capacity::shannons(ckb * 100_000_000).pack())
+                    .build()
+                    .into(),
+                out_point: OutPoint::new([index as u8 + 1; 32].pack(), 0).into(),
+                output_data: Default::default(),
+                block_number: 0.into(),
+                tx_index: 0.into(),
+            })
+            .collect();
+        StubRpc { cells }
+    }
+
+    fn verification_witness() -> Bytes {
+        let input_type = BytesOpt::new_builder()
+            .set(Some(vec![1u8; 100].pack()))
+            .build();
+        WitnessArgs::new_builder()
+            .input_type(input_type)
+            .build()
+            .as_bytes()
+            .pack()
+    }
+
+    // a partial transaction which pays 200 CKB to an output and carries a verification witness
+    fn partial_tx() -> TransactionView {
+        let output = CellOutput::new_builder()
+            .capacity(Capacity::shannons(200 * 100_000_000).pack())
+            .build();
+        TransactionBuilder::default()
+            .output(output)
+            .output_data(Default::default())
+            .witness(verification_witness())
+            .build()
+    }
+
+    #[test]
+    fn test_completer_lock_script() {
+        let (lock_script, secret_key) = signer();
+        let completer =
+            TransactionCompleter::new(lock_script.clone(), secret_key, default_fee_rate()).unwrap();
+        assert_eq!(completer.lock_script(), &lock_script);
+
+        let result = TransactionCompleter::new(Script::default(), secret_key, default_fee_rate());
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_complete_balance_and_sign() {
+        let completer = completer(default_fee_rate());
+        let rpc = capacity_cells(&[100, 300, 500]);
+        let tx = completer.complete(&rpc, partial_tx()).await.unwrap();
+
+        // 100 CKB is not enough for the output and the change, so the next cell is collected too
+        assert_eq!(tx.inputs().len(), 2);
+        assert_eq!(tx.outputs().len(), 2);
+        let change = tx.outputs().get(1).unwrap();
+        assert_eq!(&change.lock(), completer.lock_script());
+        let change_capacity: u64 = change.capacity().unpack();
+        let outputs_capacity = tx.outputs_capacity().unwrap().as_u64();
+        let fee = 400 * 100_000_000 - outputs_capacity;
+        assert_eq!(change_capacity, 200 * 100_000_000 - fee);
+        assert_eq!(fee, completer.calculate_fee(&tx));
+        assert!(fee > 0);
+
+        // the signature has its own witness, the verification witness is moved behind the inputs
+        assert_eq!(tx.witnesses().len(), 3);
+        let signature = WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data())
+            .unwrap()
+            .lock()
+            .to_opt()
+            .unwrap()
+            .raw_data();
+        assert_eq!(signature.len(), SIGNATURE_SIZE);
+        assert_ne!(signature.to_vec(), vec![0u8; SIGNATURE_SIZE]);
+        assert!(tx.witnesses().get(1).unwrap().is_empty());
+        assert_eq!(tx.witnesses().get(2).unwrap(), verification_witness());
+    }
+
+    #[tokio::test]
+    async fn test_complete_fee_rate() {
+        let rpc = capacity_cells(&[1000]);
+        let low = completer(default_fee_rate())
+            .complete(&rpc, partial_tx())
+            .await
+            .unwrap();
+        let high = completer(default_fee_rate() * 10)
+            .complete(&rpc, partial_tx())
+            .await
+            .unwrap();
+        assert_eq!(
+            low.data().serialized_size_in_block(),
+            high.data().serialized_size_in_block()
+        );
+        let change_capacity =
+            |tx: &TransactionView| -> u64 { tx.outputs().get(1).unwrap().capacity().unpack() };
+        let low_fee = 800 * 100_000_000 - change_capacity(&low);
+        let high_fee = 800 * 100_000_000 - change_capacity(&high);
+        assert_eq!(high_fee, low_fee * 10);
+    }
+
+    #[tokio::test]
+    async fn test_complete_insufficient_capacity() {
+        let completer = completer(default_fee_rate());
+        let rpc = capacity_cells(&[100, 150]);
+        assert!(completer.complete(&rpc, partial_tx()).await.is_err());
+
+        let rpc = capacity_cells(&[]);
+        assert!(completer.complete(&rpc, partial_tx()).await.is_err());
+    }
+}
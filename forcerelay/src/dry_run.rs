@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use ckb_script::TransactionScriptsVerifier;
+use ckb_traits::{CellDataProvider, HeaderProvider};
+use ckb_types::bytes::Bytes;
+use ckb_types::core::cell::{CellMeta, CellMetaBuilder, ResolvedTransaction};
+use ckb_types::core::{Capacity, Cycle, DepType, HeaderView, ScriptHashType, TransactionView};
+use ckb_types::packed::{Byte32, CellDep, CellInput, CellOutput, OutPoint, OutPointVec, Script};
+use ckb_types::prelude::*;
+use eyre::{eyre, Result};
+use log::debug;
+
+use crate::rpc::CkbRpc;
+
+/// The always success script, copied from [nervosnetwork/ckb] with tag "v0.104.0" at
+/// `script/testdata/always_success`, which locks the virtual cells of dry runs.
+///
+/// [nervosnetwork/ckb]: https://github.com/nervosnetwork/ckb
+pub(crate) const ALWAYS_SUCCESS: &[u8] = include_bytes!("../assets/always_success");
+
+/// Runs verification transactions through the CKB script VM locally, so the ones which would
+/// fail on chain are rejected before any fee is paid.
+///
+/// A partial verification transaction carries no script to run, so it is wrapped with a virtual
+/// input whose type script is the checker, a business contract which loads the lightclient cell
+/// and the verify binary from cell deps and verifies the witnesses, just like the real business.
+pub struct DryRunner {
+    checker: Bytes,
+    max_cycles: Cycle,
+}
+
+impl DryRunner {
+    pub fn new(checker: Bytes, max_cycles: Cycle) -> Self {
+        Self {
+            checker,
+            max_cycles,
+        }
+    }
+
+    pub fn from_file(checker_path: &Path, max_cycles: Cycle) -> Result<Self> {
+        let checker = std::fs::read(checker_path).map_err(|err| {
+            eyre!(
+                "cannot read dry run checker {}: {err}",
+                checker_path.display()
+            )
+        })?;
+        Ok(Self::new(checker.into(), max_cycles))
+    }
+
+    /// Resolves the cell deps of the transaction from live cells on CKB and runs the checker,
+    /// the args of the checker are the type hashes of the lightclient cell and the verify binary.
+    /// The cells listed by a dep group are resolved too.
+    pub async fn dry_run<R: CkbRpc>(
+        &self,
+        rpc: &R,
+        tx: &TransactionView,
+        checker_args: Bytes,
+    ) -> Result<Cycle> {
+        let mut cells = DryRunCells::default();
+        for celldep in tx.cell_deps_iter() {
+            let out_point = celldep.out_point();
+            let (output, data) = fetch_live_cell(rpc, &out_point).await?;
+            if celldep.dep_type() == DepType::DepGroup.into() {
+                for member in parse_dep_group(&out_point, &data)? {
+                    let (output, data) = fetch_live_cell(rpc, &member).await?;
+                    cells.insert(member, output, data);
+                }
+            }
+            cells.insert(out_point, output, data);
+        }
+
+        let always_success =
+            cells.deploy(Bytes::from_static(ALWAYS_SUCCESS), Default::default())?;
+        let always_success_lock = Script::new_builder()
+            .code_hash(CellOutput::calc_data_hash(ALWAYS_SUCCESS))
+            .hash_type(ScriptHashType::Data1.into())
+            .build();
+        let checker = cells.deploy(self.checker.clone(), Default::default())?;
+        let checker_type = Script::new_builder()
+            .code_hash(CellOutput::calc_data_hash(&self.checker))
+            .hash_type(ScriptHashType::Data1.into())
+            .args(checker_args.pack())
+            .build();
+        let checker_input = {
+            let output = CellOutput::new_builder()
+                .lock(always_success_lock.clone())
+                .type_(Some(checker_type).pack())
+                .build_exact_capacity(Capacity::zero())
+                .map_err(|err| eyre!("cannot build dry run input: {err}"))?;
+            let out_point = OutPoint::new(Byte32::zero(), u32::MAX);
+            cells.insert(out_point.clone(), output, Bytes::new());
+            CellInput::new(out_point, 0)
+        };
+        let output = CellOutput::new_builder()
+            .lock(always_success_lock)
+            .build_exact_capacity(Capacity::zero())
+            .map_err(|err| eyre!("cannot build dry run output: {err}"))?;
+        let tx = tx
+            .as_advanced_builder()
+            .cell_dep(always_success)
+            .cell_dep(checker)
+            .input(checker_input)
+            .output(output)
+            .output_data(Bytes::new().pack())
+            .build();
+
+        let rtx = cells.resolve(tx)?;
+        let mut verifier = TransactionScriptsVerifier::new(&rtx, &cells);
+        verifier.set_debug_printer(|hash: &Byte32, message: &str| {
+            debug!("{hash:#x} {message}");
+        });
+        verifier
+            .verify(self.max_cycles)
+            .map_err(|err| eyre!("dry run failed: {err}"))
+    }
+}
+
+async fn fetch_live_cell<R: CkbRpc>(rpc: &R, out_point: &OutPoint) -> Result<(CellOutput, Bytes)> {
+    let cell = rpc.get_live_cell(&out_point.clone().into(), true).await?;
+    let cell_info = match cell.cell {
+        Some(cell_info) if cell.status == "live" => cell_info,
+        _ => return Err(eyre!("cell dep {out_point} is {}", cell.status)),
+    };
+    let data = cell_info
+        .data
+        .map(|data| data.content.into_bytes())
+        .unwrap_or_default();
+    Ok((cell_info.output.into(), data))
+}
+
+// the data of a dep group cell is the list of the out points it stands for
+fn parse_dep_group(out_point: &OutPoint, data: &[u8]) -> Result<Vec<OutPoint>> {
+    OutPointVec::from_slice(data)
+        .map(|members| members.into_iter().collect())
+        .map_err(|err| eyre!("invalid dep group {out_point}: {err}"))
+}
+
+// the cells which the dry run transaction refers to, the virtual ones are put under the zero
+// transaction hash
+#[derive(Default)]
+struct DryRunCells {
+    cells: HashMap<OutPoint, (CellOutput, Bytes)>,
+}
+
+impl DryRunCells {
+    fn insert(&mut self, out_point: OutPoint, output: CellOutput, data: Bytes) {
+        self.cells.insert(out_point, (output, data));
+    }
+
+    fn deploy(&mut self, data: Bytes, lock: Script) -> Result<CellDep> {
+        let index = self.cells.len() as u32;
+        let out_point = OutPoint::new(Byte32::zero(), index);
+        let output = Capacity::bytes(data.len())
+            .and_then(|capacity| {
+                CellOutput::new_builder()
+                    .lock(lock)
+                    .build_exact_capacity(capacity)
+            })
+            .map_err(|err| eyre!("cannot build dry run cell: {err}"))?;
+        self.insert(out_point.clone(), output, data);
+        Ok(CellDep::new_builder()
+            .out_point(out_point)
+            .dep_type(DepType::Code.into())
+            .build())
+    }
+
+    fn resolve(&self, transaction: TransactionView) -> Result<ResolvedTransaction> {
+        let resolved_inputs = transaction
+            .inputs()
+            .into_iter()
+            .map(|input| self.cell_meta(input.previous_output()))
+            .collect::<Result<_>>()?;
+        // a dep group is resolved into the cells it lists, just like the chain does
+        let mut resolved_cell_deps = vec![];
+        let mut resolved_dep_groups = vec![];
+        for cell_dep in transaction.cell_deps() {
+            let cell_meta = self.cell_meta(cell_dep.out_point())?;
+            if cell_dep.dep_type() == DepType::DepGroup.into() {
+                let data = cell_meta.mem_cell_data.clone().unwrap_or_default();
+                for member in parse_dep_group(&cell_dep.out_point(), &data)? {
+                    resolved_cell_deps.push(self.cell_meta(member)?);
+                }
+                resolved_dep_groups.push(cell_meta);
+            } else {
+                resolved_cell_deps.push(cell_meta);
+            }
+        }
+        Ok(ResolvedTransaction {
+            transaction,
+            resolved_cell_deps,
+            resolved_inputs,
+            resolved_dep_groups,
+        })
+    }
+
+    fn cell_meta(&self, out_point: OutPoint) -> Result<CellMeta> {
+        let (output, data) = self
+            .cells
+            .get(&out_point)
+            .ok_or_else(|| eyre!("cell {out_point} is not resolved"))?;
+        Ok(
+            CellMetaBuilder::from_cell_output(output.to_owned(), data.clone())
+                .out_point(out_point)
+                .build(),
+        )
+    }
+}
+
+impl CellDataProvider for DryRunCells {
+    fn get_cell_data(&self, out_point: &OutPoint) -> Option<Bytes> {
+        self.cells.get(out_point).map(|(_, data)| data.clone())
+    }
+
+    fn get_cell_data_hash(&self, out_point: &OutPoint) -> Option<Byte32> {
+        self.cells
+            .get(out_point)
+            .map(|(_, data)| CellOutput::calc_data_hash(data))
+    }
+}
+
+impl HeaderProvider for DryRunCells {
+    fn get_header(&self, _block_hash: &Byte32) -> Option<HeaderView> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ckb_types::bytes::Bytes;
+    use ckb_types::core::{DepType, TransactionBuilder};
+    use ckb_types::packed::{CellDep, OutPointVec};
+    use ckb_types::prelude::*;
+
+    use super::{DryRunCells, ALWAYS_SUCCESS};
+
+    #[test]
+    fn test_resolve_dep_group() {
+        let mut cells = DryRunCells::default();
+        let code = cells
+            .deploy(Bytes::from_static(ALWAYS_SUCCESS), Default::default())
+            .unwrap();
+        let members = OutPointVec::new_builder().push(code.out_point()).build();
+        let group = cells
+            .deploy(members.as_bytes(), Default::default())
+            .unwrap();
+        let group = CellDep::new_builder()
+            .out_point(group.out_point())
+            .dep_type(DepType::DepGroup.into())
+            .build();
+
+        let tx = TransactionBuilder::default()
+            .cell_dep(group.clone())
+            .build();
+        let rtx = cells.resolve(tx).unwrap();
+        assert_eq!(rtx.resolved_cell_deps.len(), 1);
+        assert_eq!(rtx.resolved_cell_deps[0].out_point, code.out_point());
+        assert_eq!(rtx.resolved_dep_groups.len(), 1);
+        assert_eq!(rtx.resolved_dep_groups[0].out_point, group.out_point());
+
+        // a dep group whose data is not a list of out points is rejected
+        let invalid = cells
+            .deploy(Bytes::from_static(b"invalid"), Default::default())
+            .unwrap();
+        let invalid = CellDep::new_builder()
+            .out_point(invalid.out_point())
+            .dep_type(DepType::DepGroup.into())
+            .build();
+        let tx = TransactionBuilder::default().cell_dep(invalid).build();
+        let err = cells.resolve(tx).unwrap_err();
+        assert!(err.to_string().contains("invalid dep group"), "{err}");
+    }
+}
@@ -5,8 +5,67 @@ use thiserror::Error;
 pub enum ForcerelayCkbError {
     #[error("invalid ckb rpc url: {0}")]
     InvalidRpcUrl(String),
+    #[error("invalid ckb rpc request: {0}")]
+    InvalidRpcRequest(String),
     #[error("invalid ckb rpc response: {0}")]
     InvalidRpcResponse(String),
+    #[error("ckb rpc request to {url} failed: {message}")]
+    RequestFailed { url: String, message: String },
+    #[error("ckb rpc {method} returns error {code}: {message}")]
+    RpcError {
+        method: String,
+        code: i64,
+        message: String,
+    },
+    #[error("unexpected result of ckb rpc {method}: {message}")]
+    UnexpectedResult { method: String, message: String },
+    #[error("ckb rpc {0} is not supported by the backend")]
+    UnsupportedRpcMethod(String),
     #[error("invalid ethereum 2.0 lightclient contract type_args: {0}")]
     InvalidLightclientContract(H256),
 }
+
+/// A beacon block from the consensus RPC whose header doesn't match the finalized header stored
+/// at the MMR position of its slot.
+#[derive(Debug, Error)]
+#[error("beacon block of slot {slot} mismatches the stored header digest: expected 0x{expected}, got 0x{actual}")]
+pub struct BeaconBlockMismatch {
+    pub slot: u64,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// The part of a verification proof, in the order they are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofPart {
+    HeaderMmrProof,
+    TransactionSszProof,
+    ReceiptMptProof,
+    ReceiptsRootProof,
+    Unknown,
+}
+
+impl std::fmt::Display for ProofPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::HeaderMmrProof => "header MMR proof",
+            Self::TransactionSszProof => "transaction SSZ proof",
+            Self::ReceiptMptProof => "receipt MPT proof",
+            Self::ReceiptsRootProof => "receipts root SSZ proof",
+            Self::Unknown => "unknown part",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ProofVerificationError {
+    #[error("malformed {0}")]
+    Malformed(String),
+    #[error("{part} verification failed: {name} (code {code})")]
+    Failed {
+        part: ProofPart,
+        code: i8,
+        name: String,
+    },
+}
@@ -1,19 +1,23 @@
-use ckb_types::core::TransactionView;
+use ckb_types::core::{Cycle, TransactionView};
 use ckb_types::packed::CellDep;
 use consensus::rpc::ConsensusRpc;
 use consensus::ConsensusClient;
 use eth2_types::MainnetEthSpec;
 use eth_light_client_in_ckb_prover::CachedBeaconBlock;
-use eth_light_client_in_ckb_verification::types::core::Client as OnChainClient;
-use ethers::types::{Transaction, TransactionReceipt};
+pub use eth_light_client_in_ckb_verification::types::core::Client as OnChainClient;
+use ethers::types::{Transaction, TransactionReceipt, H256};
 use eyre::{eyre, Result};
 use storage::prelude::StorageReader;
 
-use crate::assembler::ForcerelayAssembler;
+use crate::assembler::{self, BatchItem, ForcerelayAssembler};
+use crate::completer::TransactionCompleter;
+use crate::dry_run::DryRunner;
 use crate::rpc::CkbRpc;
+use crate::tracker::{self, SubmittedTransaction, TrackStep};
 
 pub struct ForcerelayClient<R: CkbRpc> {
     assembler: ForcerelayAssembler<R>,
+    completer: Option<TransactionCompleter>,
 }
 
 impl<R: CkbRpc> ForcerelayClient<R> {
@@ -25,7 +29,22 @@ impl<R: CkbRpc> ForcerelayClient<R> {
     ) -> Self {
         let assembler =
             ForcerelayAssembler::new(rpc, contract_typeargs, binary_typeargs, client_id);
-        Self { assembler }
+        Self {
+            assembler,
+            completer: None,
+        }
+    }
+
+    /// Enables the complete mode, in which partial transactions can be balanced and signed.
+    pub fn with_completer(mut self, completer: TransactionCompleter) -> Self {
+        self.completer = Some(completer);
+        self
+    }
+
+    /// Enables the dry run mode, in which assembled transactions can be run in the CKB script VM.
+    pub fn with_dry_runner(mut self, dry_runner: DryRunner) -> Self {
+        self.assembler.set_dry_runner(dry_runner);
+        self
     }
 
     pub async fn onchain_client(&self) -> Result<(OnChainClient, CellDep)> {
@@ -56,12 +75,37 @@ impl<R: CkbRpc> ForcerelayClient<R> {
     }
 
     pub async fn update_assembler_celldep(&mut self) -> Result<()> {
+        if let Some(completer) = self.completer.as_mut() {
+            completer
+                .update_sighash_celldep(self.assembler.rpc())
+                .await?;
+        }
         self.assembler.update_binary_celldep().await
     }
 
+    pub async fn complete_tx(&self, tx: TransactionView) -> Result<TransactionView> {
+        match &self.completer {
+            Some(completer) => completer.complete(self.assembler.rpc(), tx).await,
+            None => Err(eyre!("complete mode is not enabled")),
+        }
+    }
+
+    pub async fn dry_run_tx(&self, tx: &TransactionView) -> Result<Option<Cycle>> {
+        self.assembler.dry_run_tx(tx).await
+    }
+
+    pub async fn send_tx(&self, tx: &TransactionView) -> Result<()> {
+        tracker::send_transaction(self.assembler.rpc(), &tx.data().into()).await?;
+        Ok(())
+    }
+
+    pub async fn track_pending_tx(&self, record: &mut SubmittedTransaction) -> Result<TrackStep> {
+        tracker::track_pending(self.assembler.rpc(), record).await
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn assemble_tx(
-        &mut self,
+        &self,
         client: OnChainClient,
         client_celldep: &CellDep,
         consensus: &ConsensusClient<impl ConsensusRpc>,
@@ -73,32 +117,71 @@ impl<R: CkbRpc> ForcerelayClient<R> {
             .assemble_tx(client, client_celldep, consensus, block, tx, receipts)
             .await
     }
+
+    pub async fn assemble_batch_tx(
+        &self,
+        client: OnChainClient,
+        client_celldep: &CellDep,
+        consensus: &ConsensusClient<impl ConsensusRpc>,
+        items: &[BatchItem<'_>],
+    ) -> Result<TransactionView> {
+        self.assembler
+            .assemble_batch_tx(client, client_celldep, consensus, items)
+            .await
+    }
+
+    pub fn assemble_batch_tx_from_cache(
+        &self,
+        client: &OnChainClient,
+        client_celldep: &CellDep,
+        consensus: &ConsensusClient<impl ConsensusRpc>,
+        tx_hashes: &[H256],
+    ) -> Result<Option<TransactionView>> {
+        self.assembler
+            .assemble_batch_tx_from_cache(client, client_celldep, consensus, tx_hashes)
+    }
+
+    pub fn prune_cached_proofs(
+        &self,
+        consensus: &ConsensusClient<impl ConsensusRpc>,
+        client: &OnChainClient,
+    ) -> Result<usize> {
+        assembler::prune_cached_proofs(consensus, client)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use ckb_jsonrpc_types::TransactionView as JsonTxView;
     use ckb_types::core::{Capacity, ScriptHashType, TransactionView};
-    use ckb_types::packed::{CellOutput, Script};
+    use ckb_types::packed::{CellOutput, Script, WitnessArgs};
     use ckb_types::{bytes::Bytes, prelude::*};
+    use eth_light_client_in_ckb_prover::Receipts;
+    use eth_light_client_in_ckb_verification::mmr;
+    use eth_light_client_in_ckb_verification::types::{
+        core, packed,
+        prelude::{Pack as LcPack, Unpack as LcUnpack},
+    };
     use ethers::types::{Transaction, TransactionReceipt};
     use eyre::Result;
-    use std::{cell::RefCell, path::PathBuf, sync::Arc};
-    use tempfile::TempDir;
+    use std::{cell::RefCell, collections::BTreeSet, path::PathBuf, sync::Arc};
+    use storage::prelude::StorageAsMMRStore as _;
     use test_utils::{Context, Verifier};
 
-    use config::{networks, Config};
+    use config::{networks, Config, StorageBackend};
     use consensus::types::{BeaconBlock, Header};
     use consensus::{rpc::mock_rpc::MockRpc, ConsensusClient};
 
+    use crate::assembler::generate_header_mmr_proofs;
+    use crate::dry_run::{self, DryRunner};
+    use crate::errors::{BeaconBlockMismatch, ProofPart, ProofVerificationError};
     use crate::forcerelay::ForcerelayClient;
     use crate::rpc::{MockRpcClient, BINARY_TYPEID_ARGS, CONTRACT_TYPEID_ARGS, TESTDATA_DIR};
-    use crate::setup_test_logger;
+    use crate::{setup_test_logger, util, verifier, CachedBeaconBlockMainnet};
 
     const BUSINESS_BIN: &str = "eth_light_client-mock_business_type_lock";
-    const ALWAYS_SUCCESS: &str = "always_success";
 
-    async fn make_consensus(path: PathBuf, last_header: &Header) -> ConsensusClient<MockRpc> {
+    async fn make_consensus(last_header: &Header) -> ConsensusClient<MockRpc> {
         let base_config = networks::goerli();
         let config = Config {
             consensus_rpc: String::new(),
@@ -106,7 +189,7 @@ mod test {
             chain: base_config.chain,
             forks: base_config.forks,
             max_checkpoint_age: u64::MAX,
-            storage_path: path,
+            storage_backend: StorageBackend::Memory,
             ..Default::default()
         };
 
@@ -132,10 +215,9 @@ mod test {
 
     async fn assemble_partial_tx(
         forcerelay: &mut ForcerelayClient<MockRpcClient>,
-        path: PathBuf,
     ) -> TransactionView {
         let headers: Vec<Header> = load_json_testdata("headers.json").expect("load headers");
-        let consensus = make_consensus(path, headers.last().unwrap()).await;
+        let consensus = make_consensus(headers.last().unwrap()).await;
         let block: BeaconBlock = load_json_testdata("block.json").expect("load block");
         let tx: Transaction = load_json_testdata("transaction.json").expect("load transaction");
         let receipts: Vec<TransactionReceipt> =
@@ -161,6 +243,287 @@ mod test {
             .expect("assemble partial")
     }
 
+    #[tokio::test]
+    async fn test_check_beacon_block() {
+        let headers: Vec<Header> = load_json_testdata("headers.json").expect("load headers");
+        let consensus = make_consensus(headers.last().unwrap()).await;
+        let mut block: BeaconBlock = load_json_testdata("block.json").expect("load block");
+        let cached_block: CachedBeaconBlockMainnet = block.clone().into();
+        assert!(util::check_beacon_block(consensus.storage(), &cached_block).unwrap());
+
+        *block.proposer_index_mut() += 1;
+        let cached_block: CachedBeaconBlockMainnet = block.into();
+        let err = util::check_beacon_block(consensus.storage(), &cached_block).unwrap_err();
+        let mismatch = err.downcast::<BeaconBlockMismatch>().unwrap();
+        assert_eq!(mismatch.slot, cached_block.slot().as_u64());
+    }
+
+    #[tokio::test]
+    async fn test_assemble_batch_tx() {
+        setup_test_logger();
+        let context = Arc::new(RefCell::new(Context::default()));
+        let mut forcerelay = ForcerelayClient::new(
+            MockRpcClient::new(context.clone()),
+            &CONTRACT_TYPEID_ARGS.to_vec(),
+            &BINARY_TYPEID_ARGS.to_vec(),
+            "client_id",
+        );
+
+        let headers: Vec<Header> = load_json_testdata("headers.json").expect("load headers");
+        let consensus = make_consensus(headers.last().unwrap()).await;
+        let block: BeaconBlock = load_json_testdata("block.json").expect("load block");
+        let block: CachedBeaconBlockMainnet = block.into();
+        let tx: Transaction = load_json_testdata("transaction.json").expect("load transaction");
+        let receipts: Vec<TransactionReceipt> =
+            load_json_testdata("receipts.json").expect("load receipts");
+        forcerelay
+            .update_assembler_celldep()
+            .await
+            .expect("update binary celldep");
+
+        let (client, client_celldep) = forcerelay.onchain_client().await.expect("client");
+        let single_tx = forcerelay
+            .assemble_tx(client, &client_celldep, &consensus, &block, &tx, &receipts)
+            .await
+            .expect("assemble single");
+        let (client, client_celldep) = forcerelay.onchain_client().await.expect("client");
+        let items = [
+            (&block, &tx, receipts.as_slice()),
+            (&block, &tx, receipts.as_slice()),
+        ];
+        let batch_tx = forcerelay
+            .assemble_batch_tx(client, &client_celldep, &consensus, &items)
+            .await
+            .expect("assemble batch");
+
+        let single_witness = single_tx.witnesses().get(0).expect("single witness");
+        assert_eq!(batch_tx.witnesses().len(), 2);
+        for witness in batch_tx.witnesses().into_iter() {
+            assert_eq!(witness.as_slice(), single_witness.as_slice());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_header_mmr_proofs() {
+        let context = Arc::new(RefCell::new(Context::default()));
+        let forcerelay = ForcerelayClient::new(
+            MockRpcClient::new(context),
+            &CONTRACT_TYPEID_ARGS.to_vec(),
+            &BINARY_TYPEID_ARGS.to_vec(),
+            "client_id",
+        );
+        let headers: Vec<Header> = load_json_testdata("headers.json").expect("load headers");
+        let consensus = make_consensus(headers.last().unwrap()).await;
+        let block: BeaconBlock = load_json_testdata("block.json").expect("load block");
+        let block: CachedBeaconBlockMainnet = block.into();
+        let (client, _) = forcerelay.onchain_client().await.expect("client");
+        let storage = consensus.storage();
+
+        let block_slot = block.slot().as_u64();
+        let slots = BTreeSet::from([client.minimal_slot, block_slot]);
+        assert_ne!(client.minimal_slot, block_slot);
+        let proofs = generate_header_mmr_proofs(storage, &client, &slots).expect("proofs");
+        let proof_bytes = |items: &[core::HeaderDigest]| {
+            items
+                .iter()
+                .map(|item| LcPack::pack(item).as_slice().to_vec())
+                .collect::<Vec<_>>()
+        };
+        // every slot gets the single-leaf proof of its own
+        let mmr = storage.chain_root_mmr(client.maximal_slot).unwrap();
+        for slot in &slots {
+            let position = mmr::lib::leaf_index_to_pos(slot - client.minimal_slot);
+            let expected = mmr.gen_proof(vec![position]).unwrap();
+            let expected = expected
+                .proof_items()
+                .iter()
+                .map(LcUnpack::unpack)
+                .collect::<Vec<_>>();
+            assert_eq!(proof_bytes(&proofs[slot]), proof_bytes(&expected));
+        }
+        assert_ne!(
+            proof_bytes(&proofs[&client.minimal_slot]),
+            proof_bytes(&proofs[&block_slot])
+        );
+
+        let single = generate_header_mmr_proofs(storage, &client, &BTreeSet::from([block_slot]))
+            .expect("single proof");
+        assert_eq!(
+            proof_bytes(&single[&block_slot]),
+            proof_bytes(&proofs[&block_slot])
+        );
+        let out_of_range = BTreeSet::from([client.maximal_slot + 1]);
+        assert!(generate_header_mmr_proofs(storage, &client, &out_of_range).is_err());
+
+        // each witness of a batch carries the proof items of its own slot
+        let tx: Transaction = load_json_testdata("transaction.json").expect("load transaction");
+        let receipts: Vec<TransactionReceipt> =
+            load_json_testdata("receipts.json").expect("load receipts");
+        let receipts: Receipts = receipts.into();
+        let index = util::find_receipt_index(tx.hash, &receipts).expect("receipt index");
+        let payload = util::generate_packed_payload(&block, &tx, &receipts, index).unwrap();
+        let packed_proofs = slots
+            .iter()
+            .map(|slot| {
+                let proof = util::generate_packed_transaction_proof(
+                    &block,
+                    &receipts,
+                    index,
+                    &proofs[slot],
+                )
+                .unwrap();
+                (proof, payload.clone())
+            })
+            .collect::<Vec<_>>();
+        let batch_tx = util::assemble_batch_verification_transaction(&packed_proofs, &[]).unwrap();
+        assert_eq!(batch_tx.witnesses().len(), slots.len());
+        for (witness, slot) in batch_tx.witnesses().into_iter().zip(&slots) {
+            let witness_args = WitnessArgs::from_slice(&witness.raw_data()).unwrap();
+            let proof = witness_args.input_type().to_opt().unwrap().raw_data();
+            let proof: core::TransactionProof =
+                LcUnpack::unpack(&packed::TransactionProof::from_slice(&proof).unwrap());
+            assert_eq!(
+                proof_bytes(&proof.header_mmr_proof),
+                proof_bytes(&proofs[slot])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_proofs() {
+        setup_test_logger();
+        let context = Arc::new(RefCell::new(Context::default()));
+        let mut forcerelay = ForcerelayClient::new(
+            MockRpcClient::new(context.clone()),
+            &CONTRACT_TYPEID_ARGS.to_vec(),
+            &BINARY_TYPEID_ARGS.to_vec(),
+            "client_id",
+        );
+
+        let headers: Vec<Header> = load_json_testdata("headers.json").expect("load headers");
+        let consensus = make_consensus(headers.last().unwrap()).await;
+        let block: BeaconBlock = load_json_testdata("block.json").expect("load block");
+        let tx: Transaction = load_json_testdata("transaction.json").expect("load transaction");
+        let receipts: Vec<TransactionReceipt> =
+            load_json_testdata("receipts.json").expect("load receipts");
+        forcerelay
+            .update_assembler_celldep()
+            .await
+            .expect("update binary celldep");
+
+        let (client, client_celldep) = forcerelay.onchain_client().await.expect("client");
+        let cached_tx = forcerelay
+            .assemble_batch_tx_from_cache(&client, &client_celldep, &consensus, &[tx.hash])
+            .expect("assemble from cache");
+        assert!(cached_tx.is_none());
+
+        let assembled_tx = forcerelay
+            .assemble_tx(
+                client.clone(),
+                &client_celldep,
+                &consensus,
+                &block.into(),
+                &tx,
+                &receipts,
+            )
+            .await
+            .expect("assemble partial");
+        let cached_tx = forcerelay
+            .assemble_batch_tx_from_cache(&client, &client_celldep, &consensus, &[tx.hash])
+            .expect("assemble from cache")
+            .expect("cached proof");
+        assert_eq!(cached_tx.as_slice(), assembled_tx.as_slice());
+
+        // the proof is stale once the on-chain client is moved
+        let mut moved_client = client.clone();
+        moved_client.maximal_slot += 1;
+        let cached_tx = forcerelay
+            .assemble_batch_tx_from_cache(&moved_client, &client_celldep, &consensus, &[tx.hash])
+            .expect("assemble from cache");
+        assert!(cached_tx.is_none());
+        assert_eq!(
+            forcerelay.prune_cached_proofs(&consensus, &client).unwrap(),
+            0
+        );
+        assert_eq!(
+            forcerelay
+                .prune_cached_proofs(&consensus, &moved_client)
+                .unwrap(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        setup_test_logger();
+        let context = Arc::new(RefCell::new(Context::default()));
+        let dry_runner = DryRunner::from_file(
+            &PathBuf::from(format!("{TESTDATA_DIR}lightclient/{BUSINESS_BIN}")),
+            u64::MAX,
+        )
+        .expect("load checker");
+        let mut forcerelay = ForcerelayClient::new(
+            MockRpcClient::new(context.clone()),
+            &CONTRACT_TYPEID_ARGS.to_vec(),
+            &BINARY_TYPEID_ARGS.to_vec(),
+            "client_id",
+        )
+        .with_dry_runner(dry_runner);
+
+        let tx = assemble_partial_tx(&mut forcerelay).await;
+        let cycles = forcerelay.dry_run_tx(&tx).await.expect("dry run");
+        assert!(cycles.unwrap() > 0);
+
+        // the verify binary rejects the transaction without proofs
+        let tx = tx.as_advanced_builder().set_witnesses(vec![]).build();
+        assert!(forcerelay.dry_run_tx(&tx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_offline() {
+        setup_test_logger();
+        let context = Arc::new(RefCell::new(Context::default()));
+        let mut forcerelay = ForcerelayClient::new(
+            MockRpcClient::new(context.clone()),
+            &CONTRACT_TYPEID_ARGS.to_vec(),
+            &BINARY_TYPEID_ARGS.to_vec(),
+            "client_id",
+        );
+
+        let tx = assemble_partial_tx(&mut forcerelay).await;
+        let (mut client, _) = forcerelay.onchain_client().await.expect("client");
+
+        let json = serde_json::to_string(&JsonTxView::from(tx.clone())).unwrap();
+        let verifications = verifier::verify_transaction_json(&client, &json).expect("verify");
+        assert_eq!(verifications.len(), 1);
+        assert!(verifications[0].result.is_ok());
+
+        // the transaction in the payload is not the proved one
+        let witness_args =
+            WitnessArgs::from_slice(&tx.witnesses().get(0).unwrap().raw_data()).unwrap();
+        let proof = witness_args.input_type().to_opt().unwrap().raw_data();
+        let payload = witness_args.output_type().to_opt().unwrap().raw_data();
+        let mut tampered: core::TransactionPayload =
+            LcUnpack::unpack(&packed::TransactionPayload::from_slice(&payload).unwrap());
+        *tampered.transaction.last_mut().unwrap() ^= 1;
+        let tampered = LcPack::pack(&tampered);
+        match verifier::verify_proof(&client, &proof, tampered.as_slice()) {
+            Err(ProofVerificationError::Failed { part, .. }) => {
+                assert_eq!(part, ProofPart::TransactionSszProof)
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+
+        client.maximal_slot += 1;
+        let verifications = verifier::verify_transaction(&client, &tx.data()).expect("verify");
+        match &verifications[0].result {
+            Err(ProofVerificationError::Failed { part, .. }) => {
+                assert_eq!(*part, ProofPart::HeaderMmrProof)
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
     fn complete_partial_tx(
         forcerelay: &ForcerelayClient<MockRpcClient>,
         context: Arc<RefCell<Context>>,
@@ -169,9 +532,8 @@ mod test {
         let mut context = context.borrow_mut();
         // prepare always_success
         let (always_success_lock, always_success_contract) = {
-            let data = std::fs::read(format!("{TESTDATA_DIR}lightclient/{ALWAYS_SUCCESS}"))
-                .expect("read always_success");
-            let deployed_cell = context.deploy(data.into(), Default::default(), None);
+            let data = Bytes::from_static(dry_run::ALWAYS_SUCCESS);
+            let deployed_cell = context.deploy(data, Default::default(), None);
             let lock = Script::new_builder()
                 .code_hash(deployed_cell.data_hash())
                 .hash_type(ScriptHashType::Data1.into())
@@ -230,8 +592,7 @@ mod test {
         );
 
         // generate tx
-        let path = TempDir::new().unwrap();
-        let tx = assemble_partial_tx(&mut forcerelay, path.into_path()).await;
+        let tx = assemble_partial_tx(&mut forcerelay).await;
         let tx = complete_partial_tx(&forcerelay, context.clone(), tx);
 
         // run tx
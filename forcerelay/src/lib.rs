@@ -4,10 +4,15 @@ use env_logger::{Builder, Target};
 use log::LevelFilter;
 
 pub mod assembler;
+pub mod completer;
+pub mod dry_run;
 pub mod errors;
 pub mod forcerelay;
 pub mod rpc;
+pub mod tracker;
 pub mod util;
+pub mod verifier;
+pub mod watcher;
 
 pub type CachedBeaconBlockMainnet =
     eth_light_client_in_ckb_prover::CachedBeaconBlock<eth2_types::MainnetEthSpec>;
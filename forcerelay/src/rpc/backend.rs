@@ -0,0 +1,73 @@
+use ckb_jsonrpc_types::{
+    BlockNumber, BlockView, CellWithStatus, HeaderView, JsonBytes, OutPoint, OutputsValidator,
+    Transaction, TransactionWithStatusResponse,
+};
+use ckb_sdk::rpc::ckb_indexer::{Cell, Pagination, SearchKey};
+use ckb_types::H256;
+
+use crate::rpc::rpc_trait::{CkbRpc, Rpc};
+use crate::rpc::{LightClientRpcClient, RpcClient};
+
+/// The CKB node which the verifier talks to, chosen by configuration.
+#[derive(Clone)]
+pub enum CkbRpcBackend {
+    /// A full node, with the indexer module enabled.
+    Full(RpcClient),
+    /// A `ckb-light-client` node.
+    LightClient(LightClientRpcClient),
+}
+
+macro_rules! delegate {
+    ($self:ident, $method:ident($($arg:expr),*)) => {
+        match $self {
+            CkbRpcBackend::Full(rpc) => rpc.$method($($arg),*),
+            CkbRpcBackend::LightClient(rpc) => rpc.$method($($arg),*),
+        }
+    };
+}
+
+impl CkbRpc for CkbRpcBackend {
+    fn get_block_by_number(&self, number: BlockNumber) -> Rpc<BlockView> {
+        delegate!(self, get_block_by_number(number))
+    }
+
+    fn get_block(&self, hash: &H256) -> Rpc<BlockView> {
+        delegate!(self, get_block(hash))
+    }
+
+    fn get_tip_header(&self) -> Rpc<HeaderView> {
+        delegate!(self, get_tip_header())
+    }
+
+    fn get_transaction(&self, hash: &H256) -> Rpc<Option<TransactionWithStatusResponse>> {
+        delegate!(self, get_transaction(hash))
+    }
+
+    fn get_live_cell(&self, out_point: &OutPoint, with_data: bool) -> Rpc<CellWithStatus> {
+        delegate!(self, get_live_cell(out_point, with_data))
+    }
+
+    fn get_txs_by_hashes(
+        &self,
+        hashes: Vec<H256>,
+    ) -> Rpc<Vec<Option<TransactionWithStatusResponse>>> {
+        delegate!(self, get_txs_by_hashes(hashes))
+    }
+
+    fn fetch_live_cells(
+        &self,
+        search_key: SearchKey,
+        limit: u32,
+        cursor: Option<JsonBytes>,
+    ) -> Rpc<Pagination<Cell>> {
+        delegate!(self, fetch_live_cells(search_key, limit, cursor))
+    }
+
+    fn send_transaction(
+        &self,
+        tx: &Transaction,
+        outputs_validator: Option<OutputsValidator>,
+    ) -> Rpc<H256> {
+        delegate!(self, send_transaction(tx, outputs_validator))
+    }
+}
@@ -5,56 +5,44 @@ use ckb_jsonrpc_types::{
 use ckb_sdk::rpc::ckb_indexer::{Cell, Order, Pagination, SearchKey};
 use ckb_types::H256;
 use futures::FutureExt;
+use jsonrpc_core::response::Output;
+use jsonrpc_core::Id;
+use log::warn;
 use reqwest::{Client, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::errors::ForcerelayCkbError;
 use crate::rpc::rpc_trait::{CkbRpc, Rpc};
 
 #[allow(clippy::upper_case_acronyms)]
-enum Target {
+#[derive(Clone, Copy)]
+pub(super) enum Target {
     CKB,
     Indexer,
 }
 
-macro_rules! jsonrpc {
-    ($method:expr, $id:expr, $self:ident, $return:ty$(, $params:ident$(,)?)*) => {{
-        let data = format!(
-            r#"{{"id": {}, "jsonrpc": "2.0", "method": "{}", "params": {}}}"#,
-            $self.id.load(Ordering::Relaxed),
-            $method,
-            serde_json::to_value(($($params,)*)).unwrap()
-        );
-        $self.id.fetch_add(1, Ordering::Relaxed);
-
-        let req_json: serde_json::Value = serde_json::from_str(&data).unwrap();
-
-        let url = match $id {
-            Target::CKB => $self.ckb_uri.clone(),
-            Target::Indexer => $self.indexer_uri.clone(),
-        };
-        let c = $self.raw.post(url.clone()).json(&req_json);
-        async {
-            let resp = c
-                .send()
-                .await
-                .map_err(move |_| ForcerelayCkbError::InvalidRpcUrl(url.to_string()))?;
-            let output = resp
-                .json::<jsonrpc_core::response::Output>()
-                .await
-                .map_err(|e| ForcerelayCkbError::InvalidRpcResponse(e.to_string()))?;
-
-            match output {
-                jsonrpc_core::response::Output::Success(success) => {
-                    Ok(serde_json::from_value::<$return>(success.result).unwrap())
-                }
-                jsonrpc_core::response::Output::Failure(e) => {
-                    Err(ForcerelayCkbError::InvalidRpcResponse(format!("{:?}", e)))
-                }
-            }
+/// The retry policy of requests which fail before a JSON-RPC response is received, e.g. the
+/// connection is refused or the node responds with an HTTP error.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    /// The delay before the first retry, which is doubled for each of the following retries.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
         }
-    }}
+    }
 }
 
 #[derive(Clone)]
@@ -63,53 +51,221 @@ pub struct RpcClient {
     ckb_uri: Url,
     indexer_uri: Url,
     id: Arc<AtomicU64>,
+    retry: RetryConfig,
+    ckb_timeout: Option<Duration>,
+    indexer_timeout: Option<Duration>,
 }
 
 impl RpcClient {
-    pub fn new(ckb_uri: &str, indexer_uri: &str) -> Self {
-        RpcClient {
+    pub fn new(ckb_uri: &str, indexer_uri: &str) -> Result<Self, ForcerelayCkbError> {
+        let parse = |uri: &str| {
+            Url::parse(uri).map_err(|_| ForcerelayCkbError::InvalidRpcUrl(uri.to_owned()))
+        };
+        Ok(RpcClient {
             raw: Client::new(),
-            ckb_uri: Url::parse(ckb_uri).expect("ckb_uri"),
-            indexer_uri: Url::parse(indexer_uri).expect("indexer_uri"),
+            ckb_uri: parse(ckb_uri)?,
+            indexer_uri: parse(indexer_uri)?,
             id: Arc::new(AtomicU64::new(0)),
+            retry: RetryConfig::default(),
+            ckb_timeout: None,
+            indexer_timeout: None,
+        })
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sets the timeouts of the requests to the CKB node and the indexer, a request which times
+    /// out is retried as a failed one.
+    pub fn with_timeouts(
+        mut self,
+        ckb_timeout: Option<Duration>,
+        indexer_timeout: Option<Duration>,
+    ) -> Self {
+        self.ckb_timeout = ckb_timeout;
+        self.indexer_timeout = indexer_timeout;
+        self
+    }
+
+    fn url(&self, target: Target) -> Url {
+        match target {
+            Target::CKB => self.ckb_uri.clone(),
+            Target::Indexer => self.indexer_uri.clone(),
+        }
+    }
+
+    fn timeout(&self, target: Target) -> Option<Duration> {
+        match target {
+            Target::CKB => self.ckb_timeout,
+            Target::Indexer => self.indexer_timeout,
+        }
+    }
+
+    pub(super) fn call<T, P>(&self, target: Target, method: &'static str, params: P) -> Rpc<T>
+    where
+        T: DeserializeOwned + Send + 'static,
+        P: Serialize,
+    {
+        let params = serde_json::to_value(params);
+        let client = self.clone();
+        async move {
+            let params =
+                params.map_err(|err| ForcerelayCkbError::InvalidRpcRequest(err.to_string()))?;
+            let id = client.id.fetch_add(1, Ordering::Relaxed);
+            let response = client
+                .post(target, &request_json(method, id, params))
+                .await?;
+            let output: Output = serde_json::from_value(response)
+                .map_err(|err| ForcerelayCkbError::InvalidRpcResponse(err.to_string()))?;
+            parse_output(method, output)
+        }
+        .boxed()
+    }
+
+    /// Sends the requests of one method in a JSON-RPC batch, the results are in the same order
+    /// as the params.
+    pub(super) fn batch_call<T, P>(
+        &self,
+        target: Target,
+        method: &'static str,
+        params: Vec<P>,
+    ) -> Rpc<Vec<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+        P: Serialize,
+    {
+        let params = params
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>();
+        let client = self.clone();
+        async move {
+            let params =
+                params.map_err(|err| ForcerelayCkbError::InvalidRpcRequest(err.to_string()))?;
+            if params.is_empty() {
+                return Ok(vec![]);
+            }
+            let count = params.len() as u64;
+            let first_id = client.id.fetch_add(count, Ordering::Relaxed);
+            let requests = params
+                .into_iter()
+                .zip(first_id..)
+                .map(|(params, id)| request_json(method, id, params))
+                .collect();
+            let response = client.post(target, &Value::Array(requests)).await?;
+            let outputs: Vec<Output> = serde_json::from_value(response)
+                .map_err(|err| ForcerelayCkbError::InvalidRpcResponse(err.to_string()))?;
+
+            // the responses of a batch can be in any order
+            let mut results = (0..count).map(|_| None).collect::<Vec<Option<T>>>();
+            for output in outputs {
+                let index = match output.id() {
+                    Id::Num(id) if (first_id..first_id + count).contains(id) => id - first_id,
+                    id => {
+                        return Err(ForcerelayCkbError::InvalidRpcResponse(format!(
+                            "unexpected id {id:?} in batch response"
+                        )))
+                    }
+                };
+                results[index as usize] = Some(parse_output(method, output)?);
+            }
+            results
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    ForcerelayCkbError::InvalidRpcResponse("missing responses in batch".to_owned())
+                })
+        }
+        .boxed()
+    }
+
+    // only the failures before a JSON-RPC response is received are retried
+    async fn post(&self, target: Target, body: &Value) -> Result<Value, ForcerelayCkbError> {
+        let url = self.url(target);
+        let mut backoff = self.retry.initial_backoff;
+        let mut retries = 0;
+        loop {
+            let result = async {
+                let mut request = self.raw.post(url.clone()).json(body);
+                if let Some(timeout) = self.timeout(target) {
+                    request = request.timeout(timeout);
+                }
+                request
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<Value>()
+                    .await
+            }
+            .await;
+            match result {
+                Ok(response) => return Ok(response),
+                Err(err) if retries < self.retry.max_retries => {
+                    warn!("ckb rpc request to {url} failed: {err}, retry in {backoff:?}");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.retry.max_backoff);
+                    retries += 1;
+                }
+                Err(err) => {
+                    return Err(ForcerelayCkbError::RequestFailed {
+                        url: url.to_string(),
+                        message: err.to_string(),
+                    })
+                }
+            }
         }
     }
 }
 
+fn request_json(method: &str, id: u64, params: Value) -> Value {
+    json!({
+        "id": id,
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+    })
+}
+
+fn parse_output<T: DeserializeOwned>(
+    method: &str,
+    output: Output,
+) -> Result<T, ForcerelayCkbError> {
+    match output {
+        Output::Success(success) => serde_json::from_value(success.result).map_err(|err| {
+            ForcerelayCkbError::UnexpectedResult {
+                method: method.to_owned(),
+                message: err.to_string(),
+            }
+        }),
+        Output::Failure(failure) => Err(ForcerelayCkbError::RpcError {
+            method: method.to_owned(),
+            code: failure.error.code.code(),
+            message: failure.error.message,
+        }),
+    }
+}
+
 impl CkbRpc for RpcClient {
     fn get_block_by_number(&self, number: BlockNumber) -> Rpc<BlockView> {
-        jsonrpc!("get_block_by_number", Target::CKB, self, BlockView, number).boxed()
+        self.call(Target::CKB, "get_block_by_number", (number,))
     }
 
     fn get_block(&self, hash: &H256) -> Rpc<BlockView> {
-        jsonrpc!("get_block", Target::CKB, self, BlockView, hash).boxed()
+        self.call(Target::CKB, "get_block", (hash,))
     }
 
     fn get_tip_header(&self) -> Rpc<HeaderView> {
-        jsonrpc!("get_tip_header", Target::CKB, self, HeaderView).boxed()
+        self.call(Target::CKB, "get_tip_header", [(); 0])
     }
 
     fn get_transaction(&self, hash: &H256) -> Rpc<Option<TransactionWithStatusResponse>> {
-        jsonrpc!(
-            "get_transaction",
-            Target::CKB,
-            self,
-            Option<TransactionWithStatusResponse>,
-            hash
-        )
-        .boxed()
+        self.call(Target::CKB, "get_transaction", (hash,))
     }
 
     fn get_live_cell(&self, out_point: &OutPoint, with_data: bool) -> Rpc<CellWithStatus> {
-        jsonrpc!(
-            "get_live_cell",
-            Target::CKB,
-            self,
-            CellWithStatus,
-            out_point,
-            with_data
-        )
-        .boxed()
+        self.call(Target::CKB, "get_live_cell", (out_point, with_data))
     }
 
     fn send_transaction(
@@ -117,36 +273,15 @@ impl CkbRpc for RpcClient {
         tx: &Transaction,
         outputs_validator: Option<OutputsValidator>,
     ) -> Rpc<H256> {
-        jsonrpc!(
-            "send_transaction",
-            Target::CKB,
-            self,
-            H256,
-            tx,
-            outputs_validator
-        )
-        .boxed()
+        self.call(Target::CKB, "send_transaction", (tx, outputs_validator))
     }
 
     fn get_txs_by_hashes(
         &self,
         hashes: Vec<H256>,
     ) -> Rpc<Vec<Option<TransactionWithStatusResponse>>> {
-        let mut list = Vec::with_capacity(hashes.len());
-        let mut res = Vec::with_capacity(hashes.len());
-        for hash in hashes {
-            let task = self.get_transaction(&hash);
-            list.push(tokio::spawn(task));
-        }
-        async {
-            for i in list {
-                let r = i.await.unwrap()?;
-                res.push(r);
-            }
-
-            Ok(res)
-        }
-        .boxed()
+        let params = hashes.into_iter().map(|hash| (hash,)).collect();
+        self.batch_call(Target::CKB, "get_transaction", params)
     }
 
     fn fetch_live_cells(
@@ -157,17 +292,87 @@ impl CkbRpc for RpcClient {
     ) -> Rpc<Pagination<Cell>> {
         let order = Order::Asc;
         let limit = Uint32::from(limit);
-
-        jsonrpc!(
-            "get_cells",
+        self.call(
             Target::Indexer,
-            self,
-            Pagination<Cell>,
-            search_key,
-            order,
-            limit,
-            cursor,
+            "get_cells",
+            (search_key, order, limit, cursor),
         )
-        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use ckb_types::H256;
+    use serde_json::{json, Value};
+
+    use super::{RetryConfig, RpcClient};
+    use crate::errors::ForcerelayCkbError;
+    use crate::rpc::{CkbRpc, StubServer};
+
+    fn retry(max_retries: u32) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_call_reorders_responses() {
+        // answers the batch in reversed order, transactions are missing except the first one
+        let server = StubServer::start(1, |request| {
+            let responses = request
+                .as_array()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, request)| {
+                    let result = if i == 0 {
+                        json!({"transaction": null, "cycles": null, "tx_status": {"status": "unknown", "block_hash": null, "reason": null}})
+                    } else {
+                        Value::Null
+                    };
+                    json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+                })
+                .collect();
+            Value::Array(responses)
+        })
+        .await;
+        let rpc = RpcClient::new(&server.url, &server.url)
+            .unwrap()
+            .with_retry(retry(1));
+
+        let hashes = vec![H256::default(); 3];
+        let txs = rpc.get_txs_by_hashes(hashes).await.unwrap();
+        assert!(txs[0].is_some());
+        assert!(txs[1].is_none() && txs[2].is_none());
+        assert_eq!(server.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_typed_errors() {
+        let server = StubServer::start(usize::MAX, |_| Value::Null).await;
+        let rpc = RpcClient::new(&server.url, &server.url)
+            .unwrap()
+            .with_retry(retry(2));
+        let err = rpc.get_tip_header().await.unwrap_err();
+        assert!(matches!(err, ForcerelayCkbError::RequestFailed { .. }));
+        assert_eq!(server.requests(), 3);
+
+        let server = StubServer::start(0, |request| {
+            json!({"jsonrpc": "2.0", "id": request["id"], "error": {"code": -3, "message": "oops"}})
+        })
+        .await;
+        let rpc = RpcClient::new(&server.url, &server.url).unwrap();
+        let err = rpc.get_tip_header().await.unwrap_err();
+        assert!(matches!(err, ForcerelayCkbError::RpcError { code: -3, .. }));
+
+        assert!(matches!(
+            RpcClient::new("not a url", &server.url),
+            Err(ForcerelayCkbError::InvalidRpcUrl(_))
+        ));
     }
 }
@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ckb_jsonrpc_types::{
+    BlockNumber, BlockView, CellData, CellInfo, CellWithStatus, HeaderView, JsonBytes, OutPoint,
+    OutputsValidator, Script as JsonScript, Status, Transaction, TransactionView,
+    TransactionWithStatusResponse, Uint32,
+};
+use ckb_sdk::rpc::ckb_indexer::{Cell, Order, Pagination, ScriptType, SearchKey};
+use ckb_sdk::traits::{CellQueryOptions, LiveCell, PrimaryScriptType};
+use ckb_types::packed::{self, CellOutput};
+use ckb_types::{prelude::*, H256};
+use futures::FutureExt;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::errors::ForcerelayCkbError;
+use crate::rpc::ckb_rpc::{RetryConfig, RpcClient, Target};
+use crate::rpc::rpc_trait::{CkbRpc, Rpc};
+
+const GET_CELLS_PAGE_SIZE: u32 = 64;
+
+/// A `CkbRpc` backed by the RPC of a `ckb-light-client` node, instead of a full node with the
+/// indexer module.
+///
+/// A light client only syncs the cells and transactions of the registered scripts, so the script
+/// of every cell search is registered by `set_scripts` before searching, e.g. the type scripts of
+/// the lightclient cell and the verify binary cell, and the lock script of the signer.
+///
+/// A script is synced from its own start block if it's set by `with_script_start_block`, or from
+/// the block of the transaction if it's registered to look up a cell of the transaction.
+#[derive(Clone)]
+pub struct LightClientRpcClient {
+    rpc: RpcClient,
+    /// The block number from which newly registered scripts are synced.
+    start_block_number: u64,
+    script_start_blocks: HashMap<JsonScript, u64>,
+    /// The registered scripts and the block numbers from which they are synced.
+    registered: Arc<Mutex<HashMap<(JsonScript, LightClientScriptType), u64>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LightClientScriptType {
+    Lock,
+    Type,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScriptStatus {
+    script: JsonScript,
+    script_type: LightClientScriptType,
+    block_number: BlockNumber,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LightClientTxStatus {
+    status: Status,
+    block_hash: Option<H256>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransactionWithStatus {
+    transaction: Option<TransactionView>,
+    tx_status: LightClientTxStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum FetchStatus<T> {
+    Added {
+        timestamp: ckb_jsonrpc_types::Uint64,
+    },
+    Fetching {
+        first_sent: ckb_jsonrpc_types::Uint64,
+    },
+    Fetched {
+        data: T,
+    },
+    NotFound,
+}
+
+impl From<ScriptType> for LightClientScriptType {
+    fn from(script_type: ScriptType) -> Self {
+        match script_type {
+            ScriptType::Lock => Self::Lock,
+            ScriptType::Type => Self::Type,
+        }
+    }
+}
+
+impl LightClientRpcClient {
+    pub fn new(uri: &str, start_block_number: u64) -> Result<Self, ForcerelayCkbError> {
+        Ok(Self {
+            rpc: RpcClient::new(uri, uri)?,
+            start_block_number,
+            script_start_blocks: Default::default(),
+            registered: Default::default(),
+        })
+    }
+
+    /// Syncs `script` from `block_number` instead of the start block number, e.g. the lock script
+    /// of the signer whose cells are created earlier.
+    pub fn with_script_start_block(mut self, script: packed::Script, block_number: u64) -> Self {
+        self.script_start_blocks.insert(script.into(), block_number);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.rpc = self.rpc.with_retry(retry);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.rpc = self.rpc.with_timeouts(timeout, timeout);
+        self
+    }
+
+    /// Registers the script into the light client to be synced from `block_number`, if it's not
+    /// registered from the block or an earlier one yet, the other registered scripts are kept.
+    async fn register_script(
+        &self,
+        script: JsonScript,
+        script_type: LightClientScriptType,
+        block_number: u64,
+    ) -> Result<(), ForcerelayCkbError> {
+        let key = (script, script_type);
+        // held until the script is registered, so that it's not registered concurrently
+        let mut registered = self.registered.lock().await;
+        if matches!(registered.get(&key), Some(from) if *from <= block_number) {
+            return Ok(());
+        }
+        let mut scripts: Vec<ScriptStatus> =
+            self.rpc.call(Target::CKB, "get_scripts", [(); 0]).await?;
+        let status = scripts
+            .iter_mut()
+            .find(|status| status.script == key.0 && status.script_type == key.1);
+        let from = match status {
+            Some(status) if status.block_number.value() <= block_number => {
+                Some(status.block_number.value())
+            }
+            Some(status) => {
+                status.block_number = block_number.into();
+                None
+            }
+            None => {
+                scripts.push(ScriptStatus {
+                    script: key.0.clone(),
+                    script_type: key.1,
+                    block_number: block_number.into(),
+                });
+                None
+            }
+        };
+        let from = match from {
+            Some(from) => from,
+            None => {
+                self.rpc
+                    .call::<(), _>(Target::CKB, "set_scripts", (scripts,))
+                    .await?;
+                info!(
+                    "registered {:?} script {} into ckb light client from block #{block_number}",
+                    key.1,
+                    packed::Script::from(key.0.clone()),
+                );
+                block_number
+            }
+        };
+        registered.insert(key, from);
+        Ok(())
+    }
+
+    fn script_start_block(&self, script: &JsonScript) -> u64 {
+        self.script_start_blocks
+            .get(script)
+            .copied()
+            .unwrap_or(self.start_block_number)
+    }
+
+    async fn block_number(&self, block_hash: &H256) -> Result<Option<u64>, ForcerelayCkbError> {
+        let header: Option<HeaderView> = self
+            .rpc
+            .call(Target::CKB, "get_header", (block_hash,))
+            .await?;
+        Ok(header.map(|header| header.inner.number.value()))
+    }
+
+    /// Gets the transaction from the synced ones first, and fetches it from the network if it's
+    /// unknown to the light client.
+    async fn transaction_with_status(
+        &self,
+        hash: &H256,
+    ) -> Result<TransactionWithStatus, ForcerelayCkbError> {
+        let tx: TransactionWithStatus = self
+            .rpc
+            .call(Target::CKB, "get_transaction", (hash,))
+            .await?;
+        if tx.tx_status.status != Status::Unknown {
+            return Ok(tx);
+        }
+        let fetched: FetchStatus<TransactionWithStatus> = self
+            .rpc
+            .call(Target::CKB, "fetch_transaction", (hash,))
+            .await?;
+        match fetched {
+            FetchStatus::Fetched { data } => Ok(data),
+            _ => Ok(tx),
+        }
+    }
+
+    async fn get_cells(
+        &self,
+        search_key: SearchKey,
+        limit: u32,
+        cursor: Option<JsonBytes>,
+        start_block: u64,
+    ) -> Result<Pagination<Cell>, ForcerelayCkbError> {
+        self.register_script(
+            search_key.script.clone(),
+            search_key.script_type.clone().into(),
+            start_block,
+        )
+        .await?;
+        let order = Order::Asc;
+        let limit = Uint32::from(limit);
+        self.rpc
+            .call(Target::CKB, "get_cells", (search_key, order, limit, cursor))
+            .await
+    }
+
+    /// The light client has no `get_live_cell`, so the cell is searched from the live cells of
+    /// its type script, or its lock script if it has no type script, which are synced from the
+    /// block of the transaction at least.
+    async fn search_live_cell(
+        &self,
+        out_point: &OutPoint,
+        with_data: bool,
+    ) -> Result<CellWithStatus, ForcerelayCkbError> {
+        let unknown = CellWithStatus {
+            cell: None,
+            status: "unknown".to_owned(),
+        };
+        let tx = self.transaction_with_status(&out_point.tx_hash).await?;
+        let output = tx.transaction.and_then(|tx| {
+            tx.inner
+                .outputs
+                .get(out_point.index.value() as usize)
+                .cloned()
+        });
+        let output = match output {
+            Some(output) => output,
+            None => return Ok(unknown),
+        };
+        let script = output.type_.clone().unwrap_or_else(|| output.lock.clone());
+        let mut start_block = self.script_start_block(&script);
+        if let Some(block_hash) = &tx.tx_status.block_hash {
+            if let Some(number) = self.block_number(block_hash).await? {
+                start_block = start_block.min(number);
+            }
+        }
+        let search: SearchKey = match output.type_ {
+            Some(typescript) => CellQueryOptions::new(typescript.into(), PrimaryScriptType::Type),
+            None => CellQueryOptions::new(output.lock.into(), PrimaryScriptType::Lock),
+        }
+        .into();
+
+        let out_point: packed::OutPoint = out_point.clone().into();
+        let mut cursor = None;
+        loop {
+            let page = self
+                .get_cells(search.clone(), GET_CELLS_PAGE_SIZE, cursor, start_block)
+                .await?;
+            let count = page.objects.len();
+            let found = page
+                .objects
+                .into_iter()
+                .map(LiveCell::from)
+                .find(|cell| cell.out_point == out_point);
+            if let Some(cell) = found {
+                let data = with_data.then(|| CellData {
+                    hash: CellOutput::calc_data_hash(&cell.output_data).unpack(),
+                    content: JsonBytes::from_bytes(cell.output_data),
+                });
+                return Ok(CellWithStatus {
+                    cell: Some(CellInfo {
+                        output: cell.output.into(),
+                        data,
+                    }),
+                    status: "live".to_owned(),
+                });
+            }
+            if count < GET_CELLS_PAGE_SIZE as usize {
+                return Ok(unknown);
+            }
+            cursor = Some(page.last_cursor);
+        }
+    }
+}
+
+impl TransactionWithStatus {
+    fn into_response(self) -> Result<TransactionWithStatusResponse, ForcerelayCkbError> {
+        let response = json!({
+            "transaction": self.transaction,
+            "tx_status": {
+                "status": self.tx_status.status,
+                "block_hash": self.tx_status.block_hash,
+                "reason": null,
+            },
+        });
+        serde_json::from_value(response).map_err(|err| ForcerelayCkbError::UnexpectedResult {
+            method: "get_transaction".to_owned(),
+            message: err.to_string(),
+        })
+    }
+}
+
+impl CkbRpc for LightClientRpcClient {
+    fn get_block_by_number(&self, number: BlockNumber) -> Rpc<BlockView> {
+        // only the genesis block is kept by the light client
+        if number.value() == 0 {
+            self.rpc.call(Target::CKB, "get_genesis_block", [(); 0])
+        } else {
+            let method = format!("get_block_by_number({})", number.value());
+            async move { Err(ForcerelayCkbError::UnsupportedRpcMethod(method)) }.boxed()
+        }
+    }
+
+    fn get_block(&self, _hash: &H256) -> Rpc<BlockView> {
+        async {
+            Err(ForcerelayCkbError::UnsupportedRpcMethod(
+                "get_block".to_owned(),
+            ))
+        }
+        .boxed()
+    }
+
+    fn get_tip_header(&self) -> Rpc<HeaderView> {
+        self.rpc.call(Target::CKB, "get_tip_header", [(); 0])
+    }
+
+    fn get_transaction(&self, hash: &H256) -> Rpc<Option<TransactionWithStatusResponse>> {
+        let client = self.clone();
+        let hash = hash.clone();
+        async move {
+            let tx = client.transaction_with_status(&hash).await?;
+            tx.into_response().map(Some)
+        }
+        .boxed()
+    }
+
+    fn get_live_cell(&self, out_point: &OutPoint, with_data: bool) -> Rpc<CellWithStatus> {
+        let client = self.clone();
+        let out_point = out_point.clone();
+        async move { client.search_live_cell(&out_point, with_data).await }.boxed()
+    }
+
+    fn get_txs_by_hashes(
+        &self,
+        hashes: Vec<H256>,
+    ) -> Rpc<Vec<Option<TransactionWithStatusResponse>>> {
+        let requests = hashes
+            .iter()
+            .map(|hash| self.get_transaction(hash))
+            .collect::<Vec<_>>();
+        futures::future::try_join_all(requests).boxed()
+    }
+
+    fn fetch_live_cells(
+        &self,
+        search_key: SearchKey,
+        limit: u32,
+        cursor: Option<JsonBytes>,
+    ) -> Rpc<Pagination<Cell>> {
+        let client = self.clone();
+        let start_block = self.script_start_block(&search_key.script);
+        async move {
+            client
+                .get_cells(search_key, limit, cursor, start_block)
+                .await
+        }
+        .boxed()
+    }
+
+    fn send_transaction(
+        &self,
+        tx: &Transaction,
+        _outputs_validator: Option<OutputsValidator>,
+    ) -> Rpc<H256> {
+        // the light client sends transactions without the outputs validator
+        self.rpc.call(Target::CKB, "send_transaction", (tx,))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use ckb_jsonrpc_types::{HeaderView, OutPoint, Status, TransactionView};
+    use ckb_sdk::traits::{CellQueryOptions, PrimaryScriptType};
+    use ckb_types::core::{Capacity, HeaderBuilder, TransactionBuilder};
+    use ckb_types::packed::{CellOutput, Script};
+    use ckb_types::{bytes::Bytes, prelude::*, H256};
+    use serde_json::{json, Value};
+
+    use super::LightClientRpcClient;
+    use crate::rpc::{CkbRpc, StubServer};
+
+    #[tokio::test]
+    async fn test_light_client_rpc() {
+        let typescript = Script::new_builder().args(vec![1u8].pack()).build();
+        let output = CellOutput::new_builder()
+            .type_(Some(typescript.clone()).pack())
+            .build_exact_capacity(Capacity::bytes(4).unwrap())
+            .unwrap();
+        let tx = TransactionBuilder::default()
+            .output(output.clone())
+            .output_data(Bytes::from_static(b"data").pack())
+            .build();
+        let tx_json = serde_json::to_value(TransactionView::from(tx.clone())).unwrap();
+        let tx_hash: H256 = tx.hash().unpack();
+        let header_json = serde_json::to_value(HeaderView::from(
+            HeaderBuilder::default().number(5u64.pack()).build(),
+        ))
+        .unwrap();
+        let cell = json!({
+            "output": ckb_jsonrpc_types::CellOutput::from(output),
+            "output_data": "0x64617461",
+            "out_point": {"tx_hash": tx_hash, "index": "0x0"},
+            "block_number": "0x10",
+            "tx_index": "0x1",
+        });
+
+        let methods = Arc::new(Mutex::new(vec![]));
+        let scripts = Arc::new(Mutex::new(Value::Array(vec![])));
+        let server = {
+            let methods = methods.clone();
+            let scripts = scripts.clone();
+            StubServer::start(0, move |request| {
+                let method = request["method"].as_str().unwrap().to_owned();
+                methods.lock().unwrap().push(method.clone());
+                let result = match method.as_str() {
+                    "get_scripts" => scripts.lock().unwrap().clone(),
+                    "set_scripts" => {
+                        *scripts.lock().unwrap() = request["params"][0].clone();
+                        Value::Null
+                    }
+                    "get_cells" => json!({"objects": [cell], "last_cursor": "0x"}),
+                    "get_header" => header_json.clone(),
+                    "get_transaction" => json!({
+                        "transaction": null,
+                        "cycles": null,
+                        "tx_status": {"status": "unknown", "block_hash": null},
+                    }),
+                    "fetch_transaction" => json!({
+                        "status": "fetched",
+                        "data": {
+                            "transaction": tx_json,
+                            "cycles": null,
+                            "tx_status": {"status": "committed", "block_hash": H256::default()},
+                        },
+                    }),
+                    _ => unreachable!(),
+                };
+                json!({"jsonrpc": "2.0", "id": request["id"], "result": result})
+            })
+            .await
+        };
+        let lock_script = Script::new_builder().args(vec![2u8].pack()).build();
+        let rpc = LightClientRpcClient::new(&server.url, 16)
+            .unwrap()
+            .with_script_start_block(lock_script.clone(), 2);
+
+        let search = CellQueryOptions::new(typescript, PrimaryScriptType::Type).into();
+        let cells = rpc.fetch_live_cells(search, 10, None).await.unwrap();
+        assert_eq!(cells.objects.len(), 1);
+        let search = CellQueryOptions::new(lock_script, PrimaryScriptType::Lock).into();
+        rpc.fetch_live_cells(search, 10, None).await.unwrap();
+        assert_eq!(registered_blocks(&scripts), vec![16, 2]);
+
+        let out_point = OutPoint {
+            tx_hash: tx_hash.clone(),
+            index: 0.into(),
+        };
+        let cell = rpc.get_live_cell(&out_point, true).await.unwrap();
+        assert_eq!(cell.status, "live");
+        assert_eq!(
+            cell.cell.unwrap().data.unwrap().content.into_bytes(),
+            Bytes::from_static(b"data")
+        );
+
+        // the type script is synced from the block of the transaction then
+        assert_eq!(registered_blocks(&scripts), vec![5, 2]);
+
+        let response = rpc.get_transaction(&tx_hash).await.unwrap().unwrap();
+        assert_eq!(response.tx_status.status, Status::Committed);
+
+        // a script is registered again only if it's synced from an earlier block
+        rpc.get_live_cell(&out_point, false).await.unwrap();
+        let methods = methods.lock().unwrap();
+        assert_eq!(
+            methods
+                .iter()
+                .filter(|method| *method == "set_scripts")
+                .count(),
+            3
+        );
+        assert_eq!(
+            methods
+                .iter()
+                .filter(|method| *method == "get_scripts")
+                .count(),
+            3
+        );
+    }
+
+    fn registered_blocks(scripts: &Mutex<Value>) -> Vec<u64> {
+        scripts
+            .lock()
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|status| {
+                let number = status["block_number"].as_str().unwrap();
+                u64::from_str_radix(number.trim_start_matches("0x"), 16).unwrap()
+            })
+            .collect()
+    }
+}
@@ -2,13 +2,13 @@ use std::cell::{RefCell, RefMut};
 use std::sync::Arc;
 
 use ckb_jsonrpc_types::{
-    BlockNumber, BlockView, CellWithStatus, HeaderView, JsonBytes, OutPoint, OutputsValidator,
-    Transaction, TransactionWithStatusResponse,
+    BlockNumber, BlockView, CellData, CellInfo, CellWithStatus, HeaderView, JsonBytes, OutPoint,
+    OutputsValidator, Transaction, TransactionWithStatusResponse,
 };
 use ckb_sdk::constants::TYPE_ID_CODE_HASH;
 use ckb_sdk::rpc::ckb_indexer::{Cell, Pagination, SearchKey};
 use ckb_types::core::ScriptHashType;
-use ckb_types::packed::Script;
+use ckb_types::packed::{self, CellOutput, Script};
 use ckb_types::{prelude::*, H256};
 use test_utils::Context;
 
@@ -62,8 +62,21 @@ impl CkbRpc for MockRpcClient {
         unimplemented!()
     }
 
-    fn get_live_cell(&self, _out_point: &OutPoint, _with_data: bool) -> Rpc<CellWithStatus> {
-        unimplemented!()
+    fn get_live_cell(&self, out_point: &OutPoint, _with_data: bool) -> Rpc<CellWithStatus> {
+        let out_point: packed::OutPoint = out_point.clone().into();
+        let cell = self
+            .context
+            .borrow()
+            .get_cell(&out_point)
+            .map(|(output, data)| CellInfo {
+                output: output.into(),
+                data: Some(CellData {
+                    hash: CellOutput::calc_data_hash(&data).unpack(),
+                    content: JsonBytes::from_bytes(data),
+                }),
+            });
+        let status = if cell.is_some() { "live" } else { "unknown" }.to_owned();
+        Box::pin(async { Ok(CellWithStatus { cell, status }) })
     }
 
     fn send_transaction(
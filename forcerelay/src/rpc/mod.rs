@@ -1,10 +1,18 @@
+mod backend;
 mod ckb_rpc;
+mod light_client_rpc;
 mod rpc_trait;
 
-pub use ckb_rpc::RpcClient;
-pub use rpc_trait::CkbRpc;
+pub use backend::CkbRpcBackend;
+pub use ckb_rpc::{RetryConfig, RpcClient};
+pub use light_client_rpc::LightClientRpcClient;
+pub use rpc_trait::{CkbRpc, Rpc};
 
 #[cfg(test)]
 mod mock_rpc;
 #[cfg(test)]
 pub use mock_rpc::*;
+#[cfg(test)]
+mod stub_server;
+#[cfg(test)]
+pub(crate) use stub_server::StubServer;
@@ -0,0 +1,451 @@
+use ckb_jsonrpc_types::{OutputsValidator, Status, Transaction as CkbTransaction};
+use ckb_types::core::TransactionView;
+use ckb_types::{prelude::*, H256 as CkbH256};
+use eth2_types::EthSpec;
+use ethers::types::H256;
+use eyre::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use storage::prelude::{StorageReader, StorageWriter};
+
+use crate::rpc::CkbRpc;
+
+/// The times a submitted transaction is rebuilt before it's given up.
+pub const MAX_SUBMIT_REBUILDS: u32 = 3;
+
+/// The lifecycle of a verification transaction submitted to CKB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmitStatus {
+    Pending,
+    Committed,
+    Rejected,
+}
+
+/// The persistent record of a verification transaction, which is keyed by the hash of the
+/// Ethereum transaction it verifies, since rebuilding changes the CKB transaction hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmittedTransaction {
+    pub eth_tx_hash: H256,
+    pub ckb_tx_hash: CkbH256,
+    /// The IBC client id explicitly chosen by the caller, which is reused in rebuilding.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    pub status: SubmitStatus,
+    pub rebuilds: u32,
+    pub reason: Option<String>,
+    pub transaction: CkbTransaction,
+}
+
+/// What the tracker observed on CKB for a pending transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackResult {
+    Pending,
+    Committed,
+    /// The transaction is neither in the pool nor on chain, so it should be sent again.
+    Dropped,
+    Rejected(String),
+    /// One of the cell deps is not live anymore, e.g. the lightclient cell is updated by the
+    /// relayer, so the transaction has to be rebuilt.
+    CellDepConsumed,
+}
+
+/// The next step of a pending record after `track_pending` updates it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackStep {
+    /// Nothing is changed, the record is tracked again later.
+    Wait,
+    /// The status of the record is changed, so it should be saved.
+    Save,
+    /// The transaction can never be committed, so it should be rebuilt against the live cells.
+    Rebuild,
+}
+
+impl SubmittedTransaction {
+    pub fn new(eth_tx_hash: H256, tx: &TransactionView) -> Self {
+        Self {
+            eth_tx_hash,
+            ckb_tx_hash: tx.hash().unpack(),
+            client_id: None,
+            status: SubmitStatus::Pending,
+            rebuilds: 0,
+            reason: None,
+            transaction: tx.data().into(),
+        }
+    }
+
+    /// Replaces the tracked transaction with a rebuilt one.
+    pub fn rebuild_with(&mut self, tx: &TransactionView) {
+        self.ckb_tx_hash = tx.hash().unpack();
+        self.transaction = tx.data().into();
+        self.rebuilds += 1;
+    }
+
+    pub fn load<S: EthSpec>(
+        storage: &impl StorageReader<S>,
+        eth_tx_hash: &H256,
+    ) -> Result<Option<Self>> {
+        match storage.get_submitted_transaction(eth_tx_hash.as_bytes())? {
+            Some(raw) => Ok(Some(serde_json::from_slice(&raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads the records indexed as pending, the ones which are indexed but not pending anymore,
+    /// e.g. all of the records indexed by the storage migration, are dropped from the index.
+    pub fn load_pending<S: EthSpec>(
+        storage: &(impl StorageReader<S> + StorageWriter<S>),
+    ) -> Result<Vec<Self>> {
+        let mut pending = vec![];
+        for (key, raw) in storage.get_pending_submitted_transactions()? {
+            let record: Self = serde_json::from_slice(&raw)?;
+            if record.status == SubmitStatus::Pending {
+                pending.push(record);
+            } else {
+                storage.put_submitted_transaction(&key, &raw, false)?;
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Saves the record, which is tracked by `load_pending` as long as it's pending.
+    pub fn save<S: EthSpec>(&self, storage: &impl StorageWriter<S>) -> Result<()> {
+        let raw = serde_json::to_vec(self)?;
+        storage.put_submitted_transaction(
+            self.eth_tx_hash.as_bytes(),
+            &raw,
+            self.status == SubmitStatus::Pending,
+        )?;
+        Ok(())
+    }
+}
+
+pub async fn send_transaction<R: CkbRpc>(rpc: &R, tx: &CkbTransaction) -> Result<CkbH256> {
+    let hash = rpc
+        .send_transaction(tx, Some(OutputsValidator::Passthrough))
+        .await?;
+    Ok(hash)
+}
+
+/// Checks the status of the transaction on CKB, and whether all of its cell deps are still live.
+pub async fn track_transaction<R: CkbRpc>(
+    rpc: &R,
+    record: &SubmittedTransaction,
+) -> Result<TrackResult> {
+    let tx_status = rpc
+        .get_transaction(&record.ckb_tx_hash)
+        .await?
+        .map(|response| response.tx_status);
+    let (status, reason) = match tx_status {
+        Some(tx_status) => (tx_status.status, tx_status.reason),
+        None => (Status::Unknown, None),
+    };
+    if status == Status::Committed {
+        return Ok(TrackResult::Committed);
+    }
+    for celldep in &record.transaction.cell_deps {
+        let cell = rpc.get_live_cell(&celldep.out_point, false).await?;
+        if cell.status != "live" {
+            return Ok(TrackResult::CellDepConsumed);
+        }
+    }
+    let result = match status {
+        Status::Unknown => TrackResult::Dropped,
+        Status::Rejected => {
+            TrackResult::Rejected(reason.unwrap_or_else(|| "unknown reason".to_owned()))
+        }
+        _ => TrackResult::Pending,
+    };
+    Ok(result)
+}
+
+/// Checks if one of the inputs of the transaction is consumed, so it can never be committed.
+pub async fn is_input_consumed<R: CkbRpc>(rpc: &R, record: &SubmittedTransaction) -> Result<bool> {
+    for input in &record.transaction.inputs {
+        let cell = rpc.get_live_cell(&input.previous_output, false).await?;
+        if cell.status != "live" {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Tracks a pending record on CKB and updates its status. A dropped transaction is sent again,
+/// and it's rebuilt only if it's confirmed gone, i.e. one of its inputs is consumed, since a
+/// rebuilt transaction spending the same cells would conflict with the one still in the pool.
+pub async fn track_pending<R: CkbRpc>(
+    rpc: &R,
+    record: &mut SubmittedTransaction,
+) -> Result<TrackStep> {
+    let step = match track_transaction(rpc, record).await? {
+        TrackResult::Pending => TrackStep::Wait,
+        TrackResult::Committed => {
+            record.status = SubmitStatus::Committed;
+            TrackStep::Save
+        }
+        TrackResult::Rejected(reason) => {
+            record.status = SubmitStatus::Rejected;
+            record.reason = Some(reason);
+            TrackStep::Save
+        }
+        TrackResult::CellDepConsumed => rebuild_or_give_up(record),
+        TrackResult::Dropped => match send_transaction(rpc, &record.transaction).await {
+            Ok(_) => TrackStep::Wait,
+            Err(err) => {
+                if is_input_consumed(rpc, record).await? {
+                    rebuild_or_give_up(record)
+                } else {
+                    warn!(
+                        "resend ckb transaction {:#x} error: {err}, retry later",
+                        record.ckb_tx_hash
+                    );
+                    TrackStep::Wait
+                }
+            }
+        },
+    };
+    Ok(step)
+}
+
+fn rebuild_or_give_up(record: &mut SubmittedTransaction) -> TrackStep {
+    if record.rebuilds >= MAX_SUBMIT_REBUILDS {
+        record.status = SubmitStatus::Rejected;
+        record.reason = Some(format!("give up after {MAX_SUBMIT_REBUILDS} rebuilds"));
+        TrackStep::Save
+    } else {
+        TrackStep::Rebuild
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ckb_jsonrpc_types::{
+        BlockNumber, BlockView, CellWithStatus, HeaderView, JsonBytes, OutPoint, OutputsValidator,
+        Status, Transaction, TransactionWithStatusResponse,
+    };
+    use ckb_sdk::rpc::ckb_indexer::{Cell, Pagination, SearchKey};
+    use ckb_types::core::TransactionBuilder;
+    use ckb_types::packed::{CellDep, CellInput, OutPoint as PackedOutPoint};
+    use ckb_types::{prelude::*, H256 as CkbH256};
+    use eth2_types::MainnetEthSpec;
+    use ethers::types::H256;
+    use serde_json::json;
+    use storage::prelude::{StorageReader as _, StorageWriter as _};
+    use storage::Storage;
+
+    use super::{
+        track_pending, SubmitStatus, SubmittedTransaction, TrackStep, MAX_SUBMIT_REBUILDS,
+    };
+    use crate::errors::ForcerelayCkbError;
+    use crate::rpc::{CkbRpc, Rpc};
+
+    // a ckb rpc which reports the same status of any transaction
+    struct StubRpc {
+        tx_status: Option<Status>,
+        dead_cells: Vec<OutPoint>,
+        send_ok: bool,
+    }
+
+    impl CkbRpc for StubRpc {
+        fn get_block_by_number(&self, _number: BlockNumber) -> Rpc<BlockView> {
+            unimplemented!()
+        }
+
+        fn get_block(&self, _hash: &CkbH256) -> Rpc<BlockView> {
+            unimplemented!()
+        }
+
+        fn get_tip_header(&self) -> Rpc<HeaderView> {
+            unimplemented!()
+        }
+
+        fn get_transaction(&self, _hash: &CkbH256) -> Rpc<Option<TransactionWithStatusResponse>> {
+            let response = self.tx_status.clone().map(|status| {
+                let reason = (status == Status::Rejected).then(|| "rejected".to_owned());
+                serde_json::from_value(json!({
+                    "transaction": null,
+                    "cycles": null,
+                    "tx_status": { "status": status, "block_hash": null, "reason": reason },
+                }))
+                .expect("tx status")
+            });
+            Box::pin(async { Ok(response) })
+        }
+
+        fn get_live_cell(&self, out_point: &OutPoint, _with_data: bool) -> Rpc<CellWithStatus> {
+            let status = if self.dead_cells.contains(out_point) {
+                "dead"
+            } else {
+                "live"
+            };
+            let cell = CellWithStatus {
+                cell: None,
+                status: status.to_owned(),
+            };
+            Box::pin(async { Ok(cell) })
+        }
+
+        fn get_txs_by_hashes(
+            &self,
+            _hashes: Vec<CkbH256>,
+        ) -> Rpc<Vec<Option<TransactionWithStatusResponse>>> {
+            unimplemented!()
+        }
+
+        fn fetch_live_cells(
+            &self,
+            _search_key: SearchKey,
+            _limit: u32,
+            _cursor: Option<JsonBytes>,
+        ) -> Rpc<Pagination<Cell>> {
+            unimplemented!()
+        }
+
+        fn send_transaction(
+            &self,
+            _tx: &Transaction,
+            _outputs_validator: Option<OutputsValidator>,
+        ) -> Rpc<CkbH256> {
+            let result = if self.send_ok {
+                Ok(CkbH256::default())
+            } else {
+                Err(ForcerelayCkbError::RequestFailed {
+                    url: "stub".to_owned(),
+                    message: "timeout".to_owned(),
+                })
+            };
+            Box::pin(async { result })
+        }
+    }
+
+    fn input() -> PackedOutPoint {
+        PackedOutPoint::new([1u8; 32].pack(), 0)
+    }
+
+    fn celldep() -> PackedOutPoint {
+        PackedOutPoint::new([2u8; 32].pack(), 0)
+    }
+
+    fn pending_record() -> SubmittedTransaction {
+        let tx = TransactionBuilder::default()
+            .input(CellInput::new(input(), 0))
+            .cell_dep(CellDep::new_builder().out_point(celldep()).build())
+            .build();
+        SubmittedTransaction::new(H256::repeat_byte(1), &tx)
+    }
+
+    async fn track(
+        tx_status: Option<Status>,
+        dead_cells: Vec<PackedOutPoint>,
+        send_ok: bool,
+        record: &mut SubmittedTransaction,
+    ) -> TrackStep {
+        let rpc = StubRpc {
+            tx_status,
+            dead_cells: dead_cells.into_iter().map(Into::into).collect(),
+            send_ok,
+        };
+        track_pending(&rpc, record).await.expect("track")
+    }
+
+    #[tokio::test]
+    async fn test_track_pending_status() {
+        let mut record = pending_record();
+        let step = track(Some(Status::Pending), vec![], true, &mut record).await;
+        assert_eq!(step, TrackStep::Wait);
+        assert_eq!(record.status, SubmitStatus::Pending);
+
+        let step = track(Some(Status::Committed), vec![], true, &mut record).await;
+        assert_eq!(step, TrackStep::Save);
+        assert_eq!(record.status, SubmitStatus::Committed);
+
+        let mut record = pending_record();
+        let step = track(Some(Status::Rejected), vec![], true, &mut record).await;
+        assert_eq!(step, TrackStep::Save);
+        assert_eq!(record.status, SubmitStatus::Rejected);
+        assert_eq!(record.reason.as_deref(), Some("rejected"));
+    }
+
+    #[tokio::test]
+    async fn test_track_pending_dropped() {
+        // the dropped transaction is sent again
+        let mut record = pending_record();
+        let step = track(None, vec![], true, &mut record).await;
+        assert_eq!(step, TrackStep::Wait);
+
+        // a transient error of resending doesn't rebuild the transaction
+        let step = track(None, vec![], false, &mut record).await;
+        assert_eq!(step, TrackStep::Wait);
+        assert_eq!(record.status, SubmitStatus::Pending);
+
+        // the transaction is rebuilt once its input is consumed
+        let step = track(None, vec![input()], false, &mut record).await;
+        assert_eq!(step, TrackStep::Rebuild);
+        assert_eq!(record.status, SubmitStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_track_pending_rebuild() {
+        let mut record = pending_record();
+        let step = track(Some(Status::Pending), vec![celldep()], true, &mut record).await;
+        assert_eq!(step, TrackStep::Rebuild);
+
+        // the rebuilt transaction replaces the old one
+        let old_hash = record.ckb_tx_hash.clone();
+        let tx = TransactionBuilder::default()
+            .input(CellInput::new(input(), 1))
+            .build();
+        record.rebuild_with(&tx);
+        assert_ne!(record.ckb_tx_hash, old_hash);
+        assert_eq!(record.rebuilds, 1);
+
+        // give up after too many rebuilds
+        record.rebuilds = MAX_SUBMIT_REBUILDS;
+        let step = track(Some(Status::Pending), vec![celldep()], true, &mut record).await;
+        assert_eq!(step, TrackStep::Save);
+        assert_eq!(record.status, SubmitStatus::Rejected);
+    }
+
+    #[test]
+    fn test_submitted_transaction_storage() {
+        let storage = Storage::<MainnetEthSpec>::new_in_memory().unwrap();
+        let tx = TransactionBuilder::default().build();
+        let eth_tx_hash = H256::repeat_byte(1);
+
+        let mut record = SubmittedTransaction::new(eth_tx_hash, &tx);
+        record.save(&storage).unwrap();
+        let pending = SubmittedTransaction::load_pending(&storage).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].ckb_tx_hash, record.ckb_tx_hash);
+
+        record.status = SubmitStatus::Committed;
+        record.save(&storage).unwrap();
+        let loaded = SubmittedTransaction::load(&storage, &eth_tx_hash)
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.status, SubmitStatus::Committed);
+        assert!(SubmittedTransaction::load_pending(&storage)
+            .unwrap()
+            .is_empty());
+        assert!(SubmittedTransaction::load(&storage, &H256::zero())
+            .unwrap()
+            .is_none());
+
+        // a record indexed by the migration is dropped from the index once it's loaded
+        let raw = serde_json::to_vec(&loaded).unwrap();
+        storage
+            .put_submitted_transaction(eth_tx_hash.as_bytes(), &raw, true)
+            .unwrap();
+        assert_eq!(
+            storage.get_pending_submitted_transactions().unwrap().len(),
+            1
+        );
+        assert!(SubmittedTransaction::load_pending(&storage)
+            .unwrap()
+            .is_empty());
+        assert!(storage
+            .get_pending_submitted_transactions()
+            .unwrap()
+            .is_empty());
+    }
+}
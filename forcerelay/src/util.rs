@@ -1,22 +1,58 @@
-use ckb_sdk::rpc::ckb_indexer::SearchKey;
+use ckb_sdk::rpc::ckb_indexer::{Cell, SearchKey};
 use ckb_sdk::traits::{CellQueryOptions, LiveCell, PrimaryScriptType};
+use ckb_types::bytes::Bytes;
 use ckb_types::core::{DepType, TransactionView};
 use ckb_types::packed::{BytesOpt, CellDep, Script, WitnessArgs};
 use ckb_types::prelude::Pack as _;
 use consensus::types::Header;
-use eth2_types::{BeaconBlockHeader, Hash256, MainnetEthSpec};
+use eth2_types::{BeaconBlockHeader, EthSpec, Hash256, MainnetEthSpec};
 use eth_light_client_in_ckb_prover::{CachedBeaconBlock, Receipts};
 use eth_light_client_in_ckb_verification::types::{core, packed, prelude::*};
 use ethers::types::Transaction;
 use eyre::Result;
+use log::warn;
+use storage::prelude::StorageAsMMRStore;
 
+use crate::errors::BeaconBlockMismatch;
 use crate::rpc::CkbRpc;
 
-pub async fn search_cell<R: CkbRpc>(rpc: &R, typescript: &Script) -> Result<Option<LiveCell>> {
+const SEARCH_CELLS_PAGE_SIZE: u32 = 64;
+
+/// Fetches all live cells of the search key, page by page.
+pub async fn fetch_all_live_cells<R: CkbRpc>(rpc: &R, search: SearchKey) -> Result<Vec<Cell>> {
+    let mut cells = vec![];
+    let mut cursor = None;
+    loop {
+        let page = rpc
+            .fetch_live_cells(search.clone(), SEARCH_CELLS_PAGE_SIZE, cursor)
+            .await?;
+        let count = page.objects.len();
+        cells.extend(page.objects);
+        if count < SEARCH_CELLS_PAGE_SIZE as usize {
+            break;
+        }
+        cursor = Some(page.last_cursor);
+    }
+    Ok(cells)
+}
+
+pub async fn search_cells<R: CkbRpc>(rpc: &R, typescript: &Script) -> Result<Vec<LiveCell>> {
     let search: SearchKey =
         CellQueryOptions::new(typescript.clone(), PrimaryScriptType::Type).into();
-    let result = rpc.fetch_live_cells(search, 1, None).await?;
-    Ok(result.objects.first().cloned().map(Into::into))
+    let cells = fetch_all_live_cells(rpc, search).await?;
+    Ok(cells.into_iter().map(Into::into).collect())
+}
+
+/// Searches the only live cell of the type script, e.g. a type id cell.
+pub async fn search_cell<R: CkbRpc>(rpc: &R, typescript: &Script) -> Result<Option<LiveCell>> {
+    let cells = search_cells(rpc, typescript).await?;
+    if cells.len() > 1 {
+        warn!(
+            "found {} live cells of type script {typescript}, the first one is used",
+            cells.len()
+        );
+    }
+    Ok(cells.into_iter().next())
 }
 
 pub async fn search_cell_as_celldep<R: CkbRpc>(
@@ -47,6 +83,41 @@ pub fn header_helios_to_lighthouse(header: &Header) -> BeaconBlockHeader {
     }
 }
 
+pub fn beacon_block_header_digest(
+    block: &CachedBeaconBlock<MainnetEthSpec>,
+) -> packed::HeaderDigest {
+    let header: core::Header =
+        packed::Header::from_ssz_header(&block.original().block_header()).unpack();
+    header.calc_cache().digest()
+}
+
+/// Compares the header digest of the beacon block with the one stored at the MMR position of its
+/// slot, a mismatch is returned as `BeaconBlockMismatch`. Returns `false` if the slot is out of
+/// the stored headers, so the block cannot be checked yet.
+pub fn check_beacon_block<S: EthSpec>(
+    storage: &impl StorageAsMMRStore<S>,
+    block: &CachedBeaconBlock<MainnetEthSpec>,
+) -> Result<bool> {
+    let slot = block.slot().as_u64();
+    let actual = beacon_block_header_digest(block);
+    match storage.is_header_digest_matched(slot, &actual)? {
+        Some(true) => Ok(true),
+        Some(false) => {
+            let expected = storage
+                .get_beacon_header_digest_by_slot(slot)?
+                .map(|digest| hex::encode(digest.as_slice()))
+                .unwrap_or_default();
+            Err(BeaconBlockMismatch {
+                slot,
+                expected,
+                actual: hex::encode(actual.as_slice()),
+            }
+            .into())
+        }
+        None => Ok(false),
+    }
+}
+
 pub fn find_receipt_index(transaction_hash: Hash256, receipts: &Receipts) -> Option<u64> {
     let mut index = None;
     receipts
@@ -109,22 +180,43 @@ pub fn assemble_partial_verification_transaction(
     packed_payload: &packed::TransactionPayload,
     celldeps: &[CellDep],
 ) -> Result<TransactionView> {
-    let witness = {
-        let input_type_args = BytesOpt::new_builder()
-            .set(Some(packed_proof.as_slice().pack()))
-            .build();
-        let output_type_args = BytesOpt::new_builder()
-            .set(Some(packed_payload.as_slice().pack()))
-            .build();
-        let witness_args = WitnessArgs::new_builder()
-            .input_type(input_type_args)
-            .output_type(output_type_args)
-            .build();
-        witness_args.as_bytes()
-    };
     let tx = TransactionView::new_advanced_builder()
         .cell_deps(celldeps.to_owned())
-        .witness(witness.pack())
+        .witness(build_verification_witness(packed_proof, packed_payload).pack())
+        .build();
+    Ok(tx)
+}
+
+pub fn assemble_batch_verification_transaction(
+    proofs: &[(packed::TransactionProof, packed::TransactionPayload)],
+    celldeps: &[CellDep],
+) -> Result<TransactionView> {
+    let witnesses = proofs
+        .iter()
+        .map(|(packed_proof, packed_payload)| {
+            build_verification_witness(packed_proof, packed_payload).pack()
+        })
+        .collect::<Vec<_>>();
+    let tx = TransactionView::new_advanced_builder()
+        .cell_deps(celldeps.to_owned())
+        .witnesses(witnesses)
         .build();
     Ok(tx)
 }
+
+fn build_verification_witness(
+    packed_proof: &packed::TransactionProof,
+    packed_payload: &packed::TransactionPayload,
+) -> Bytes {
+    let input_type_args = BytesOpt::new_builder()
+        .set(Some(packed_proof.as_slice().pack()))
+        .build();
+    let output_type_args = BytesOpt::new_builder()
+        .set(Some(packed_payload.as_slice().pack()))
+        .build();
+    let witness_args = WitnessArgs::new_builder()
+        .input_type(input_type_args)
+        .output_type(output_type_args)
+        .build();
+    witness_args.as_bytes()
+}
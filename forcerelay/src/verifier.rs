@@ -0,0 +1,114 @@
+use ckb_jsonrpc_types::{Transaction as JsonTransaction, TransactionView as JsonTransactionView};
+use ckb_types::packed::{Transaction, WitnessArgs};
+use ckb_types::prelude::{Entity as _, Reader as _};
+use eth_light_client_in_ckb_verification::error::TxVerificationError;
+use eth_light_client_in_ckb_verification::types::{core, packed, prelude::*};
+
+use crate::errors::{ProofPart, ProofVerificationError};
+
+type Result<T> = std::result::Result<T, ProofVerificationError>;
+
+/// The verification result of one witness in a CKB transaction.
+#[derive(Debug)]
+pub struct WitnessVerification {
+    pub index: usize,
+    pub result: Result<()>,
+}
+
+/// Decodes a `core::Client` from the data of an on-chain lightclient cell.
+pub fn parse_client(data: &[u8]) -> Result<core::Client> {
+    packed::ClientReader::verify(data, false)
+        .map_err(|e| ProofVerificationError::Malformed(format!("client: {e}")))?;
+    Ok(packed::Client::new_unchecked(data.to_vec().into()).unpack())
+}
+
+/// Runs the same checks as the lightclient contract does, without any node or CKB connection.
+///
+/// The header is checked against the MMR of the client at first, and then the payload is
+/// checked against the header.
+pub fn verify_packed_proof(
+    client: &core::Client,
+    proof: packed::TransactionProofReader,
+    payload: packed::TransactionPayloadReader,
+) -> Result<()> {
+    client
+        .verify_packed_transaction_proof(proof)
+        .map_err(verification_failed)?;
+    proof
+        .to_entity()
+        .unpack()
+        .verify_packed_payload(payload)
+        .map_err(verification_failed)
+}
+
+/// Verifies the raw bytes of a `TransactionProof` and a `TransactionPayload`.
+pub fn verify_proof(client: &core::Client, proof: &[u8], payload: &[u8]) -> Result<()> {
+    let proof = packed::TransactionProofReader::from_slice(proof)
+        .map_err(|e| ProofVerificationError::Malformed(format!("transaction proof: {e}")))?;
+    let payload = packed::TransactionPayloadReader::from_slice(payload)
+        .map_err(|e| ProofVerificationError::Malformed(format!("transaction payload: {e}")))?;
+    verify_packed_proof(client, proof, payload)
+}
+
+/// Verifies every verification witness in a CKB transaction, whose `input_type` is the proof and
+/// `output_type` is the payload. Witnesses of other usages, e.g. signatures, are skipped.
+pub fn verify_transaction(
+    client: &core::Client,
+    tx: &Transaction,
+) -> Result<Vec<WitnessVerification>> {
+    let mut verifications = vec![];
+    for (index, witness) in tx.witnesses().into_iter().enumerate() {
+        let witness_args = match WitnessArgs::from_slice(&witness.raw_data()) {
+            Ok(witness_args) => witness_args,
+            Err(_) => continue,
+        };
+        let (proof, payload) = match (
+            witness_args.input_type().to_opt(),
+            witness_args.output_type().to_opt(),
+        ) {
+            (Some(proof), Some(payload)) => (proof.raw_data(), payload.raw_data()),
+            _ => continue,
+        };
+        verifications.push(WitnessVerification {
+            index,
+            result: verify_proof(client, &proof, &payload),
+        });
+    }
+    if verifications.is_empty() {
+        return Err(ProofVerificationError::Malformed(
+            "transaction: no verification witness".to_owned(),
+        ));
+    }
+    Ok(verifications)
+}
+
+/// Verifies a CKB transaction in JSON, which is either a transaction or a transaction view.
+pub fn verify_transaction_json(
+    client: &core::Client,
+    json: &str,
+) -> Result<Vec<WitnessVerification>> {
+    let tx: JsonTransaction = match serde_json::from_str(json) {
+        Ok(tx) => tx,
+        Err(_) => {
+            serde_json::from_str::<JsonTransactionView>(json)
+                .map_err(|e| ProofVerificationError::Malformed(format!("transaction json: {e}")))?
+                .inner
+        }
+    };
+    verify_transaction(client, &tx.into())
+}
+
+fn verification_failed(error: TxVerificationError) -> ProofVerificationError {
+    let part = match error {
+        TxVerificationError::HeaderMmrProof => ProofPart::HeaderMmrProof,
+        TxVerificationError::TransactionSszProof => ProofPart::TransactionSszProof,
+        TxVerificationError::ReceiptMptProof => ProofPart::ReceiptMptProof,
+        TxVerificationError::ReceiptsRootSszProof => ProofPart::ReceiptsRootProof,
+        _ => ProofPart::Unknown,
+    };
+    ProofVerificationError::Failed {
+        part,
+        name: format!("{error:?}"),
+        code: error as i8,
+    }
+}
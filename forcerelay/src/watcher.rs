@@ -0,0 +1,261 @@
+use ckb_jsonrpc_types::Transaction as CkbTransaction;
+use ckb_types::core::TransactionView;
+use eth2_types::EthSpec;
+use ethers::types::{Address, Log, TransactionReceipt, H256};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use storage::{
+    prelude::{StorageReader, StorageWriter},
+    Storage,
+};
+
+/// Selects the Ethereum logs whose verification transactions are generated in watch mode.
+///
+/// An empty list of addresses or topics matches any, a log matches when both its address and
+/// its first topic, i.e. the event signature, match.
+#[derive(Debug, Clone, Default)]
+pub struct LogWatcher {
+    addresses: Vec<Address>,
+    topics: Vec<H256>,
+}
+
+/// A verification transaction generated in watch mode, which waits in the queue until a
+/// consumer drains it.
+///
+/// If the transaction cannot be assembled for the matched logs, a dead-letter entry without the
+/// transaction but with the error is queued instead, so the slot is still marked as watched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchedTransaction {
+    /// The position in the queue, assigned when the transaction is pushed.
+    pub index: u64,
+    pub eth_transaction_hash: H256,
+    pub block_number: u64,
+    /// The block-wide indexes of the matched logs of the Ethereum transaction.
+    pub log_indexes: Vec<u64>,
+    pub transaction: Option<CkbTransaction>,
+    /// Why the transaction failed to assemble, only set for dead-letter entries.
+    pub error: Option<String>,
+}
+
+impl LogWatcher {
+    pub fn new(addresses: Vec<Address>, topics: Vec<H256>) -> Self {
+        Self { addresses, topics }
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        let address_matched = self.addresses.is_empty() || self.addresses.contains(&log.address);
+        let topic_matched = self.topics.is_empty()
+            || log
+                .topics
+                .first()
+                .map(|topic| self.topics.contains(topic))
+                .unwrap_or(false);
+        address_matched && topic_matched
+    }
+
+    /// Returns the Ethereum transactions which emit matched logs, with the indexes of those
+    /// logs, in the order of the receipts.
+    ///
+    /// The block-wide log indexes are counted from the receipts, which are verified against the
+    /// receipts root, instead of trusting the `log_index` reported by the RPC.
+    pub fn matched_transactions(&self, receipts: &[TransactionReceipt]) -> Vec<(H256, Vec<u64>)> {
+        let mut first_log_index = 0;
+        receipts
+            .iter()
+            .filter_map(|receipt| {
+                let log_indexes = receipt
+                    .logs
+                    .iter()
+                    .zip(first_log_index..)
+                    .filter(|(log, _)| self.matches(log))
+                    .map(|(_, index)| index)
+                    .collect::<Vec<_>>();
+                first_log_index += receipt.logs.len() as u64;
+                if log_indexes.is_empty() {
+                    None
+                } else {
+                    Some((receipt.transaction_hash, log_indexes))
+                }
+            })
+            .collect()
+    }
+}
+
+impl WatchedTransaction {
+    pub fn new(
+        eth_transaction_hash: H256,
+        block_number: u64,
+        log_indexes: Vec<u64>,
+        tx: &TransactionView,
+    ) -> Self {
+        Self {
+            index: 0,
+            eth_transaction_hash,
+            block_number,
+            log_indexes,
+            transaction: Some(tx.data().into()),
+            error: None,
+        }
+    }
+
+    /// Returns a dead-letter entry of the Ethereum transaction whose verification transaction
+    /// failed to assemble.
+    pub fn failed(
+        eth_transaction_hash: H256,
+        block_number: u64,
+        log_indexes: Vec<u64>,
+        error: String,
+    ) -> Self {
+        Self {
+            index: 0,
+            eth_transaction_hash,
+            block_number,
+            log_indexes,
+            transaction: None,
+            error: Some(error),
+        }
+    }
+
+    /// Pushes the transactions generated from the receipts of `slot` to the tail of the queue,
+    /// assigns their indexes and marks the slot as watched, all in one atomic write.
+    pub fn push_slot<S: EthSpec>(
+        storage: &Storage<S>,
+        slot: u64,
+        records: &mut [Self],
+    ) -> Result<()> {
+        let mut next_index = storage.get_next_watched_transaction_index()?;
+        let batch = storage.batch();
+        for record in records {
+            record.index = next_index;
+            let raw = serde_json::to_vec(record)?;
+            batch.put_watched_transaction(next_index, &raw)?;
+            next_index += 1;
+        }
+        batch.put_last_watched_slot(slot)?;
+        batch.commit()?;
+        Ok(())
+    }
+
+    pub fn last_watched_slot<S: EthSpec>(storage: &impl StorageReader<S>) -> Result<Option<u64>> {
+        storage.get_last_watched_slot().map_err(Into::into)
+    }
+
+    /// Returns at most `limit` transactions from the head of the queue without removing them.
+    pub fn peek<S: EthSpec>(storage: &impl StorageReader<S>, limit: usize) -> Result<Vec<Self>> {
+        storage
+            .get_watched_transactions(limit)?
+            .into_iter()
+            .map(|(_, raw)| serde_json::from_slice(&raw).map_err(Into::into))
+            .collect()
+    }
+
+    /// Removes and returns at most `limit` transactions from the head of the queue.
+    pub fn drain<S: EthSpec>(
+        storage: &(impl StorageReader<S> + StorageWriter<S>),
+        limit: usize,
+    ) -> Result<Vec<Self>> {
+        let records = Self::peek(storage, limit)?;
+        for record in &records {
+            storage.delete_watched_transaction(record.index)?;
+        }
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ckb_types::core::TransactionBuilder;
+    use eth2_types::MainnetEthSpec;
+    use ethers::types::{Address, Log, TransactionReceipt, H256, U256};
+    use storage::Storage;
+
+    use super::{LogWatcher, WatchedTransaction};
+
+    #[test]
+    fn test_log_watcher() {
+        let address = Address::repeat_byte(1);
+        let topic = H256::repeat_byte(2);
+        // the indexes reported by the rpc are ignored
+        let log = |address: Address, topic: H256, index: u64| Log {
+            address,
+            topics: vec![topic],
+            log_index: Some(U256::from(index)),
+            ..Default::default()
+        };
+        let receipts = vec![
+            TransactionReceipt {
+                transaction_hash: H256::repeat_byte(3),
+                logs: vec![log(address, topic, 7), log(address, H256::zero(), 7)],
+                ..Default::default()
+            },
+            TransactionReceipt {
+                transaction_hash: H256::repeat_byte(4),
+                logs: vec![log(Address::zero(), topic, 0)],
+                ..Default::default()
+            },
+        ];
+
+        let watcher = LogWatcher::new(vec![address], vec![topic]);
+        assert_eq!(
+            watcher.matched_transactions(&receipts),
+            vec![(H256::repeat_byte(3), vec![0])]
+        );
+        let watcher = LogWatcher::new(vec![], vec![topic]);
+        assert_eq!(
+            watcher.matched_transactions(&receipts),
+            vec![
+                (H256::repeat_byte(3), vec![0]),
+                (H256::repeat_byte(4), vec![2])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watched_transaction_queue() {
+        let storage = Storage::<MainnetEthSpec>::new_in_memory().unwrap();
+        let tx = TransactionBuilder::default().build();
+
+        let mut records = (0..300u64)
+            .map(|number| WatchedTransaction::new(H256::repeat_byte(1), number, vec![0], &tx))
+            .collect::<Vec<_>>();
+        WatchedTransaction::push_slot(&storage, 1, &mut records).unwrap();
+
+        let drained = WatchedTransaction::drain(&storage, 257).unwrap();
+        assert_eq!(drained.len(), 257);
+        assert!(drained
+            .iter()
+            .enumerate()
+            .all(|(i, record)| record.index == i as u64 && record.block_number == i as u64));
+
+        let mut records = vec![WatchedTransaction::failed(
+            H256::zero(),
+            300,
+            vec![],
+            "dry run failed".to_owned(),
+        )];
+        WatchedTransaction::push_slot(&storage, 2, &mut records).unwrap();
+        assert_eq!(records[0].index, 300);
+        assert_eq!(
+            WatchedTransaction::last_watched_slot(&storage).unwrap(),
+            Some(2)
+        );
+
+        let remaining = WatchedTransaction::peek(&storage, usize::MAX).unwrap();
+        assert_eq!(remaining.len(), 44);
+        assert_eq!(remaining[0].index, 257);
+        assert_eq!(remaining[43].block_number, 300);
+        assert!(remaining[43].transaction.is_none());
+        assert_eq!(remaining[43].error.as_deref(), Some("dry run failed"));
+
+        // the indexes are not reused after the queue is drained
+        let drained = WatchedTransaction::drain(&storage, usize::MAX).unwrap();
+        assert_eq!(drained.len(), 44);
+        let mut records = vec![WatchedTransaction::new(H256::zero(), 301, vec![], &tx)];
+        WatchedTransaction::push_slot(&storage, 3, &mut records).unwrap();
+        assert_eq!(records[0].index, 301);
+        assert_eq!(
+            WatchedTransaction::peek(&storage, usize::MAX).unwrap()[0].index,
+            301
+        );
+    }
+}
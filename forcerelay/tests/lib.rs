@@ -9,7 +9,7 @@ use ckb_types::{core::ScriptHashType, prelude::*};
 use jsonrpc_core::{self, Output, Response};
 
 use client::{Client, ClientBuilder};
-use config::networks;
+use config::{networks, StorageBackend};
 use eyre::Result;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
 
@@ -282,6 +282,7 @@ fn get_client() -> (Client, Sender<()>) {
         .ckb_rpc("http://127.0.0.1:8114")
         .checkpoint(checkpoint)
         .storage_path("ckb_mmr_storage".into())
+        .storage_backend(StorageBackend::Memory)
         .lightclient_contract_typeargs(
             "0xf49ce32397c6741998b04d7548c5ed372007424daf67ee5bfadaefec3c865781",
         )
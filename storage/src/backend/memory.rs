@@ -0,0 +1,137 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+    sync::RwLock,
+};
+
+use super::{IterMode, KeyValueBackend, WriteOp};
+use crate::{
+    error::{Error, Result},
+    schemas::columns::{self, Column},
+};
+
+type Columns = HashMap<Option<Column>, BTreeMap<Vec<u8>, Vec<u8>>>;
+
+/// The backend which keeps all of the data in memory, and drops them with itself. It's used by
+/// tests and embeddings which don't need the data to survive a restart.
+///
+/// Like RocksDB, it only has the default column and the column families in `schemas::columns`.
+pub struct MemoryBackend {
+    columns: RwLock<Columns>,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        let columns = std::iter::once(None)
+            .chain(columns::ALL.into_iter().map(Some))
+            .map(|col| (col, BTreeMap::new()))
+            .collect();
+        Self {
+            columns: RwLock::new(columns),
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn column(columns: &Columns, col: Option<Column>) -> Result<&BTreeMap<Vec<u8>, Vec<u8>>> {
+    columns
+        .get(&col)
+        .ok_or_else(|| Error::storage(format!("column {col:?} not found")))
+}
+
+fn column_mut(
+    columns: &mut Columns,
+    col: Option<Column>,
+) -> Result<&mut BTreeMap<Vec<u8>, Vec<u8>>> {
+    columns
+        .get_mut(&col)
+        .ok_or_else(|| Error::storage(format!("column {col:?} not found")))
+}
+
+impl KeyValueBackend for MemoryBackend {
+    fn get(&self, col: Option<Column>, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let columns = self.columns.read().map_err(Error::storage)?;
+        Ok(column(&columns, col)?.get(key).cloned())
+    }
+
+    fn put(&self, col: Option<Column>, key: &[u8], value: &[u8]) -> Result<()> {
+        self.write(vec![WriteOp::Put {
+            col,
+            key: key.to_vec(),
+            value: value.to_vec(),
+        }])
+    }
+
+    fn delete(&self, col: Option<Column>, key: &[u8]) -> Result<()> {
+        self.write(vec![WriteOp::Delete {
+            col,
+            key: key.to_vec(),
+        }])
+    }
+
+    fn iterate(
+        &self,
+        col: Column,
+        mode: IterMode,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let columns = self.columns.read().map_err(Error::storage)?;
+        let column = column(&columns, Some(col))?;
+        let clone = |(key, value): (&Vec<u8>, &Vec<u8>)| (key.clone(), value.clone());
+        let kvs = match mode {
+            IterMode::Start => column.iter().take(limit).map(clone).collect(),
+            IterMode::End => column.iter().rev().take(limit).map(clone).collect(),
+            IterMode::From(key) => column
+                .range::<[u8], _>((Bound::Included(key), Bound::Unbounded))
+                .take(limit)
+                .map(clone)
+                .collect(),
+        };
+        Ok(kvs)
+    }
+
+    fn delete_range(&self, col: Column, from: Option<&[u8]>, to: Option<&[u8]>) -> Result<usize> {
+        let mut columns = self.columns.write().map_err(Error::storage)?;
+        let column = column_mut(&mut columns, Some(col))?;
+        let start = from.map(Bound::Included).unwrap_or(Bound::Unbounded);
+        let end = to.map(Bound::Excluded).unwrap_or(Bound::Unbounded);
+        if let (Some(from), Some(to)) = (from, to) {
+            if from >= to {
+                return Ok(0);
+            }
+        }
+        let keys = column
+            .range::<[u8], _>((start, end))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in &keys {
+            column.remove(key);
+        }
+        Ok(keys.len())
+    }
+
+    fn write(&self, ops: Vec<WriteOp>) -> Result<()> {
+        let mut columns = self.columns.write().map_err(Error::storage)?;
+        // check all of the columns first, so a batch is either applied entirely or not at all
+        for op in &ops {
+            let (WriteOp::Put { col, .. } | WriteOp::Delete { col, .. }) = op;
+            column(&columns, *col)?;
+        }
+        for op in ops {
+            match op {
+                WriteOp::Put { col, key, value } => {
+                    column_mut(&mut columns, col)?.insert(key, value);
+                }
+                WriteOp::Delete { col, key } => {
+                    column_mut(&mut columns, col)?.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+}
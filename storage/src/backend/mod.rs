@@ -0,0 +1,135 @@
+//! The key-value backends of the storage.
+//!
+//! A backend holds the default column, whose column is `None`, and the column families defined in
+//! `schemas::columns`. The keys of a column are iterated in the lexicographic order.
+
+use crate::{error::Result, schemas::columns::Column};
+
+mod memory;
+pub(crate) mod rocks;
+
+pub use memory::MemoryBackend;
+pub use rocks::RocksDBBackend;
+
+/// The start of an iteration over a column.
+#[derive(Debug, Clone, Copy)]
+pub enum IterMode<'a> {
+    /// Forwards from the first key.
+    Start,
+    /// Backwards from the last key.
+    End,
+    /// Forwards from the first key which is not less than the provided one.
+    From(&'a [u8]),
+}
+
+/// A write of a batch, which is applied by `KeyValueBackend::write`.
+#[derive(Debug, Clone)]
+pub enum WriteOp {
+    Put {
+        col: Option<Column>,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        col: Option<Column>,
+        key: Vec<u8>,
+    },
+}
+
+pub trait KeyValueBackend: Send + Sync {
+    fn get(&self, col: Option<Column>, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    fn put(&self, col: Option<Column>, key: &[u8], value: &[u8]) -> Result<()>;
+
+    fn delete(&self, col: Option<Column>, key: &[u8]) -> Result<()>;
+
+    /// Returns the first `limit` key-value pairs of the column in the order of `mode`.
+    fn iterate(&self, col: Column, mode: IterMode, limit: usize)
+        -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    /// Deletes the keys in `[from, to)` of the column atomically without reading their values,
+    /// and returns the number of deleted keys. An absent bound means unbounded.
+    fn delete_range(&self, col: Column, from: Option<&[u8]>, to: Option<&[u8]>) -> Result<usize>;
+
+    /// Applies all of the writes atomically, in order.
+    fn write(&self, ops: Vec<WriteOp>) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::{IterMode, KeyValueBackend, MemoryBackend, RocksDBBackend, WriteOp};
+    use crate::schemas::columns::COLUMN_TRANSACTION_PROOFS as COL;
+
+    // runs the same operations on a backend, and returns the observed results
+    fn run(backend: &dyn KeyValueBackend) -> Vec<String> {
+        let mut results = vec![];
+        backend.put(None, b"key", b"default").unwrap();
+        for key in [&b"a"[..], b"b", b"c", b"d", b"e"] {
+            backend.put(Some(COL), key, key).unwrap();
+        }
+        results.push(format!("{:?}", backend.get(None, b"key").unwrap()));
+        results.push(format!("{:?}", backend.get(Some(COL), b"key").unwrap()));
+        results.push(format!("{:?}", backend.get(Some(COL), b"c").unwrap()));
+        results.push(format!("{:?}", backend.iterate(COL, IterMode::Start, 2)));
+        results.push(format!("{:?}", backend.iterate(COL, IterMode::End, 3)));
+        results.push(format!(
+            "{:?}",
+            backend.iterate(COL, IterMode::From(&b"bb"[..]), 9)
+        ));
+        results.push(format!(
+            "{:?}",
+            backend.get(Some("unknown"), b"key").is_err()
+        ));
+
+        // a batch with an unknown column is not applied at all
+        let ops = vec![
+            WriteOp::Delete {
+                col: Some(COL),
+                key: b"a".to_vec(),
+            },
+            WriteOp::Put {
+                col: Some("unknown"),
+                key: b"key".to_vec(),
+                value: vec![],
+            },
+        ];
+        results.push(format!("{:?}", backend.write(ops).is_err()));
+        results.push(format!("{:?}", backend.get(Some(COL), b"a").unwrap()));
+
+        results.push(format!(
+            "{:?}",
+            backend.delete_range(COL, Some(&b"b"[..]), Some(&b"d"[..]))
+        ));
+        results.push(format!("{:?}", backend.iterate(COL, IterMode::Start, 9)));
+        results.push(format!(
+            "{:?}",
+            backend.delete_range(COL, Some(&b"d"[..]), None)
+        ));
+        results.push(format!("{:?}", backend.delete_range(COL, None, None)));
+        results.push(format!("{:?}", backend.iterate(COL, IterMode::End, 9)));
+        results.push(format!("{:?}", backend.get(None, b"key").unwrap()));
+        results
+    }
+
+    #[test]
+    fn test_backend_parity() {
+        let path = TempDir::new().unwrap();
+        let rocksdb = run(&RocksDBBackend::open(path.path()).unwrap());
+        let memory = run(&MemoryBackend::new());
+        assert_eq!(rocksdb, memory);
+        // the last keys come first by `IterMode::End`
+        assert_eq!(
+            memory[4],
+            format!(
+                "{:?}",
+                Ok::<_, ()>(vec![
+                    (b"e".to_vec(), b"e".to_vec()),
+                    (b"d".to_vec(), b"d".to_vec()),
+                    (b"c".to_vec(), b"c".to_vec()),
+                ])
+            )
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use rocksdb::{
+    prelude::{
+        Delete as _, DeleteCF as _, GetColumnFamilys as _, GetPinned as _, GetPinnedCF as _,
+        IterateCF as _, OpenCF as _, Put as _, PutCF as _, WriteOps as _,
+    },
+    ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, Options, WriteBatch, DB,
+};
+
+use super::{IterMode, KeyValueBackend, WriteOp};
+use crate::{
+    error::{Error, Result},
+    schemas::columns::{self, Column},
+};
+
+/// The backend of a RocksDB database on disk.
+pub struct RocksDBBackend {
+    db: DB,
+}
+
+impl RocksDBBackend {
+    /// Opens the database at `path` with all of the column families, or creates it if it doesn't
+    /// exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let cf_descriptors: Vec<_> = columns::ALL
+            .iter()
+            .map(|col| ColumnFamilyDescriptor::new(*col, Options::default()))
+            .collect();
+
+        let opts = {
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            opts.create_missing_column_families(true);
+            opts
+        };
+
+        let db = DB::open_cf_descriptors(&opts, path.as_ref(), cf_descriptors)?;
+        Ok(Self { db })
+    }
+
+    fn cf_handle(&self, col: Column) -> Result<&ColumnFamily> {
+        self.db
+            .cf_handle(col)
+            .ok_or_else(|| Error::storage(format!("column {col} not found")))
+    }
+}
+
+/// Checks if the error of `RocksDBBackend::open` is caused by column families in the database
+/// which are not opened, RocksDB reports it as an invalid argument without a dedicated kind.
+pub(crate) fn is_unknown_column_family(err: &Error) -> bool {
+    matches!(err, Error::DB(err) if err.to_string().contains("Column families not opened"))
+}
+
+impl KeyValueBackend for RocksDBBackend {
+    fn get(&self, col: Option<Column>, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let value = match col {
+            Some(col) => self.db.get_pinned_cf(self.cf_handle(col)?, key)?,
+            None => self.db.get_pinned(key)?,
+        };
+        Ok(value.map(|raw| raw.to_vec()))
+    }
+
+    fn put(&self, col: Option<Column>, key: &[u8], value: &[u8]) -> Result<()> {
+        match col {
+            Some(col) => self.db.put_cf(self.cf_handle(col)?, key, value)?,
+            None => self.db.put(key, value)?,
+        }
+        Ok(())
+    }
+
+    fn delete(&self, col: Option<Column>, key: &[u8]) -> Result<()> {
+        match col {
+            Some(col) => self.db.delete_cf(self.cf_handle(col)?, key)?,
+            None => self.db.delete(key)?,
+        }
+        Ok(())
+    }
+
+    fn iterate(
+        &self,
+        col: Column,
+        mode: IterMode,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mode = match mode {
+            IterMode::Start => IteratorMode::Start,
+            IterMode::End => IteratorMode::End,
+            IterMode::From(key) => IteratorMode::From(key, Direction::Forward),
+        };
+        let iter = self.db.iterator_cf(self.cf_handle(col)?, mode)?;
+        Ok(iter
+            .take(limit)
+            .map(|(key, value)| (key.into_vec(), value.into_vec()))
+            .collect())
+    }
+
+    // the keys are only counted by a raw iterator, which never copies the values, and they're
+    // deleted by a single range tombstone
+    fn delete_range(&self, col: Column, from: Option<&[u8]>, to: Option<&[u8]>) -> Result<usize> {
+        let cf = self.cf_handle(col)?;
+        let mut iter = self.db.raw_iterator_cf(cf)?;
+        match from {
+            Some(key) => iter.seek(key),
+            None => iter.seek_to_first(),
+        }
+        let mut count = 0;
+        let mut last_key = None;
+        while let Some(key) = iter.key() {
+            if to.map(|to| key >= to).unwrap_or(false) {
+                break;
+            }
+            count += 1;
+            last_key = Some(key.to_vec());
+            iter.next();
+        }
+        iter.status()?;
+        let last_key = match last_key {
+            Some(key) => key,
+            None => return Ok(0),
+        };
+        // the end of the range is exclusive, so an unbounded one ends right after the last key
+        let end = match to {
+            Some(to) => to.to_vec(),
+            None => [last_key.as_slice(), &[0]].concat(),
+        };
+        let mut batch = WriteBatch::default();
+        batch.delete_range_cf(cf, from.unwrap_or_default(), end.as_slice())?;
+        self.db.write(&batch)?;
+        Ok(count)
+    }
+
+    fn write(&self, ops: Vec<WriteOp>) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        for op in &ops {
+            match op {
+                WriteOp::Put {
+                    col: Some(col),
+                    key,
+                    value,
+                } => batch.put_cf(self.cf_handle(col)?, key, value)?,
+                WriteOp::Put {
+                    col: None,
+                    key,
+                    value,
+                } => batch.put(key, value)?,
+                WriteOp::Delete {
+                    col: Some(col),
+                    key,
+                } => batch.delete_cf(self.cf_handle(col)?, key)?,
+                WriteOp::Delete { col: None, key } => batch.delete(key)?,
+            }
+        }
+        self.db.write(&batch)?;
+        Ok(())
+    }
+}
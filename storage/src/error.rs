@@ -19,6 +19,9 @@ pub enum Error {
 
     #[error("data error: {0}")]
     Data(String),
+
+    #[error("migration error: {0}")]
+    Migration(String),
 }
 
 impl Error {
@@ -29,6 +32,10 @@ impl Error {
     pub fn data<T: fmt::Display>(inner: T) -> Self {
         Self::Data(inner.to_string())
     }
+
+    pub fn migration<T: fmt::Display>(inner: T) -> Self {
+        Self::Migration(inner.to_string())
+    }
 }
 
 impl From<molecule::error::VerificationError> for Error {
@@ -1,8 +1,10 @@
+pub mod backend;
 pub mod error;
 pub mod prelude;
 pub mod schemas;
+pub mod snapshot;
 
 pub type Slot = u64;
 
 mod storage;
-pub use crate::storage::Storage;
+pub use crate::storage::{Storage, StorageBatch};
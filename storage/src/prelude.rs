@@ -1,9 +1,9 @@
 use eth2_types::EthSpec;
 use eth_light_client_in_ckb_verification::{
     mmr::{self, ClientRootMMR},
-    types::packed,
+    types::{core, packed, prelude::*},
 };
-use types::Update;
+use types::{Header, Update};
 
 use crate::{
     error::{Error, Result},
@@ -17,6 +17,29 @@ pub trait StorageReader<S: EthSpec>: Send + Sync + Sized {
     fn get_beacon_header_digest(&self, position: u64) -> Result<Option<packed::HeaderDigest>>;
 
     fn get_finalized_update(&self, slot: Slot) -> Result<Option<Update>>;
+
+    fn get_submitted_transaction(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    /// Returns the keys and values of the submitted transactions indexed as pending.
+    fn get_pending_submitted_transactions(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
+
+    fn get_last_watched_slot(&self) -> Result<Option<Slot>>;
+    /// Returns the first `limit` watched transactions in the queue, with their indexes.
+    fn get_watched_transactions(&self, limit: usize) -> Result<Vec<(u64, Vec<u8>)>>;
+    /// Returns the index of the next watched transaction, the indexes are never reused.
+    fn get_next_watched_transaction_index(&self) -> Result<u64>;
+
+    fn get_transaction_proof(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+        tx_hash: &[u8],
+    ) -> Result<Option<Vec<u8>>>;
+
+    fn get_backfilled_slot(&self) -> Result<Option<Slot>>;
+    fn get_backfilled_header(&self, slot: Slot) -> Result<Option<Header>>;
+
+    fn get_light_client_store(&self) -> Result<Option<Vec<u8>>>;
+    fn get_last_checkpoint(&self) -> Result<Option<Vec<u8>>>;
 }
 
 pub trait StorageWriter<S: EthSpec>: Send + Sync + Sized {
@@ -29,6 +52,39 @@ pub trait StorageWriter<S: EthSpec>: Send + Sync + Sized {
     fn put_beacon_header_digest(&self, position: u64, digest: &packed::HeaderDigest) -> Result<()>;
 
     fn put_finalized_update(&self, slot: Slot, update: &Update) -> Result<()>;
+
+    /// Puts a submitted transaction, and indexes it as pending or drops it from the index in the
+    /// same atomic write.
+    fn put_submitted_transaction(&self, key: &[u8], value: &[u8], pending: bool) -> Result<()>;
+
+    fn put_last_watched_slot(&self, slot: Slot) -> Result<()>;
+    fn put_watched_transaction(&self, index: u64, value: &[u8]) -> Result<()>;
+    fn delete_watched_transaction(&self, index: u64) -> Result<()>;
+
+    fn put_transaction_proof(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+        tx_hash: &[u8],
+        value: &[u8],
+    ) -> Result<()>;
+    /// Deletes the transaction proofs of all client ranges except `[minimal_slot, maximal_slot]`,
+    /// and returns the number of deleted proofs.
+    fn delete_stale_transaction_proofs(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+    ) -> Result<usize>;
+
+    /// Puts the backfilled header of `slot` and moves the backfilled slot to it.
+    fn put_backfilled_header(&self, slot: Slot, header: &Header) -> Result<()>;
+    /// Deletes all of the backfilled headers and the backfilled slot.
+    fn clear_backfilled_headers(&self) -> Result<()>;
+    /// Deletes the backfilled headers from `slot` on, and moves the backfilled slot before it.
+    fn truncate_backfilled_headers(&self, slot: Slot) -> Result<()>;
+
+    fn put_light_client_store(&self, value: &[u8]) -> Result<()>;
+    fn put_last_checkpoint(&self, checkpoint: &[u8]) -> Result<()>;
 }
 
 pub trait StorageAsMMRStore<S: EthSpec>:
@@ -58,6 +114,48 @@ pub trait StorageAsMMRStore<S: EthSpec>:
         Ok(())
     }
 
+    /// Checks if the MMR nodes of all stored headers are there, which may be broken by a crash
+    /// in the middle of non-atomic writes, and rolls the tip back to the last complete header.
+    /// Returns whether the storage is repaired.
+    fn repair_torn_writes(&self) -> Result<bool> {
+        let (base, tip_opt) = match (
+            self.get_base_beacon_header_slot()?,
+            self.get_tip_beacon_header_slot()?,
+        ) {
+            (None, None) => return Ok(false),
+            (None, Some(_)) => {
+                self.delete_tip_beacon_header_slot()?;
+                return Ok(true);
+            }
+            (Some(base), tip_opt) => (base, tip_opt),
+        };
+        // the last node of an MMR is written after the others
+        let is_complete = |slot: Slot| -> Result<bool> {
+            let position = mmr::lib::leaf_index_to_mmr_size(slot - base) - 1;
+            Ok(self.get_beacon_header_digest(position)?.is_some())
+        };
+        let mut slot = match tip_opt {
+            Some(tip) if tip < base => {
+                self.rollback_to(None)?;
+                return Ok(true);
+            }
+            Some(tip) if is_complete(tip)? => return Ok(false),
+            Some(tip) => tip,
+            None => base,
+        };
+        loop {
+            if is_complete(slot)? {
+                self.rollback_to(Some(slot))?;
+                return Ok(true);
+            }
+            if slot == base {
+                self.rollback_to(None)?;
+                return Ok(true);
+            }
+            slot -= 1;
+        }
+    }
+
     /// Returns the chain root MMR for a provided slot.
     fn chain_root_mmr(&self, curr: Slot) -> Result<ClientRootMMR<Self>> {
         if let Some(base) = self.get_base_beacon_header_slot()? {
@@ -69,6 +167,92 @@ pub trait StorageAsMMRStore<S: EthSpec>:
             Err(Error::data("no headers"))
         }
     }
+
+    /// Returns the header digest stored for `slot`, or `None` if the slot is out of the stored
+    /// headers.
+    fn get_beacon_header_digest_by_slot(&self, slot: Slot) -> Result<Option<packed::HeaderDigest>> {
+        let (base, tip) = match (
+            self.get_base_beacon_header_slot()?,
+            self.get_tip_beacon_header_slot()?,
+        ) {
+            (Some(base), Some(tip)) => (base, tip),
+            _ => return Ok(None),
+        };
+        if slot < base || slot > tip {
+            return Ok(None);
+        }
+        let position = mmr::lib::leaf_index_to_pos(slot - base);
+        self.get_beacon_header_digest(position)
+    }
+
+    /// Checks if the header digest stored for `slot` is the same as the provided one, returns
+    /// `None` if the slot is out of the stored headers.
+    fn is_header_digest_matched(
+        &self,
+        slot: Slot,
+        digest: &packed::HeaderDigest,
+    ) -> Result<Option<bool>> {
+        let matched = self
+            .get_beacon_header_digest_by_slot(slot)?
+            .map(|stored| stored.as_slice() == digest.as_slice());
+        Ok(matched)
+    }
+
+    /// Checks if the MMR root for headers in `[minimal_slot, maximal_slot]` of the on-chain client
+    /// is the same as its `headers_mmr_root`, the stored headers must cover the client.
+    fn is_onchain_client_matched(&self, client: &core::Client) -> Result<bool> {
+        match (
+            self.get_base_beacon_header_slot()?,
+            self.get_tip_beacon_header_slot()?,
+        ) {
+            (Some(base), Some(tip))
+                if base == client.minimal_slot && tip >= client.maximal_slot =>
+            {
+                self.is_chain_root_matched(client.maximal_slot, &client.headers_mmr_root)
+            }
+            (base, tip) => Err(Error::data(format!(
+                "stored headers [{base:?}, {tip:?}] don't cover the on-chain client {client}"
+            ))),
+        }
+    }
+
+    /// Finds the first slot in `[base, curr]` whose header digest is different from the one in
+    /// `reference`, an MMR of the same slots from another source, by bisecting the roots of the
+    /// MMRs for headers in `[base, slot]`. Returns `None` if there is no difference.
+    fn find_first_diverging_slot<T>(&self, curr: Slot, reference: &T) -> Result<Option<Slot>>
+    where
+        T: mmr::lib::MMRStore<packed::HeaderDigest> + Clone,
+    {
+        let base = self
+            .get_base_beacon_header_slot()?
+            .ok_or_else(|| Error::data("no headers"))?;
+        let is_diverged = |slot: Slot| -> Result<bool> {
+            let mmr_size = mmr::lib::leaf_index_to_mmr_size(slot - base);
+            let local_root = ClientRootMMR::new(mmr_size, self.clone()).get_root()?;
+            let reference_root = ClientRootMMR::new(mmr_size, reference.clone()).get_root()?;
+            Ok(local_root.as_slice() != reference_root.as_slice())
+        };
+        if !is_diverged(curr)? {
+            return Ok(None);
+        }
+        let (mut low, mut high) = (base, curr);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if is_diverged(mid)? {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Ok(Some(low))
+    }
+
+    /// Checks if the MMR root for headers in `[base, curr]` is the same as the provided one.
+    fn is_chain_root_matched(&self, curr: Slot, root: &core::HeaderDigest) -> Result<bool> {
+        let local_root = self.chain_root_mmr(curr)?.get_root()?;
+        let root: packed::HeaderDigest = root.pack();
+        Ok(local_root.as_slice() == root.as_slice())
+    }
 }
 
 impl<S: EthSpec> StorageAsMMRStore<S> for Storage<S> {}
@@ -4,10 +4,42 @@
 pub type Column = &'static str;
 
 /// Total column number
-pub const COUNT: usize = 2;
+pub const COUNT: usize = 8;
 
 /// Column to store MMR for beacon headers
 pub const COLUMN_BEACON_HEADER_MMR: Column = "beacon-header-mmr";
 
 /// Column to store finalized updates
 pub const COLUMN_FINALIZED_UPDATES: Column = "finalized-updates";
+
+/// Column to store CKB transactions submitted by the verifier
+pub const COLUMN_SUBMITTED_TRANSACTIONS: Column = "submitted-transactions";
+
+/// Column to index the submitted transactions which are still pending, by the same keys and with
+/// empty values, so tracking them doesn't scan the committed and rejected ones
+pub const COLUMN_PENDING_SUBMITTED_TRANSACTIONS: Column = "pending-submitted-transactions";
+
+/// Column to store the queue of CKB transactions generated for watched Ethereum logs
+pub const COLUMN_WATCHED_TRANSACTIONS: Column = "watched-transactions";
+
+/// Column to store verified transaction proofs, keyed by the range of the on-chain client and
+/// the Ethereum transaction hash
+pub const COLUMN_TRANSACTION_PROOFS: Column = "transaction-proofs";
+
+/// Column to store the state of the light client, to resume it without bootstrapping again
+pub const COLUMN_LIGHT_CLIENT_STORE: Column = "light-client-store";
+
+/// Column to store the beacon headers fetched by the backfill, which are not authenticated yet
+pub const COLUMN_BACKFILLED_HEADERS: Column = "backfilled-headers";
+
+/// All of the column families
+pub const ALL: [Column; COUNT] = [
+    COLUMN_BEACON_HEADER_MMR,
+    COLUMN_FINALIZED_UPDATES,
+    COLUMN_SUBMITTED_TRANSACTIONS,
+    COLUMN_PENDING_SUBMITTED_TRANSACTIONS,
+    COLUMN_WATCHED_TRANSACTIONS,
+    COLUMN_TRANSACTION_PROOFS,
+    COLUMN_LIGHT_CLIENT_STORE,
+    COLUMN_BACKFILLED_HEADERS,
+];
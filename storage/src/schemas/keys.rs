@@ -7,3 +7,30 @@ pub const MIGRATION_VERSION_KEY: &[u8] = b"db-version";
 pub const BASE_BEACON_HEADER_SLOT: &[u8] = b"base-beacon-header-slot";
 /// The current tip beacon header.
 pub const TIP_BEACON_HEADER_SLOT: &[u8] = b"tip-beacon-header-slot";
+
+/// The last slot of the contiguous backfilled beacon headers.
+pub const BACKFILLED_SLOT: &[u8] = b"backfilled-slot";
+
+/// The last beacon slot whose receipts are scanned for watched logs.
+pub const LAST_WATCHED_SLOT: &[u8] = b"last-watched-slot";
+/// The index of the next watched transaction, which keeps increasing after the queue is drained.
+pub const NEXT_WATCHED_INDEX: &[u8] = b"next-watched-index";
+
+/// The serialized light client store, in the light client store column.
+pub const LIGHT_CLIENT_STORE: &[u8] = b"light-client-store";
+/// The last finalized checkpoint of the light client, in the light client store column.
+pub const LAST_CHECKPOINT: &[u8] = b"last-checkpoint";
+
+/// The key of a transaction proof, the big-endian slots keep the proofs of one client range
+/// together.
+pub(crate) fn transaction_proof_key(
+    minimal_slot: u64,
+    maximal_slot: u64,
+    tx_hash: &[u8],
+) -> Vec<u8> {
+    let mut key = Vec::with_capacity(16 + tx_hash.len());
+    key.extend_from_slice(&minimal_slot.to_be_bytes());
+    key.extend_from_slice(&maximal_slot.to_be_bytes());
+    key.extend_from_slice(tx_hash);
+    key
+}
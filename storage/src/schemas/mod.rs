@@ -2,3 +2,6 @@
 
 pub mod columns;
 pub mod keys;
+
+/// The version of the schemas, it's increased by each migration of the stored data.
+pub const SCHEMA_VERSION: u64 = 2;
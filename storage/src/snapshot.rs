@@ -0,0 +1,433 @@
+//! The portable snapshot of the stored beacon headers, which lets a new instance skip downloading
+//! all of the headers of the on-chain client.
+//!
+//! A snapshot file is laid out as follows, all integers are big-endian:
+//!
+//! - the magic bytes `forcerelay-mmr-snapshot` and the format version as `u32`
+//! - the base slot, the tip slot and the MMR size as `u64`
+//! - the header digests of MMR positions in `[0, MMR size)`
+//! - the number of finalized updates as `u64`, and each of them as the key and the value, both
+//!   prefixed by the length as `u32`
+
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
+
+use eth2_types::EthSpec;
+use eth_light_client_in_ckb_verification::{
+    mmr::{
+        self,
+        lib::{Error as MMRError, MMRStore, Result as MMRResult},
+        ClientRootMMR,
+    },
+    types::{core, packed, prelude::*},
+};
+use types::Update;
+
+use crate::{
+    backend::IterMode,
+    error::{Error, Result},
+    prelude::{StorageAsMMRStore as _, StorageReader as _},
+    schemas::columns,
+    Slot, Storage,
+};
+
+const MAGIC: &[u8] = b"forcerelay-mmr-snapshot";
+const FORMAT_VERSION: u32 = 1;
+
+/// A snapshot read from a file, which holds the MMR nodes in memory.
+pub struct Snapshot {
+    pub base_slot: Slot,
+    pub tip_slot: Slot,
+    nodes: SnapshotMMRStore,
+    updates: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Clone)]
+struct SnapshotMMRStore(Arc<Vec<packed::HeaderDigest>>);
+
+impl MMRStore<packed::HeaderDigest> for SnapshotMMRStore {
+    fn get_elem(&self, pos: u64) -> MMRResult<Option<packed::HeaderDigest>> {
+        Ok(self.0.get(pos as usize).cloned())
+    }
+
+    fn append(&mut self, _pos: u64, _elems: Vec<packed::HeaderDigest>) -> MMRResult<()> {
+        Err(MMRError::StoreError("snapshot is read-only".to_owned()))
+    }
+}
+
+impl Snapshot {
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut magic = vec![0u8; MAGIC.len()];
+        reader.read_exact(&mut magic).map_err(Error::storage)?;
+        if magic != MAGIC {
+            return Err(Error::data("not a snapshot file"));
+        }
+        let version = u32::from_be_bytes(read_array(reader)?);
+        if version != FORMAT_VERSION {
+            return Err(Error::data(format!(
+                "unsupported snapshot format version {version}"
+            )));
+        }
+        let base_slot = u64::from_be_bytes(read_array(reader)?);
+        let tip_slot = u64::from_be_bytes(read_array(reader)?);
+        let mmr_size = u64::from_be_bytes(read_array(reader)?);
+        if tip_slot < base_slot
+            || mmr_size != mmr::lib::leaf_index_to_mmr_size(tip_slot - base_slot)
+        {
+            return Err(Error::data(format!(
+                "MMR size {mmr_size} mismatches the slots [{base_slot}, {tip_slot}]"
+            )));
+        }
+        // the sizes are not trusted, so the buffers grow with the data actually read
+        let mut nodes = Vec::new();
+        let mut digest = vec![0u8; packed::HeaderDigest::TOTAL_SIZE];
+        for _ in 0..mmr_size {
+            reader.read_exact(&mut digest).map_err(Error::storage)?;
+            nodes.push(packed::HeaderDigest::from_slice(&digest)?);
+        }
+        let updates_count = u64::from_be_bytes(read_array(reader)?);
+        let mut updates = Vec::new();
+        for _ in 0..updates_count {
+            let key = read_bytes(reader)?;
+            let value = read_bytes(reader)?;
+            updates.push((key, value));
+        }
+        Ok(Self {
+            base_slot,
+            tip_slot,
+            nodes: SnapshotMMRStore(Arc::new(nodes)),
+            updates,
+        })
+    }
+
+    pub fn updates_count(&self) -> usize {
+        self.updates.len()
+    }
+
+    /// Checks if the MMR root for headers in `[minimal_slot, maximal_slot]` of the on-chain client
+    /// is the same as its `headers_mmr_root`.
+    pub fn is_onchain_client_matched(&self, client: &core::Client) -> Result<bool> {
+        let root: packed::HeaderDigest = client.headers_mmr_root.pack();
+        self.is_root_matched(client.minimal_slot, client.maximal_slot, &root)
+    }
+
+    fn is_root_matched(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+        root: &packed::HeaderDigest,
+    ) -> Result<bool> {
+        if self.base_slot != minimal_slot || self.tip_slot < maximal_slot {
+            return Err(Error::data(format!(
+                "snapshot [{}, {}] doesn't cover the on-chain client [{minimal_slot}, \
+                {maximal_slot}]",
+                self.base_slot, self.tip_slot
+            )));
+        }
+        let mmr_size = mmr::lib::leaf_index_to_mmr_size(maximal_slot - self.base_slot);
+        let actual = ClientRootMMR::new(mmr_size, self.nodes.clone()).get_root()?;
+        Ok(actual.as_slice() == root.as_slice())
+    }
+
+    // checks the finalized header of the update stored for `slot` against the header digest in
+    // the snapshot, which is authenticated by the MMR root already
+    fn check_update(&self, slot: Slot, value: &[u8]) -> Result<()> {
+        let update = Update::from_slice(value).map_err(Error::storage)?;
+        let header = &update.finalized_header;
+        if header.slot != slot {
+            return Err(Error::data(format!(
+                "finalized update stored for slot {slot} is of slot {}",
+                header.slot
+            )));
+        }
+        let digest = core::Header::from(header).calc_cache().digest();
+        let position = mmr::lib::leaf_index_to_pos(slot - self.base_slot);
+        match self.nodes.0.get(position as usize) {
+            Some(expected) if expected.as_slice() == digest.as_slice() => Ok(()),
+            _ => Err(Error::data(format!(
+                "finalized update of slot {slot} mismatches the header in snapshot"
+            ))),
+        }
+    }
+}
+
+impl<S> Storage<S>
+where
+    S: EthSpec,
+{
+    /// Writes the stored headers, and the finalized updates if `with_updates` is true, as a
+    /// snapshot. Returns the slots of the exported headers.
+    pub fn export_snapshot<W: Write>(
+        &self,
+        writer: &mut W,
+        with_updates: bool,
+    ) -> Result<(Slot, Slot)> {
+        let (base_slot, tip_slot) = match (
+            self.get_base_beacon_header_slot()?,
+            self.get_tip_beacon_header_slot()?,
+        ) {
+            (Some(base), Some(tip)) => (base, tip),
+            _ => return Err(Error::data("no headers")),
+        };
+        let mmr_size = mmr::lib::leaf_index_to_mmr_size(tip_slot - base_slot);
+        let mut write = |bytes: &[u8]| writer.write_all(bytes).map_err(Error::storage);
+        write(MAGIC)?;
+        write(&FORMAT_VERSION.to_be_bytes())?;
+        write(&base_slot.to_be_bytes())?;
+        write(&tip_slot.to_be_bytes())?;
+        write(&mmr_size.to_be_bytes())?;
+        for position in 0..mmr_size {
+            let digest = self
+                .get_beacon_header_digest(position)?
+                .ok_or_else(|| Error::data(format!("no MMR node at position {position}")))?;
+            write(digest.as_slice())?;
+        }
+        let updates = if with_updates {
+            self.iterate_cf(
+                columns::COLUMN_FINALIZED_UPDATES,
+                IterMode::Start,
+                usize::MAX,
+            )?
+        } else {
+            vec![]
+        };
+        write(&(updates.len() as u64).to_be_bytes())?;
+        for (key, value) in &updates {
+            write(&(key.len() as u32).to_be_bytes())?;
+            write(key)?;
+            write(&(value.len() as u32).to_be_bytes())?;
+            write(value)?;
+        }
+        Ok((base_slot, tip_slot))
+    }
+
+    /// Puts the headers of the on-chain client from the snapshot into the empty storage
+    /// atomically, after the MMR root is checked against the client. The headers after the
+    /// maximal slot of the client are dropped, since they're not covered by the root, and so are
+    /// the finalized updates of them. The finalized updates must match the imported headers.
+    pub fn import_snapshot(&self, snapshot: &Snapshot, client: &core::Client) -> Result<()> {
+        let root: packed::HeaderDigest = client.headers_mmr_root.pack();
+        self.import_snapshot_headers(snapshot, client.minimal_slot, client.maximal_slot, &root)
+    }
+
+    fn import_snapshot_headers(
+        &self,
+        snapshot: &Snapshot,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+        root: &packed::HeaderDigest,
+    ) -> Result<()> {
+        if self.is_initialized()? {
+            return Err(Error::data("storage is initialized already"));
+        }
+        if !snapshot.is_root_matched(minimal_slot, maximal_slot, root)? {
+            return Err(Error::data(format!(
+                "MMR root of snapshot mismatches the on-chain client [{minimal_slot}, \
+                {maximal_slot}]"
+            )));
+        }
+        let mmr_size = mmr::lib::leaf_index_to_mmr_size(maximal_slot - minimal_slot);
+        let batch = self.batch();
+        batch.put_base_beacon_header_slot(minimal_slot)?;
+        batch.put_tip_beacon_header_slot(maximal_slot)?;
+        for (position, digest) in snapshot.nodes.0.iter().take(mmr_size as usize).enumerate() {
+            batch.put_beacon_header_digest(position as u64, digest)?;
+        }
+        for (key, value) in &snapshot.updates {
+            let slot = packed::Uint64Reader::from_slice(key)?.unpack();
+            if slot >= minimal_slot && slot <= maximal_slot {
+                snapshot.check_update(slot, value)?;
+                batch.put_cf(columns::COLUMN_FINALIZED_UPDATES, key, value)?;
+            }
+        }
+        batch.commit()
+    }
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes).map_err(Error::storage)?;
+    Ok(bytes)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let len = u32::from_be_bytes(read_array(reader)?);
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(u64::from(len))
+        .read_to_end(&mut bytes)
+        .map_err(Error::storage)?;
+    if bytes.len() != len as usize {
+        return Err(Error::data("unexpected end of snapshot"));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use eth2_types::MainnetEthSpec;
+    use eth_light_client_in_ckb_verification::{
+        mmr::{self, ClientRootMMR},
+        types::{core, packed, prelude::*},
+    };
+    use types::{Header, Update};
+
+    use super::{Snapshot, FORMAT_VERSION, MAGIC};
+    use crate::{
+        error::Error,
+        prelude::{StorageAsMMRStore as _, StorageReader as _, StorageWriter as _},
+        Storage,
+    };
+
+    const BASE_SLOT: u64 = 100;
+    const TIP_SLOT: u64 = 120;
+    // the on-chain client covers a part of the exported headers
+    const MAXIMAL_SLOT: u64 = 115;
+
+    fn header(slot: u64, proposer_index: u64) -> Header {
+        Header {
+            slot,
+            proposer_index,
+            ..Default::default()
+        }
+    }
+
+    fn new_storage() -> Storage<MainnetEthSpec> {
+        Storage::new_in_memory().unwrap()
+    }
+
+    // stores the headers in [BASE_SLOT, TIP_SLOT] and the finalized updates of some of them
+    fn storage_with_headers(updates: &[Update]) -> Storage<MainnetEthSpec> {
+        let storage = new_storage();
+        let batch = storage.batch();
+        batch.put_base_beacon_header_slot(BASE_SLOT).unwrap();
+        let mut mmr = ClientRootMMR::new(0, batch.clone());
+        for slot in BASE_SLOT..=TIP_SLOT {
+            let header: core::Header = (&header(slot, 1)).into();
+            mmr.push(header.calc_cache().digest()).unwrap();
+        }
+        mmr.commit().unwrap();
+        batch.put_tip_beacon_header_slot(TIP_SLOT).unwrap();
+        batch.commit().unwrap();
+        for update in updates {
+            storage
+                .put_finalized_update(update.finalized_header.slot, update)
+                .unwrap();
+        }
+        storage
+    }
+
+    fn export(storage: &Storage<MainnetEthSpec>) -> Vec<u8> {
+        let mut bytes = vec![];
+        storage.export_snapshot(&mut bytes, true).unwrap();
+        bytes
+    }
+
+    fn root(storage: &Storage<MainnetEthSpec>, maximal_slot: u64) -> packed::HeaderDigest {
+        storage
+            .chain_root_mmr(maximal_slot)
+            .unwrap()
+            .get_root()
+            .unwrap()
+    }
+
+    fn import(
+        bytes: &[u8],
+        minimal_slot: u64,
+        maximal_slot: u64,
+        root: &packed::HeaderDigest,
+    ) -> (Storage<MainnetEthSpec>, Result<(), Error>) {
+        let snapshot = Snapshot::read_from(&mut &bytes[..]).unwrap();
+        let storage = new_storage();
+        let result = storage.import_snapshot_headers(&snapshot, minimal_slot, maximal_slot, root);
+        (storage, result)
+    }
+
+    #[test]
+    fn test_import_snapshot() {
+        let updates: Vec<_> = [BASE_SLOT, 110, TIP_SLOT]
+            .into_iter()
+            .map(|slot| Update::from_finalized_header(header(slot, 1)))
+            .collect();
+        let source = storage_with_headers(&updates);
+        let root = root(&source, MAXIMAL_SLOT);
+        let (storage, result) = import(&export(&source), BASE_SLOT, MAXIMAL_SLOT, &root);
+        result.unwrap();
+
+        assert_eq!(
+            storage.get_base_beacon_header_slot().unwrap(),
+            Some(BASE_SLOT)
+        );
+        assert_eq!(
+            storage.get_tip_beacon_header_slot().unwrap(),
+            Some(MAXIMAL_SLOT)
+        );
+        let imported_root = storage
+            .chain_root_mmr(MAXIMAL_SLOT)
+            .unwrap()
+            .get_root()
+            .unwrap();
+        assert_eq!(imported_root.as_slice(), root.as_slice());
+        // the updates after the maximal slot are not covered by the root
+        assert!(storage.get_finalized_update(110).unwrap().is_some());
+        assert!(storage.get_finalized_update(TIP_SLOT).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_import_tampered_snapshot() {
+        let source = storage_with_headers(&[]);
+        let root = root(&source, MAXIMAL_SLOT);
+
+        // a header digest is changed
+        let mut bytes = export(&source);
+        let offset = MAGIC.len() + 4 + 8 * 3 + 3 * packed::HeaderDigest::TOTAL_SIZE;
+        bytes[offset] ^= 1;
+        let (storage, result) = import(&bytes, BASE_SLOT, MAXIMAL_SLOT, &root);
+        assert!(matches!(result, Err(Error::Data(_))));
+        assert!(!storage.is_initialized().unwrap());
+
+        // a finalized update doesn't match the header of its slot
+        let forged = Update::from_finalized_header(header(110, 2));
+        source.put_finalized_update(110, &forged).unwrap();
+        let (storage, result) = import(&export(&source), BASE_SLOT, MAXIMAL_SLOT, &root);
+        assert!(matches!(result, Err(Error::Data(_))));
+        assert!(!storage.is_initialized().unwrap());
+    }
+
+    #[test]
+    fn test_import_snapshot_not_covering_client() {
+        let source = storage_with_headers(&[]);
+        let bytes = export(&source);
+        let root = root(&source, MAXIMAL_SLOT);
+        for (minimal_slot, maximal_slot) in
+            [(BASE_SLOT + 1, MAXIMAL_SLOT), (BASE_SLOT, TIP_SLOT + 1)]
+        {
+            let (storage, result) = import(&bytes, minimal_slot, maximal_slot, &root);
+            assert!(matches!(result, Err(Error::Data(_))));
+            assert!(!storage.is_initialized().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_read_truncated_snapshot() {
+        // the sizes in the file are not trusted to allocate memory
+        let tip_slot: u64 = 1 << 40;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&0u64.to_be_bytes());
+        bytes.extend_from_slice(&tip_slot.to_be_bytes());
+        bytes.extend_from_slice(&mmr::lib::leaf_index_to_mmr_size(tip_slot).to_be_bytes());
+        assert!(Snapshot::read_from(&mut &bytes[..]).is_err());
+
+        // an update claims more bytes than the file has
+        let mut bytes = export(&storage_with_headers(&[]));
+        let updates_count = bytes.len() - 8;
+        bytes.truncate(updates_count);
+        bytes.extend_from_slice(&1u64.to_be_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Snapshot::read_from(&mut &bytes[..]).is_err());
+    }
+}
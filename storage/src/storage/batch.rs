@@ -0,0 +1,198 @@
+use std::sync::{Arc, Mutex};
+
+use eth2_types::EthSpec;
+use eth_light_client_in_ckb_verification::{
+    mmr::{
+        self,
+        lib::{Error as MMRError, MMRStore, Result as MMRResult},
+        ClientRootMMR,
+    },
+    types::{packed, prelude::*},
+};
+
+use super::Storage;
+use crate::{
+    backend::WriteOp,
+    error::{Error, Result},
+    prelude::StorageReader as _,
+    schemas::{
+        columns::{self, Column},
+        keys,
+    },
+    Slot,
+};
+
+/// A batch of writes, e.g. to the beacon header MMR and its slots, which are invisible until
+/// they're committed atomically by `commit`.
+///
+/// The reads through the batch, e.g. `get_elem` of the MMR, only see the committed data, so all
+/// headers of one batch should be pushed into the same MMR.
+#[derive(Clone)]
+pub struct StorageBatch<S> {
+    storage: Storage<S>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    ops: Vec<WriteOp>,
+    base_beacon_header_slot: Option<Slot>,
+}
+
+impl<S> Storage<S> {
+    /// Starts a batch of writes, which are committed atomically by `StorageBatch::commit`.
+    pub fn batch(&self) -> StorageBatch<S> {
+        StorageBatch {
+            storage: self.clone(),
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<S> StorageBatch<S>
+where
+    S: EthSpec,
+{
+    pub fn put_base_beacon_header_slot(&self, slot: Slot) -> Result<()> {
+        self.put(keys::BASE_BEACON_HEADER_SLOT, slot.pack().as_slice())?;
+        self.inner
+            .lock()
+            .map_err(Error::storage)?
+            .base_beacon_header_slot = Some(slot);
+        Ok(())
+    }
+
+    pub fn put_tip_beacon_header_slot(&self, slot: Slot) -> Result<()> {
+        self.put(keys::TIP_BEACON_HEADER_SLOT, slot.pack().as_slice())
+    }
+
+    pub fn put_beacon_header_digest(
+        &self,
+        position: u64,
+        digest: &packed::HeaderDigest,
+    ) -> Result<()> {
+        let key: packed::Uint64 = position.pack();
+        self.put_cf(
+            columns::COLUMN_BEACON_HEADER_MMR,
+            key.as_slice(),
+            digest.as_slice(),
+        )
+    }
+
+    /// Puts a watched transaction and moves the next index after it.
+    pub fn put_watched_transaction(&self, index: u64, value: &[u8]) -> Result<()> {
+        self.put_cf(
+            columns::COLUMN_WATCHED_TRANSACTIONS,
+            index.to_be_bytes(),
+            value,
+        )?;
+        self.put(keys::NEXT_WATCHED_INDEX, (index + 1).pack().as_slice())
+    }
+
+    /// Puts a submitted transaction, and indexes it as pending or drops it from the index.
+    pub fn put_submitted_transaction(&self, key: &[u8], value: &[u8], pending: bool) -> Result<()> {
+        self.put_cf(columns::COLUMN_SUBMITTED_TRANSACTIONS, key, value)?;
+        if pending {
+            self.put_cf(columns::COLUMN_PENDING_SUBMITTED_TRANSACTIONS, key, b"")
+        } else {
+            self.delete_cf(columns::COLUMN_PENDING_SUBMITTED_TRANSACTIONS, key)
+        }
+    }
+
+    pub fn put_last_watched_slot(&self, slot: Slot) -> Result<()> {
+        self.put(keys::LAST_WATCHED_SLOT, slot.pack().as_slice())
+    }
+
+    fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
+        let mut inner = self.inner.lock().map_err(Error::storage)?;
+        inner.ops.push(WriteOp::Put {
+            col: None,
+            key: key.as_ref().to_vec(),
+            value: value.as_ref().to_vec(),
+        });
+        Ok(())
+    }
+
+    pub(crate) fn put_cf<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &self,
+        col: Column,
+        key: K,
+        value: V,
+    ) -> Result<()> {
+        let mut inner = self.inner.lock().map_err(Error::storage)?;
+        inner.ops.push(WriteOp::Put {
+            col: Some(col),
+            key: key.as_ref().to_vec(),
+            value: value.as_ref().to_vec(),
+        });
+        Ok(())
+    }
+
+    pub(crate) fn delete_cf<K: AsRef<[u8]>>(&self, col: Column, key: K) -> Result<()> {
+        let mut inner = self.inner.lock().map_err(Error::storage)?;
+        inner.ops.push(WriteOp::Delete {
+            col: Some(col),
+            key: key.as_ref().to_vec(),
+        });
+        Ok(())
+    }
+
+    /// Returns the chain root MMR for a provided slot, whose new nodes are written into the batch.
+    /// The base slot put into the batch takes precedence over the committed one.
+    pub fn chain_root_mmr(&self, curr: Slot) -> Result<ClientRootMMR<Self>> {
+        let base_opt = self
+            .inner
+            .lock()
+            .map_err(Error::storage)?
+            .base_beacon_header_slot;
+        let base = match base_opt {
+            Some(base) => base,
+            None => self
+                .storage
+                .get_base_beacon_header_slot()?
+                .ok_or_else(|| Error::data("no headers"))?,
+        };
+        let mmr_size = mmr::lib::leaf_index_to_mmr_size(curr - base);
+        Ok(ClientRootMMR::new(mmr_size, self.clone()))
+    }
+
+    /// Writes all of the batched writes atomically.
+    pub fn commit(self) -> Result<()> {
+        let mut inner = self.inner.lock().map_err(Error::storage)?;
+        let mut writer = self
+            .storage
+            .cache
+            .base_beacon_header_slot
+            .write()
+            .map_err(Error::storage)?;
+        let ops = std::mem::take(&mut inner.ops);
+        self.storage.db.write(ops)?;
+        if let Some(slot) = inner.base_beacon_header_slot.take() {
+            *writer = Some(slot);
+        }
+        Ok(())
+    }
+}
+
+impl<S> MMRStore<packed::HeaderDigest> for StorageBatch<S>
+where
+    S: EthSpec,
+{
+    fn get_elem(&self, pos: u64) -> MMRResult<Option<packed::HeaderDigest>> {
+        self.storage.get_beacon_header_digest(pos).map_err(|err| {
+            MMRError::StoreError(format!(
+                "Failed to read position {pos} from MMR, DB error {err}"
+            ))
+        })
+    }
+
+    fn append(&mut self, pos: u64, elems: Vec<packed::HeaderDigest>) -> MMRResult<()> {
+        for (offset, elem) in elems.iter().enumerate() {
+            let pos: u64 = pos + (offset as u64);
+            self.put_beacon_header_digest(pos, elem).map_err(|err| {
+                MMRError::StoreError(format!("Failed to append to MMR, DB error {err}"))
+            })?;
+        }
+        Ok(())
+    }
+}
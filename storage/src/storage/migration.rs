@@ -0,0 +1,210 @@
+use eth_light_client_in_ckb_verification::types::{packed, prelude::*};
+
+use crate::{
+    backend::{IterMode, KeyValueBackend, WriteOp},
+    error::{Error, Result},
+    schemas::{columns, keys, SCHEMA_VERSION},
+};
+
+/// A change of the stored data, e.g. re-keying a column, which upgrades the database to
+/// `version()` from the previous version.
+///
+/// The new column families are created when the database is opened, so a migration only has to
+/// move the data into them.
+pub(crate) trait Migration {
+    fn version(&self) -> u64;
+
+    fn migrate(&self, db: &dyn KeyValueBackend) -> Result<()>;
+}
+
+/// The databases created before the versioning are of version 0, and share the same format as
+/// version 1.
+struct Unversioned;
+
+impl Migration for Unversioned {
+    fn version(&self) -> u64 {
+        1
+    }
+
+    fn migrate(&self, _db: &dyn KeyValueBackend) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Indexes the submitted transactions in the pending column. The statuses are opaque to the
+/// storage, so all of them are indexed, and the tracker drops the ones which are not pending
+/// anymore when it loads them for the first time.
+struct IndexPendingSubmissions;
+
+impl Migration for IndexPendingSubmissions {
+    fn version(&self) -> u64 {
+        2
+    }
+
+    fn migrate(&self, db: &dyn KeyValueBackend) -> Result<()> {
+        let ops = db
+            .iterate(
+                columns::COLUMN_SUBMITTED_TRANSACTIONS,
+                IterMode::Start,
+                usize::MAX,
+            )?
+            .into_iter()
+            .map(|(key, _)| WriteOp::Put {
+                col: Some(columns::COLUMN_PENDING_SUBMITTED_TRANSACTIONS),
+                key,
+                value: vec![],
+            })
+            .collect();
+        db.write(ops)
+    }
+}
+
+/// The registered migrations, in the order of versions.
+fn migrations() -> Vec<Box<dyn Migration>> {
+    vec![Box::new(Unversioned), Box::new(IndexPendingSubmissions)]
+}
+
+pub(crate) fn get_version(db: &dyn KeyValueBackend) -> Result<Option<u64>> {
+    db.get(None, keys::MIGRATION_VERSION_KEY)?
+        .map(|raw| packed::Uint64Reader::from_slice(&raw).map(|reader| reader.unpack()))
+        .transpose()
+        .map_err(Into::into)
+}
+
+fn put_version(db: &dyn KeyValueBackend, version: u64) -> Result<()> {
+    db.put(None, keys::MIGRATION_VERSION_KEY, version.pack().as_slice())
+}
+
+/// Upgrades the database to `SCHEMA_VERSION` by running the migrations after its version one by
+/// one, a database without a version is created just now if `created` is true, otherwise it's of
+/// version 0. A database of a later version is refused since it can't be downgraded.
+pub(crate) fn migrate(db: &dyn KeyValueBackend, created: bool) -> Result<()> {
+    migrate_with(db, created, migrations(), SCHEMA_VERSION)
+}
+
+fn migrate_with(
+    db: &dyn KeyValueBackend,
+    created: bool,
+    migrations: Vec<Box<dyn Migration>>,
+    target: u64,
+) -> Result<()> {
+    let version = match get_version(db)? {
+        Some(version) => version,
+        None if created => return put_version(db, target),
+        None => 0,
+    };
+    if version > target {
+        return Err(Error::migration(format!(
+            "database version {version} is later than the supported version {target}, \
+            downgrade is not supported"
+        )));
+    }
+    let mut current = version;
+    for migration in migrations {
+        if migration.version() <= current {
+            continue;
+        }
+        if migration.version() != current + 1 {
+            return Err(Error::migration(format!(
+                "no migration from database version {current} to {}",
+                migration.version()
+            )));
+        }
+        migration.migrate(db).map_err(|err| {
+            Error::migration(format!(
+                "failed to migrate database from version {current} to {}: {err}",
+                migration.version()
+            ))
+        })?;
+        current = migration.version();
+        put_version(db, current)?;
+    }
+    if current != target {
+        return Err(Error::migration(format!(
+            "no migration from database version {current} to {target}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_version, migrate, migrate_with, put_version, Migration, Unversioned};
+    use crate::{
+        backend::{IterMode, KeyValueBackend, MemoryBackend},
+        error::{Error, Result},
+        schemas::{columns, SCHEMA_VERSION},
+    };
+
+    struct Noop(u64);
+
+    impl Migration for Noop {
+        fn version(&self) -> u64 {
+            self.0
+        }
+
+        fn migrate(&self, _db: &dyn KeyValueBackend) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_new_database_is_stamped() {
+        let db = MemoryBackend::new();
+        migrate(&db, true).unwrap();
+        assert_eq!(get_version(&db).unwrap(), Some(SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn test_unversioned_database_is_migrated() {
+        let db = MemoryBackend::new();
+        migrate_with(&db, false, vec![Box::new(Unversioned)], 1).unwrap();
+        assert_eq!(get_version(&db).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_submitted_transactions_are_indexed() {
+        let db = MemoryBackend::new();
+        put_version(&db, 1).unwrap();
+        for key in [&b"a"[..], b"b"] {
+            db.put(Some(columns::COLUMN_SUBMITTED_TRANSACTIONS), key, b"record")
+                .unwrap();
+        }
+        migrate(&db, false).unwrap();
+        assert_eq!(get_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        let indexed = db
+            .iterate(
+                columns::COLUMN_PENDING_SUBMITTED_TRANSACTIONS,
+                IterMode::Start,
+                usize::MAX,
+            )
+            .unwrap();
+        assert_eq!(
+            indexed,
+            vec![(b"a".to_vec(), vec![]), (b"b".to_vec(), vec![])]
+        );
+    }
+
+    #[test]
+    fn test_later_version_is_refused() {
+        let db = MemoryBackend::new();
+        put_version(&db, SCHEMA_VERSION + 1).unwrap();
+        let err = migrate(&db, false).unwrap_err();
+        assert!(matches!(err, Error::Migration(_)), "{err}");
+        assert_eq!(get_version(&db).unwrap(), Some(SCHEMA_VERSION + 1));
+    }
+
+    #[test]
+    fn test_migration_gap_is_rejected() {
+        let db = MemoryBackend::new();
+        let migrations: Vec<Box<dyn Migration>> = vec![Box::new(Unversioned), Box::new(Noop(3))];
+        let err = migrate_with(&db, false, migrations, 3).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("no migration from database version 1 to 3"),
+            "{err}"
+        );
+        // the migrations before the gap are applied
+        assert_eq!(get_version(&db).unwrap(), Some(1));
+    }
+}
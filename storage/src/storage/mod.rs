@@ -1,56 +1,66 @@
 use std::{marker::PhantomData, path::Path, sync::Arc};
 
-use rocksdb::{
-    prelude::{
-        Delete as _, GetColumnFamilys as _, GetPinned as _, GetPinnedCF as _, OpenCF as _,
-        Put as _, PutCF as _,
-    },
-    ColumnFamily, ColumnFamilyDescriptor, DBPinnableSlice, Options, DB,
-};
-
 use crate::{
+    backend::{rocks, IterMode, KeyValueBackend, MemoryBackend, RocksDBBackend},
     error::{Error, Result},
-    schemas::columns::{self, Column},
+    schemas::columns::Column,
 };
 
+mod batch;
 mod cache;
+mod migration;
 mod mmr;
 mod reader;
 mod writer;
 
+pub use batch::StorageBatch;
 use cache::Cache;
 
 #[derive(Clone)]
 pub struct Storage<S> {
-    pub(crate) db: Arc<DB>,
+    pub(crate) db: Arc<dyn KeyValueBackend>,
     pub(crate) cache: Arc<Cache>,
     _phantom_data: PhantomData<S>,
 }
 
 impl<S> Storage<S> {
+    /// Opens the RocksDB database at `path`, or creates it if it doesn't exist. An existing
+    /// database is migrated to the current schema version, and it's refused if it's of a later
+    /// version.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let cf_names = {
-            let mut cf_names = Vec::with_capacity(columns::COUNT);
-            cf_names.push(columns::COLUMN_BEACON_HEADER_MMR.to_string());
-            cf_names.push(columns::COLUMN_FINALIZED_UPDATES.to_string());
-            cf_names
-        };
-        let cf_descriptors: Vec<_> = cf_names
-            .iter()
-            .map(|c| ColumnFamilyDescriptor::new(c, Options::default()))
-            .collect();
-
-        let opts = {
-            let mut opts = Options::default();
-            opts.create_if_missing(true);
-            opts.create_missing_column_families(true);
-            opts
-        };
+        let created = !path.as_ref().join("CURRENT").exists();
+        let backend = RocksDBBackend::open(path.as_ref()).map_err(|err| {
+            // an existing database can't be opened if it has unknown column families, which may
+            // be created by a later version, the other errors are passed through
+            if rocks::is_unknown_column_family(&err) {
+                Error::migration(format!(
+                    "failed to open database {}: {err}, a database created by a later version \
+                    can't be opened",
+                    path.as_ref().display()
+                ))
+            } else {
+                err
+            }
+        })?;
+        Self::open(Arc::new(backend), created)
+    }
+
+    /// Creates an empty storage in memory, whose data are dropped with it.
+    pub fn new_in_memory() -> Result<Self> {
+        Self::open(Arc::new(MemoryBackend::new()), true)
+    }
 
-        let db = DB::open_cf_descriptors(&opts, path.as_ref(), cf_descriptors)?;
+    /// Opens a storage over a custom backend. The backend without a schema version is regarded
+    /// as an unversioned database, and it's migrated like the one on disk.
+    pub fn with_backend(backend: Arc<dyn KeyValueBackend>) -> Result<Self> {
+        Self::open(backend, false)
+    }
+
+    fn open(backend: Arc<dyn KeyValueBackend>, created: bool) -> Result<Self> {
+        migration::migrate(backend.as_ref(), created)?;
         let cache = Cache::default();
         let storage = Self {
-            db: Arc::new(db),
+            db: backend,
             cache: Arc::new(cache),
             _phantom_data: PhantomData,
         };
@@ -58,27 +68,20 @@ impl<S> Storage<S> {
         Ok(storage)
     }
 
-    pub(crate) fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<DBPinnableSlice>> {
-        self.db.get_pinned(key.as_ref()).map_err(Into::into)
+    pub(crate) fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        self.db.get(None, key.as_ref())
     }
 
     pub(crate) fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<()> {
-        self.db
-            .put(key.as_ref(), value.as_ref())
-            .map_err(Into::into)
+        self.db.put(None, key.as_ref(), value.as_ref())
     }
 
     pub(crate) fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<()> {
-        self.db.delete(key.as_ref()).map_err(Into::into)
+        self.db.delete(None, key.as_ref())
     }
 
-    pub(crate) fn get_cf<K: AsRef<[u8]>>(
-        &self,
-        col: Column,
-        key: K,
-    ) -> Result<Option<DBPinnableSlice>> {
-        let cf = cf_handle(&self.db, col)?;
-        self.db.get_pinned_cf(cf, key.as_ref()).map_err(Into::into)
+    pub(crate) fn get_cf<K: AsRef<[u8]>>(&self, col: Column, key: K) -> Result<Option<Vec<u8>>> {
+        self.db.get(Some(col), key.as_ref())
     }
 
     pub(crate) fn put_cf<K: AsRef<[u8]>, V: AsRef<[u8]>>(
@@ -87,14 +90,67 @@ impl<S> Storage<S> {
         key: K,
         value: V,
     ) -> Result<()> {
-        let cf = cf_handle(&self.db, col)?;
-        self.db
-            .put_cf(cf, key.as_ref(), value.as_ref())
-            .map_err(Into::into)
+        self.db.put(Some(col), key.as_ref(), value.as_ref())
+    }
+
+    pub(crate) fn delete_cf<K: AsRef<[u8]>>(&self, col: Column, key: K) -> Result<()> {
+        self.db.delete(Some(col), key.as_ref())
+    }
+
+    pub(crate) fn iterate_cf(
+        &self,
+        col: Column,
+        mode: IterMode,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.db.iterate(col, mode, limit)
+    }
+
+    /// Deletes the keys in `[from, to)` of the column, and returns the number of deleted keys.
+    /// An absent bound means unbounded.
+    pub(crate) fn delete_range_cf(
+        &self,
+        col: Column,
+        from: Option<&[u8]>,
+        to: Option<&[u8]>,
+    ) -> Result<usize> {
+        self.db.delete_range(col, from, to)
     }
 }
 
-pub(crate) fn cf_handle(db: &DB, col: Column) -> Result<&ColumnFamily> {
-    db.cf_handle(col)
-        .ok_or_else(|| Error::storage(format!("column {col} not found")))
+#[cfg(test)]
+mod tests {
+    use eth2_types::MainnetEthSpec;
+    use rocksdb::{prelude::OpenCF as _, ColumnFamilyDescriptor, Options, DB};
+    use tempfile::TempDir;
+
+    use super::Storage;
+    use crate::{error::Error, schemas::columns};
+
+    #[test]
+    fn test_open_errors() {
+        let path = TempDir::new().unwrap();
+        {
+            let _storage = Storage::<MainnetEthSpec>::new(path.path()).unwrap();
+            // the database is locked by another instance, which is not about its version
+            let err = Storage::<MainnetEthSpec>::new(path.path()).unwrap_err();
+            assert!(matches!(err, Error::DB(_)), "{err}");
+        }
+        {
+            // a later version added a column family
+            let opts = {
+                let mut opts = Options::default();
+                opts.create_missing_column_families(true);
+                opts
+            };
+            let cf_descriptors: Vec<_> = columns::ALL
+                .iter()
+                .chain(["later-column"].iter())
+                .map(|col| ColumnFamilyDescriptor::new(*col, Options::default()))
+                .collect();
+            DB::open_cf_descriptors(&opts, path.path(), cf_descriptors).unwrap();
+        }
+        let err = Storage::<MainnetEthSpec>::new(path.path()).unwrap_err();
+        assert!(matches!(err, Error::Migration(_)), "{err}");
+    }
 }
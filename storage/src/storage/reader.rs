@@ -1,8 +1,9 @@
 use eth2_types::EthSpec;
 use eth_light_client_in_ckb_verification::types::{packed, prelude::*};
-use types::Update;
+use types::{Header, Update};
 
 use crate::{
+    backend::IterMode,
     error::{Error, Result},
     prelude::StorageReader,
     schemas::{columns, keys},
@@ -61,4 +62,86 @@ where
             .map(|raw| Update::from_slice(&raw).map_err(Error::storage))
             .transpose()
     }
+
+    fn get_submitted_transaction(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.get_cf(columns::COLUMN_SUBMITTED_TRANSACTIONS, key)
+    }
+
+    fn get_pending_submitted_transactions(&self) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let keys = self.iterate_cf(
+            columns::COLUMN_PENDING_SUBMITTED_TRANSACTIONS,
+            IterMode::Start,
+            usize::MAX,
+        )?;
+        let mut records = Vec::with_capacity(keys.len());
+        for (key, _) in keys {
+            if let Some(value) = self.get_submitted_transaction(&key)? {
+                records.push((key, value));
+            }
+        }
+        Ok(records)
+    }
+
+    fn get_last_watched_slot(&self) -> Result<Option<Slot>> {
+        self.get(keys::LAST_WATCHED_SLOT)?
+            .map(|raw| packed::Uint64Reader::from_slice(&raw).map(|reader| reader.unpack()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn get_watched_transactions(&self, limit: usize) -> Result<Vec<(u64, Vec<u8>)>> {
+        self.iterate_cf(columns::COLUMN_WATCHED_TRANSACTIONS, IterMode::Start, limit)?
+            .into_iter()
+            .map(|(key, value)| Ok((watched_transaction_index(&key)?, value)))
+            .collect()
+    }
+
+    fn get_next_watched_transaction_index(&self) -> Result<u64> {
+        if let Some(raw) = self.get(keys::NEXT_WATCHED_INDEX)? {
+            return Ok(packed::Uint64Reader::from_slice(&raw)?.unpack());
+        }
+        // the queue pushed before the counter is persisted
+        self.iterate_cf(columns::COLUMN_WATCHED_TRANSACTIONS, IterMode::End, 1)?
+            .first()
+            .map(|(key, _)| watched_transaction_index(key).map(|index| index + 1))
+            .unwrap_or(Ok(0))
+    }
+
+    fn get_transaction_proof(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+        tx_hash: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let key = keys::transaction_proof_key(minimal_slot, maximal_slot, tx_hash);
+        self.get_cf(columns::COLUMN_TRANSACTION_PROOFS, key)
+    }
+
+    fn get_backfilled_slot(&self) -> Result<Option<Slot>> {
+        self.get(keys::BACKFILLED_SLOT)?
+            .map(|raw| packed::Uint64Reader::from_slice(&raw).map(|reader| reader.unpack()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    fn get_backfilled_header(&self, slot: Slot) -> Result<Option<Header>> {
+        self.get_cf(columns::COLUMN_BACKFILLED_HEADERS, slot.to_be_bytes())?
+            .map(|raw| Header::from_slice(&raw).map_err(Error::storage))
+            .transpose()
+    }
+
+    fn get_light_client_store(&self) -> Result<Option<Vec<u8>>> {
+        self.get_cf(columns::COLUMN_LIGHT_CLIENT_STORE, keys::LIGHT_CLIENT_STORE)
+    }
+
+    fn get_last_checkpoint(&self) -> Result<Option<Vec<u8>>> {
+        self.get_cf(columns::COLUMN_LIGHT_CLIENT_STORE, keys::LAST_CHECKPOINT)
+    }
+}
+
+// The indexes of watched transactions are stored in big-endian, to keep the queue in order.
+fn watched_transaction_index(key: &[u8]) -> Result<u64> {
+    key.try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| Error::data("invalid index of watched transaction"))
 }
@@ -1,10 +1,10 @@
 use eth2_types::EthSpec;
 use eth_light_client_in_ckb_verification::types::{packed, prelude::*};
-use types::Update;
+use types::{Header, Update};
 
 use crate::{
     error::{Error, Result},
-    prelude::StorageWriter,
+    prelude::{StorageReader, StorageWriter},
     schemas::{columns, keys},
     Slot, Storage,
 };
@@ -59,4 +59,99 @@ where
         let bytes = update.clone().into_bytes().map_err(Error::storage)?;
         self.put_cf(columns::COLUMN_FINALIZED_UPDATES, key.as_slice(), bytes)
     }
+
+    fn put_submitted_transaction(&self, key: &[u8], value: &[u8], pending: bool) -> Result<()> {
+        let batch = self.batch();
+        batch.put_submitted_transaction(key, value, pending)?;
+        batch.commit()
+    }
+
+    fn put_last_watched_slot(&self, slot: Slot) -> Result<()> {
+        let value = slot.pack();
+        self.put(keys::LAST_WATCHED_SLOT, value.as_slice())
+    }
+
+    fn put_watched_transaction(&self, index: u64, value: &[u8]) -> Result<()> {
+        let batch = self.batch();
+        batch.put_watched_transaction(index, value)?;
+        batch.commit()
+    }
+
+    fn delete_watched_transaction(&self, index: u64) -> Result<()> {
+        self.delete_cf(columns::COLUMN_WATCHED_TRANSACTIONS, index.to_be_bytes())
+    }
+
+    fn put_transaction_proof(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+        tx_hash: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        let key = keys::transaction_proof_key(minimal_slot, maximal_slot, tx_hash);
+        self.put_cf(columns::COLUMN_TRANSACTION_PROOFS, key, value)
+    }
+
+    fn delete_stale_transaction_proofs(
+        &self,
+        minimal_slot: Slot,
+        maximal_slot: Slot,
+    ) -> Result<usize> {
+        let prefix = keys::transaction_proof_key(minimal_slot, maximal_slot, &[]);
+        // all keys of the kept range are less than the prefix followed by 0xff
+        let mut prefix_end = prefix.clone();
+        prefix_end.resize(prefix.len() + 33, 0xff);
+        let before =
+            self.delete_range_cf(columns::COLUMN_TRANSACTION_PROOFS, None, Some(&prefix))?;
+        let after =
+            self.delete_range_cf(columns::COLUMN_TRANSACTION_PROOFS, Some(&prefix_end), None)?;
+        Ok(before + after)
+    }
+
+    fn put_backfilled_header(&self, slot: Slot, header: &Header) -> Result<()> {
+        let bytes = header.to_bytes().map_err(Error::storage)?;
+        self.put_cf(
+            columns::COLUMN_BACKFILLED_HEADERS,
+            slot.to_be_bytes(),
+            bytes,
+        )?;
+        let value = slot.pack();
+        self.put(keys::BACKFILLED_SLOT, value.as_slice())
+    }
+
+    fn clear_backfilled_headers(&self) -> Result<()> {
+        self.delete(keys::BACKFILLED_SLOT)?;
+        self.delete_range_cf(columns::COLUMN_BACKFILLED_HEADERS, None, None)?;
+        Ok(())
+    }
+
+    fn truncate_backfilled_headers(&self, slot: Slot) -> Result<()> {
+        match self.get_backfilled_slot()? {
+            Some(backfilled_slot) if backfilled_slot >= slot => {}
+            _ => return Ok(()),
+        }
+        if slot == 0 || self.get_backfilled_header(slot - 1)?.is_none() {
+            return self.clear_backfilled_headers();
+        }
+        let from = slot.to_be_bytes();
+        self.delete_range_cf(columns::COLUMN_BACKFILLED_HEADERS, Some(&from), None)?;
+        let value = (slot - 1).pack();
+        self.put(keys::BACKFILLED_SLOT, value.as_slice())
+    }
+
+    fn put_light_client_store(&self, value: &[u8]) -> Result<()> {
+        self.put_cf(
+            columns::COLUMN_LIGHT_CLIENT_STORE,
+            keys::LIGHT_CLIENT_STORE,
+            value,
+        )
+    }
+
+    fn put_last_checkpoint(&self, checkpoint: &[u8]) -> Result<()> {
+        self.put_cf(
+            columns::COLUMN_LIGHT_CLIENT_STORE,
+            keys::LAST_CHECKPOINT,
+            checkpoint,
+        )
+    }
 }
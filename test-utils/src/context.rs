@@ -70,6 +70,10 @@ impl Context {
         }
     }
 
+    pub fn get_cell(&self, out_point: &packed::OutPoint) -> Option<(packed::CellOutput, Bytes)> {
+        self.cells.get(out_point).cloned()
+    }
+
     pub fn resolve(&self, transaction: TransactionView) -> ResolvedTransaction {
         let resolved_inputs = transaction
             .inputs()
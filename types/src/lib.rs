@@ -320,6 +320,18 @@ pub struct Header {
     pub body_root: Bytes32,
 }
 
+impl Header {
+    pub fn from_slice(bytes: &[u8]) -> StdResult<Self, DeserializeError> {
+        Self::deserialize(bytes)
+    }
+
+    pub fn to_bytes(&self) -> StdResult<Vec<u8>, SerializeError> {
+        let mut bytes = Vec::new();
+        self.serialize(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
 #[derive(Debug, Clone, Default, SimpleSerialize, serde::Deserialize)]
 pub struct SyncCommittee {
     #[serde(deserialize_with = "pubkeys_deserialize")]
@@ -335,6 +347,7 @@ pub struct SyncAggregate {
     pub sync_committee_signature: SignatureBytes,
 }
 
+#[derive(Clone)]
 pub struct GenericUpdate {
     pub attested_header: Header,
     pub sync_aggregate: SyncAggregate,